@@ -1,3 +1,4 @@
+use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
 
@@ -34,3 +35,66 @@ pub fn set_last_commit(branch_name: String, commit_hash: String) -> Result<(), B
         }
     }
 }
+
+/// The commit a ref file (`"ref\n<hash>"`, or just the hash) points to;
+/// `None` for a branch that has no commits yet.
+fn ref_target(content: &str) -> Option<String> {
+    let mut lines = content.lines();
+    let target = match lines.next() {
+        Some("ref") => lines.next(),
+        line => line,
+    };
+    target
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+        .map(String::from)
+}
+
+pub fn all_refs() -> Result<Vec<(String, String)>, BranchError> {
+    let mut refs = Vec::new();
+    for kind in &["heads", "tags"] {
+        let dir = String::from(".yit/refs/") + kind;
+        let entries = match fs::read_dir(&dir) {
+            Err(_) => return Err(BranchError::IOError),
+            Ok(entries) => entries,
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            match fs::read_to_string(entry.path()) {
+                Err(_) => return Err(BranchError::IOError),
+                Ok(content) => {
+                    if let Some(hash) = ref_target(&content) {
+                        refs.push((String::from("refs/") + kind + "/" + &name, hash));
+                    }
+                }
+            }
+        }
+    }
+    refs.sort();
+    Ok(refs)
+}
+
+pub fn head_ref() -> Result<Option<String>, BranchError> {
+    match fs::read_to_string(".yit/HEAD") {
+        Err(_) => Err(BranchError::IOError),
+        Ok(head) => Ok(head
+            .trim()
+            .strip_prefix(".yit/")
+            .filter(|name| name.starts_with("refs/"))
+            .map(String::from)),
+    }
+}
+
+/// Empty while the current branch has no commits.
+pub fn head_commit() -> Result<String, BranchError> {
+    match head_ref()? {
+        None => match fs::read_to_string(".yit/HEAD") {
+            Err(_) => Err(BranchError::IOError),
+            Ok(head) => Ok(String::from(head.trim())),
+        },
+        Some(name) => match fs::read_to_string(String::from(".yit/") + &name) {
+            Err(_) => Ok(String::from("")),
+            Ok(content) => Ok(ref_target(&content).unwrap_or_default()),
+        },
+    }
+}
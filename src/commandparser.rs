@@ -1,5 +1,223 @@
+use crate::config;
+use crate::encoding;
+use crate::log;
+use crate::message;
 use crate::repo;
+use crate::status;
+use std::fs;
 use std::io::{self, Write};
+use std::path::Path;
+
+/// Splits `log` arguments into the revisions to show and the options. Words
+/// before `--` are revisions unless they name an existing file.
+fn parse_log_args(args: &[&str]) -> Result<(Vec<String>, log::LogOptions), String> {
+    let mut revisions = Vec::new();
+    let mut options = log::LogOptions::default();
+    let parse_count = |value: &str| match value.parse::<usize>() {
+        Err(_) => Err(format!("Invalid count: {}", value)),
+        Ok(count) => Ok(Some(count)),
+    };
+    let parse_time = |value: &str| match log::parse_time(value) {
+        None => Err(format!("Invalid date: {}", value)),
+        Some(time) => Ok(Some(time)),
+    };
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i];
+        if arg == "--" {
+            options
+                .paths
+                .extend(args[i + 1..].iter().map(|path| String::from(*path)));
+            break;
+        } else if arg == "-n" {
+            i += 1;
+            options.max_count = parse_count(args.get(i).copied().unwrap_or(""))?;
+        } else if let Some(value) = arg.strip_prefix("--max-count=") {
+            options.max_count = parse_count(value)?;
+        } else if let Some(value) = arg
+            .strip_prefix("--since=")
+            .or_else(|| arg.strip_prefix("--after="))
+        {
+            options.since = parse_time(value)?;
+        } else if let Some(value) = arg
+            .strip_prefix("--until=")
+            .or_else(|| arg.strip_prefix("--before="))
+        {
+            options.until = parse_time(value)?;
+        } else if let Some(value) = arg.strip_prefix("--author=") {
+            options.author = Some(String::from(value));
+        } else if arg == "--oneline" || arg == "--pretty=oneline" {
+            options.format = log::Format::Oneline;
+        } else if arg == "--pretty=medium" {
+            options.format = log::Format::Medium;
+        } else if let Some(value) = arg
+            .strip_prefix("--format=")
+            .or_else(|| arg.strip_prefix("--pretty=format:"))
+        {
+            options.format = log::Format::Custom(String::from(value));
+        } else if arg == "--first-parent" {
+            options.first_parent = true;
+        } else if arg.len() > 1 && arg.starts_with('-') && arg[1..].parse::<usize>().is_ok() {
+            options.max_count = parse_count(&arg[1..])?;
+        } else if arg.starts_with('-') {
+            return Err(format!("Unknown option: {}", arg));
+        } else if Path::new(arg).exists() {
+            options.paths.push(String::from(arg));
+        } else {
+            revisions.push(String::from(arg));
+        }
+        i += 1;
+    }
+    Ok((revisions, options))
+}
+
+/// Backslashes escape the next character, except within single quotes.
+pub fn split_args(input: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some('\'') if c == '\'' => quote = None,
+            Some('"') if c == '"' => quote = None,
+            Some('"') if c == '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+                match chars.next() {
+                    None => return Err(String::from("Unterminated quote")),
+                    Some(next) if "\"\\$`".contains(next) => arg.push(next),
+                    Some(next) => {
+                        arg.push('\\');
+                        arg.push(next);
+                    }
+                }
+            }
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c == '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+                if let Some(next) = chars.next() {
+                    arg.push(next);
+                }
+            }
+            None => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(String::from("Unterminated quote"));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+struct CommitArgs {
+    /// `None` when the editor should be opened
+    message: Option<String>,
+    amend: bool,
+    no_edit: bool,
+}
+
+fn parse_commit_args(args: &[&str]) -> Result<CommitArgs, String> {
+    let mut amend = false;
+    let mut no_edit = false;
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut file: Option<String> = None;
+    let mut words: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i];
+        if arg == "--amend" {
+            amend = true;
+        } else if arg == "--no-edit" {
+            no_edit = true;
+        } else if arg == "-m" || arg == "--message" || arg == "-F" || arg == "--file" {
+            i += 1;
+            let value = match args.get(i) {
+                None => return Err(format!("Option {} requires a value", arg)),
+                Some(value) => String::from(*value),
+            };
+            if arg == "-F" || arg == "--file" {
+                file = Some(value);
+            } else {
+                paragraphs.push(value);
+            }
+        } else if let Some(value) = arg.strip_prefix("--message=") {
+            paragraphs.push(String::from(value));
+        } else if let Some(value) = arg.strip_prefix("--file=") {
+            file = Some(String::from(value));
+        } else if let Some(value) = arg.strip_prefix("-m") {
+            paragraphs.push(String::from(value));
+        } else if let Some(value) = arg.strip_prefix("-F") {
+            file = Some(String::from(value));
+        } else if arg.starts_with('-') {
+            return Err(format!("Unknown option: {}", arg));
+        } else {
+            words.push(arg);
+        }
+        i += 1;
+    }
+    if !words.is_empty() && (file.is_some() || !paragraphs.is_empty()) {
+        return Err(format!("Unexpected argument: {}", words[0]));
+    }
+    let message = match file {
+        Some(_) if !paragraphs.is_empty() => {
+            return Err(String::from("Options -m and -F cannot be combined"))
+        }
+        Some(file) => match fs::read_to_string(&file) {
+            Err(_) => return Err(format!("Could not read file: {}", file)),
+            Ok(content) => Some(content),
+        },
+        None if !paragraphs.is_empty() => Some(paragraphs.join("\n\n")),
+        None if !words.is_empty() => Some(words.join(" ")),
+        None => None,
+    };
+    Ok(CommitArgs {
+        message,
+        amend,
+        no_edit,
+    })
+}
+
+/// The revisions `diff` compares. `A..B` is `A B`, and `A...B` compares B
+/// with its merge base with A. A missing side of a range is HEAD.
+pub fn diff_revisions(repo: &repo::Repository, args: &[&str]) -> Result<(String, String), String> {
+    let or_head = |side: &str| {
+        if side.is_empty() {
+            String::from("HEAD")
+        } else {
+            String::from(side)
+        }
+    };
+    match args {
+        [] => Err(String::from("Too few arguments! Try: diff <rev1> <rev2>")),
+        [range] => {
+            if let Some((first, second)) = range.split_once("...") {
+                let second = or_head(second);
+                return match repo.clone().merge_bases(or_head(first), second.clone()) {
+                    Err(repo::RepoError::RevisionError(err)) => Err(err.to_string()),
+                    Err(_) => Err(String::from("Error reading history.")),
+                    Ok(bases) => match bases.into_iter().next() {
+                        None => Err(format!("{} has no merge base", range)),
+                        Some(base) => Ok((base, second)),
+                    },
+                };
+            }
+            match range.split_once("..") {
+                None => Err(String::from("Too few arguments! Try: diff <rev1> <rev2>")),
+                Some((first, second)) => Ok((or_head(first), or_head(second))),
+            }
+        }
+        [first, second, ..] => Ok((String::from(*first), String::from(*second))),
+    }
+}
 
 pub fn read_command() {
     let repo = repo::Repository::new();
@@ -10,37 +228,233 @@ pub fn read_command() {
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
             Err(e) => println!("{}", e),
+            Ok(0) => break,
             Ok(_) => {
-                let words: Vec<&str> = input.split_whitespace().collect();
+                let args = match split_args(&input) {
+                    Err(message) => {
+                        println!("{}", message);
+                        continue;
+                    }
+                    Ok(args) if args.is_empty() => continue,
+                    Ok(args) => args,
+                };
+                let words: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
                 let command = words[0];
                 if command == "init" {
-                    match repo.clone().init() {
+                    let mut config = config::Config::new();
+                    if words[1..].contains(&"--git-compat") {
+                        config.set("core.gitcompat", "true");
+                    }
+                    let format = words[1..]
+                        .iter()
+                        .find_map(|word| word.strip_prefix("--object-format="));
+                    if let Some(format) = format {
+                        match encoding::ObjectFormat::parse(format) {
+                            None => {
+                                println!("Unknown object format! Try: sha1 or sha256");
+                                continue;
+                            }
+                            Some(format) => config.set("extensions.objectformat", format.as_str()),
+                        }
+                    }
+                    match repo.clone().init_with(config) {
                         Err(_) => println!("Error initializing."),
                         Ok(_) => println!("Successfully initialized repo."),
                     }
                 } else if command == "add" {
-                    if words.len() < 2 {
-                        println!("Too few arguments! Try: add <file>");
-                    } else {
-                        match repo.clone().add(String::from(words[1])) {
-                            Err(_) => println!("Error adding file."),
-                            Ok(_) => println!("Successfully added file."),
+                    let mut options = repo::AddOptions::default();
+                    let mut pathspecs = Vec::new();
+                    let mut patch = false;
+                    let mut valid = true;
+                    for (i, arg) in words.iter().enumerate().skip(1) {
+                        match *arg {
+                            "-p" | "--patch" => patch = true,
+                            "-A" | "--all" => options.all = true,
+                            "-u" | "--update" => options.update = true,
+                            "-f" | "--force" => options.force = true,
+                            "--" => {
+                                pathspecs.extend(words[i + 1..].iter().map(|w| String::from(*w)));
+                                break;
+                            }
+                            _ if arg.starts_with('-') => {
+                                println!("Unknown option: {}", arg);
+                                valid = false;
+                            }
+                            _ => pathspecs.push(String::from(*arg)),
+                        }
+                    }
+                    if !valid {
+                        continue;
+                    }
+                    if patch {
+                        let stdin = io::stdin();
+                        match repo.clone().add_patch(
+                            pathspecs,
+                            &mut stdin.lock(),
+                            &mut io::stdout(),
+                        ) {
+                            Err(_) => println!("Error adding files."),
+                            Ok(staged) => {
+                                for path in staged {
+                                    println!("add '{}'", path);
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    if pathspecs.is_empty() && !options.all && !options.update {
+                        println!("Nothing specified, nothing added. Try: add <pathspec>...");
+                        continue;
+                    }
+                    match repo.clone().add_paths(pathspecs, options) {
+                        Err(repo::RepoError::UntrackedPath(path)) => {
+                            println!("pathspec '{}' did not match any files", path)
+                        }
+                        Err(repo::RepoError::IgnoredPath(path)) => println!(
+                            "'{}' is ignored, see check-ignore -v. Use -f to add it anyway.",
+                            path
+                        ),
+                        Err(_) => println!("Error adding files."),
+                        Ok(staged) => {
+                            for (action, path) in staged {
+                                println!("{} '{}'", action, path);
+                            }
                         }
                     }
                 } else if command == "commit" {
-                    if words.len() < 2 {
-                        println!("Too few arguments! Try: commit <message>");
+                    let args = match parse_commit_args(&words[1..]) {
+                        Err(message) => {
+                            println!("{}", message);
+                            continue;
+                        }
+                        Ok(args) => args,
+                    };
+                    let message = match args.message {
+                        Some(message) => Some(message::cleanup(&message, false)),
+                        None if args.amend && args.no_edit => None,
+                        None => match repo.clone().edit_commit_message(args.amend) {
+                            Err(repo::RepoError::EditorError(
+                                message::MessageError::EditorFailed(editor),
+                            )) => {
+                                println!("There was a problem with the editor '{}'.", editor);
+                                continue;
+                            }
+                            Err(_) => {
+                                println!("Error commiting.");
+                                continue;
+                            }
+                            Ok(message) => Some(message),
+                        },
+                    };
+                    if message.as_deref() == Some("") {
+                        println!("Aborting commit due to empty commit message.");
+                    } else if args.amend {
+                        match repo.clone().amend(message) {
+                            Err(_) => println!("Error amending commit."),
+                            Ok(_) => println!("Successfully amended commit."),
+                        }
                     } else {
-                        match repo.clone().commit(String::from(words[1])) {
+                        match repo.clone().commit(message.unwrap_or_default()) {
                             Err(_) => println!("Error commiting."),
                             Ok(_) => println!("Successful commit."),
                         }
                     }
+                } else if command == "status" {
+                    let mut format = status::Format::Long;
+                    let mut show_branch = false;
+                    let mut valid = true;
+                    for arg in &words[1..] {
+                        match *arg {
+                            "-s" | "--short" => format = status::Format::Short,
+                            "--porcelain" => format = status::Format::Porcelain,
+                            "-b" | "--branch" => show_branch = true,
+                            _ => {
+                                println!("Unknown option: {}", arg);
+                                valid = false;
+                            }
+                        }
+                    }
+                    if valid {
+                        match repo.clone().status() {
+                            Err(_) => println!("Error reading status."),
+                            Ok(result) => {
+                                print!("{}", status::format_status(&result, format, show_branch))
+                            }
+                        }
+                    }
+                } else if command == "check-ignore" {
+                    let verbose = words[1..].iter().any(|w| *w == "-v" || *w == "--verbose");
+                    let non_matching = words[1..]
+                        .iter()
+                        .any(|w| *w == "-n" || *w == "--non-matching");
+                    let no_index = words[1..].contains(&"--no-index");
+                    let paths: Vec<String> = words[1..]
+                        .iter()
+                        .filter(|word| !word.starts_with('-'))
+                        .map(|word| String::from(*word))
+                        .collect();
+                    if paths.is_empty() {
+                        println!("Too few arguments! Try: check-ignore [-v] [-n] <path>...");
+                        continue;
+                    }
+                    match repo.clone().check_ignore(paths, no_index) {
+                        Err(_) => println!("Error reading ignore rules."),
+                        Ok(results) => {
+                            for (path, rule) in results {
+                                match rule {
+                                    Some(rule) if verbose => println!(
+                                        "{}:{}:{}\t{}",
+                                        rule.source, rule.line, rule.text, path
+                                    ),
+                                    Some(rule) if !rule.negated => println!("{}", path),
+                                    None if verbose && non_matching => println!("::\t{}", path),
+                                    _ => {}
+                                }
+                            }
+                        }
+                    }
+                } else if command == "rm" {
+                    let cached = words[1..].contains(&"--cached");
+                    let paths: Vec<String> = words[1..]
+                        .iter()
+                        .filter(|word| **word != "--cached")
+                        .map(|word| String::from(*word))
+                        .collect();
+                    if paths.is_empty() {
+                        println!("Too few arguments! Try: rm [--cached] <file>...");
+                    } else {
+                        match repo.clone().rm(paths, cached) {
+                            Err(repo::RepoError::UntrackedPath(path)) => {
+                                println!("pathspec '{}' did not match any tracked files", path)
+                            }
+                            Err(_) => println!("Error removing files."),
+                            Ok(_) => println!("Successfully removed files."),
+                        }
+                    }
+                } else if command == "mv" {
+                    if words.len() < 3 {
+                        println!("Too few arguments! Try: mv <source> <destination>");
+                    } else {
+                        match repo
+                            .clone()
+                            .mv(String::from(words[1]), String::from(words[2]))
+                        {
+                            Err(repo::RepoError::UntrackedPath(path)) => {
+                                println!("'{}' is not tracked", path)
+                            }
+                            Err(repo::RepoError::DestinationExists(path)) => {
+                                println!("Destination '{}' already exists", path)
+                            }
+                            Err(_) => println!("Error moving file."),
+                            Ok(_) => println!("Successfully moved file."),
+                        }
+                    }
                 } else if command == "checkout" {
                     if words.len() < 2 {
                         println!("Too few arguments! Try: checkout <branch-name>");
                     } else {
                         match repo.clone().checkout(String::from(words[1])) {
+                            Err(repo::RepoError::RevisionError(err)) => println!("{}", err),
                             Err(_) => println!("Error in checkout."),
                             Ok(_) => println!("Successful checkout to {}", words[1]),
                         }
@@ -53,32 +467,147 @@ pub fn read_command() {
                             .clone()
                             .merge(String::from(words[1]), String::from(words[2]))
                         {
+                            Err(repo::RepoError::RevisionError(err)) => println!("{}", err),
                             Err(_) => println!("Error merging"),
                             Ok(_) => println!("Successfully merged {} into {}", words[1], words[2]),
                         }
                     }
-                } else if command == "diff" {
-                    if words.len() < 3 {
-                        println!("Too few arguments! Try: diff <branch1> <branch2>");
+                } else if command == "merge-base" {
+                    let is_ancestor = words[1..].contains(&"--is-ancestor");
+                    let all = words[1..].contains(&"--all");
+                    let revisions: Vec<&str> = words[1..]
+                        .iter()
+                        .filter(|word| !word.starts_with("--"))
+                        .copied()
+                        .collect();
+                    if revisions.len() != 2 {
+                        println!("Two revisions needed! Try: merge-base [--all] <rev1> <rev2>");
+                    } else if is_ancestor {
+                        match repo
+                            .clone()
+                            .is_ancestor(String::from(revisions[0]), String::from(revisions[1]))
+                        {
+                            Err(repo::RepoError::RevisionError(err)) => println!("{}", err),
+                            Err(_) => println!("Error reading history."),
+                            Ok(true) => {
+                                println!("{} is an ancestor of {}", revisions[0], revisions[1])
+                            }
+                            Ok(false) => {
+                                println!("{} is not an ancestor of {}", revisions[0], revisions[1])
+                            }
+                        }
                     } else {
                         match repo
                             .clone()
-                            .diff(String::from(words[1]), String::from(words[2]))
+                            .merge_bases(String::from(revisions[0]), String::from(revisions[1]))
                         {
-                            Err(_) => println!("Error merging"),
-                            Ok(_) => println!("End diff between {} and {}", words[1], words[2]),
+                            Err(repo::RepoError::RevisionError(err)) => println!("{}", err),
+                            Err(_) => println!("Error reading history."),
+                            Ok(bases) if bases.is_empty() => println!("No common ancestor."),
+                            Ok(bases) => {
+                                let shown = if all { bases.len() } else { 1 };
+                                for base in &bases[..shown] {
+                                    println!("{}", base);
+                                }
+                            }
+                        }
+                    }
+                } else if command == "diff" {
+                    match diff_revisions(&repo, &words[1..]) {
+                        Err(message) => println!("{}", message),
+                        Ok((first, second)) => {
+                            match repo.clone().diff(first.clone(), second.clone()) {
+                                Err(repo::RepoError::RevisionError(err)) => println!("{}", err),
+                                Err(_) => println!("Error merging"),
+                                Ok(_) => println!("End diff between {} and {}", first, second),
+                            }
                         }
                     }
+                } else if command == "gc" {
+                    let prune = words[1..]
+                        .iter()
+                        .find(|word| word.starts_with("--prune="))
+                        .map(|word| String::from(word.trim_start_matches("--prune=")));
+                    match repo.clone().gc(prune) {
+                        Err(_) => println!("Error collecting garbage."),
+                        Ok(report) => println!(
+                            "Packed {} objects, pruned {} unreachable objects.",
+                            report.packed, report.pruned
+                        ),
+                    }
+                } else if command == "fsck" {
+                    match repo.clone().fsck() {
+                        Err(_) => println!("Error checking repository."),
+                        Ok(report) => {
+                            for problem in &report.problems {
+                                println!("{}", problem);
+                            }
+                            println!(
+                                "Checked {} objects, found {} problems.",
+                                report.checked,
+                                report.problems.len()
+                            );
+                        }
+                    }
+                } else if command == "log" {
+                    match parse_log_args(&words[1..]) {
+                        Err(message) => println!("{}", message),
+                        Ok((revisions, options)) => match repo.clone().log(revisions, options) {
+                            Err(repo::RepoError::RevisionError(err)) => println!("{}", err),
+                            Err(_) => println!("Error reading history."),
+                            Ok(output) => print!("{}", output),
+                        },
+                    }
                 } else if command == "quit" {
                     break;
                 } else if command == "help" {
                     println!("Available commands:");
-                    println!("  init                            Initialize a new repo");
-                    println!("  add      <file>                 Add a new file to be committed");
-                    println!("  commit   <message>              Commit the added files");
-                    println!("  checkout <branch-name>          Check out the given branch");
+                    println!("  init     [--git-compat]         Initialize a new repo");
+                    println!("           [--object-format=<f>]  Hash objects with sha1 (default) or sha256");
+                    println!("  add      [-A|-u] [-f] <pathspec>...");
+                    println!("                                  Stage files, directories or globs like '*.rs';");
+                    println!("                                  -A stages every change, -u only tracked files");
+                    println!(
+                        "  add      -p [<pathspec>...]     Choose hunks of tracked files to stage"
+                    );
+                    println!("  status   [-s|--porcelain] [-b]  Show staged, unstaged and untracked files");
+                    println!("  check-ignore [-v] [-n] [--no-index] <path>...");
+                    println!("                                  Show which paths .yitignore and .yit/info/exclude");
+                    println!(
+                        "                                  ignore; -v names the rule that matched"
+                    );
+                    println!("  rm       [--cached] <file>...   Remove files from the next commit and the worktree,");
+                    println!("                                  or only from the next commit with --cached");
+                    println!(
+                        "  mv       <source> <destination> Move or rename a file or directory"
+                    );
+                    println!("  commit   [--amend [--no-edit]] [-m <msg>]... [-F <file>]");
+                    println!("                                  Commit the added files; each -m adds a paragraph,");
+                    println!(
+                        "                                  without a message $EDITOR is opened; --amend"
+                    );
+                    println!("                                  replaces the last commit, --no-edit keeps its message");
+                    println!(
+                        "  checkout <branch|commit>        Check out the given branch or commit"
+                    );
                     println!("  merge    <branch> <into-branch> Merge the first branch into the second one");
-                    println!("  diff     <branch1> <branch2>    Diff the two branches");
+                    println!("  merge-base [--all] <rev1> <rev2>");
+                    println!("                                  Show the best common ancestor(s) of two commits");
+                    println!("  merge-base --is-ancestor <rev1> <rev2>");
+                    println!("                                  Check whether rev1 is an ancestor of rev2");
+                    println!("  diff     <rev1> <rev2>          Diff the two revisions (also: diff <rev1>..<rev2>, <rev1>...<rev2>)");
+                    println!("  gc       [--prune=<expire>]     Pack reachable objects, prune unreachable ones");
+                    println!("  fsck                            Verify the integrity of the object database");
+                    println!("  log      [<rev>...] [-- <path>...]");
+                    println!("                                  Show the commit history; options: -n <count>,");
+                    println!("                                  --since=<date>, --until=<date>, --author=<pattern>,");
+                    println!("                                  --oneline, --format=<format>, --first-parent");
+                    println!();
+                    println!("Revisions: HEAD, <branch>, <tag>, <id>, followed by ~<n> (n-th first parent),");
+                    println!("^<n> (n-th parent) or @{{<n>|<date>}} (earlier value of a branch); @{{-<n>}} is the");
+                    println!("n-th branch checked out before. log also takes A..B, A...B and ^A.");
+                    println!();
+                    println!("Arguments with spaces can be quoted: commit -m \"Fix the build\"");
                 } else {
                     println!("Unknown command. Try `help` to get a list of valid commands");
                 }
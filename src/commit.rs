@@ -1,115 +1,327 @@
-use crate::file;
-use crypto::digest::Digest;
-use crypto::sha1::Sha1;
-use miniz_oxide::deflate::compress_to_vec;
-use std::fs;
-use std::fs::File;
-use std::io::prelude::*;
+use crate::commitgraph;
+use crate::identity;
+use crate::object;
+use crate::store;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::rc::Rc;
 
 pub enum CommitError {
     IOError,
+    InvalidId,
+    MissingCommit(String),
 }
 
 #[derive(std::clone::Clone)]
 pub struct CommitNode {
-    pub parents: Vec<CommitNode>,
+    pub parents: Vec<String>,
     pub hash: String,
     pub tree_hash: String,
+    /// Missing on commits written before identities were recorded.
+    pub author: Option<identity::Signature>,
+    pub committer: Option<identity::Signature>,
+    /// committer time; commits written before identities were recorded sort
+    /// as the oldest
+    pub time: i64,
+    /// one more than the largest generation of the parents, or
+    /// `GENERATION_INFINITY` when the commit is not in the commit-graph file
+    pub generation: u32,
 }
 
+/// Generation of commits whose number is not known; it sorts above all others.
+pub const GENERATION_INFINITY: u32 = u32::MAX;
+
 pub fn write_commit(
+    store: &dyn store::ObjectStore,
     message: String,
     parents: Vec<String>,
     tree_hash: String,
+    author: &identity::Signature,
+    committer: &identity::Signature,
 ) -> Result<String, CommitError> {
-    let mut content = String::from("commit\n");
-    content.push_str(&tree_hash);
-    content.push_str("\n");
+    let tree = match store::ObjectId::from_hex(&tree_hash, store.format()) {
+        None => return Err(CommitError::InvalidId),
+        Some(tree) => tree,
+    };
+    let mut parent_ids = Vec::new();
     for parent in parents {
-        content.push_str(&parent);
-        content.push_str("\n");
-    }
-    content.push_str("\n");
-    content.push_str(&message);
-    let mut hasher = Sha1::new();
-    hasher.input_str(&content);
-    let hash = hasher.result_str();
-
-    let dir = &hash[0..2];
-    let filename = &hash[2..];
-    let res = fs::create_dir_all(String::from(".yit/objects/") + dir);
-    if res.is_err() {
-        return Err(CommitError::IOError);
-    }
-    match File::create(String::from(".yit/objects/") + dir + "/" + filename) {
+        if parent.is_empty() {
+            continue;
+        }
+        match store::ObjectId::from_hex(&parent, store.format()) {
+            None => return Err(CommitError::InvalidId),
+            Some(id) => parent_ids.push(id),
+        }
+    }
+    let commit = object::Object::Commit(object::Commit {
+        tree,
+        parents: parent_ids,
+        headers: vec![
+            (String::from("author"), author.to_string()),
+            (String::from("committer"), committer.to_string()),
+        ],
+        message,
+    });
+    match store.write(&commit) {
         Err(_) => Err(CommitError::IOError),
-        Ok(mut file) => {
-            let compressed = compress_to_vec(content.as_bytes(), 0);
-            let res_wr = file.write_all(&compressed);
-            if res_wr.is_err() {
-                return Err(CommitError::IOError);
+        Ok(id) => Ok(id.to_string()),
+    }
+}
+
+pub fn read_message(store: &dyn store::ObjectStore, hash: &str) -> Result<String, CommitError> {
+    match store::ObjectId::from_hex(hash, store.format()) {
+        None => Err(CommitError::InvalidId),
+        Some(id) => match store.read(&id) {
+            Ok(object::Object::Commit(commit)) => Ok(commit.message),
+            _ => Err(CommitError::MissingCommit(String::from(hash))),
+        },
+    }
+}
+
+impl CommitNode {
+    /// The parents are left to be loaded on demand.
+    pub fn read(store: &dyn store::ObjectStore, hash: &str) -> Result<Self, CommitError> {
+        let id = match store::ObjectId::from_hex(hash, store.format()) {
+            None => return Err(CommitError::InvalidId),
+            Some(id) => id,
+        };
+        match store.read(&id) {
+            Ok(object::Object::Commit(commit)) => {
+                let committer = commit
+                    .header("committer")
+                    .and_then(identity::Signature::parse);
+                Ok(CommitNode {
+                    hash: String::from(hash),
+                    tree_hash: commit.tree.to_string(),
+                    author: commit.header("author").and_then(identity::Signature::parse),
+                    time: committer.as_ref().map_or(0, |committer| committer.time),
+                    committer,
+                    parents: commit
+                        .parents
+                        .iter()
+                        .map(|parent| parent.to_string())
+                        .collect(),
+                    generation: GENERATION_INFINITY,
+                })
             }
-            Ok(hash)
+            _ => Err(CommitError::MissingCommit(String::from(hash))),
         }
     }
+
+    /// Queue position: children before their parents, then newest first.
+    fn priority(&self) -> (u32, i64) {
+        (self.generation, self.time)
+    }
 }
 
-impl CommitNode {
-    pub fn new(hash: String) -> Self {
-        match file::cat_file(hash.clone()) {
-            Err(_) => CommitNode {
-                hash: String::from(""),
-                tree_hash: String::from(""),
-                parents: Vec::new(),
-            },
-            Ok(file_content) => {
-                let words: Vec<&str> = file_content.split('\n').collect();
-                let tree_hash = words[0];
-                let mut parents: Vec<CommitNode> = Vec::new();
-                for word in (words[1..]).to_vec() {
-                    if word.is_empty() {
-                        return CommitNode {
-                            hash: hash.clone(),
-                            tree_hash: String::from(tree_hash),
-                            parents,
-                        };
-                    }
-                    parents.push(CommitNode::new(String::from(word)));
+/// Commits are read the first time they are asked for, from the commit-graph
+/// file when one is given and holds them.
+pub struct CommitGraph<'a> {
+    store: &'a dyn store::ObjectStore,
+    cache: Option<commitgraph::GraphFile>,
+    nodes: HashMap<String, Rc<CommitNode>>,
+}
+
+impl<'a> CommitGraph<'a> {
+    pub fn new(store: &'a dyn store::ObjectStore) -> Self {
+        CommitGraph {
+            store,
+            cache: None,
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn with_cache(mut self, cache: commitgraph::GraphFile) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn get(&mut self, hash: &str) -> Result<Rc<CommitNode>, CommitError> {
+        if let Some(node) = self.nodes.get(hash) {
+            return Ok(node.clone());
+        }
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(hash));
+        let node = match cached {
+            Some(node) => Rc::new(node.clone()),
+            None => Rc::new(CommitNode::read(self.store, hash)?),
+        };
+        self.nodes.insert(String::from(hash), node.clone());
+        Ok(node)
+    }
+
+    /// Every commit comes before its parents, and otherwise newest first.
+    pub fn ancestors<'g>(&'g mut self, start: &str) -> Ancestors<'g, 'a> {
+        self.ancestors_of(&[String::from(start)])
+    }
+
+    pub fn ancestors_of<'g>(&'g mut self, starts: &[String]) -> Ancestors<'g, 'a> {
+        Ancestors {
+            graph: self,
+            queue: BinaryHeap::new(),
+            seen: HashSet::new(),
+            order: 0,
+            pending: starts.to_vec(),
+            first_parent: false,
+        }
+    }
+
+    /// A commit is its own ancestor.
+    pub fn is_ancestor(&mut self, ancestor: &str, descendant: &str) -> Result<bool, CommitError> {
+        let ancestor = self.get(ancestor)?;
+        for node in self.ancestors(descendant) {
+            let node = node?;
+            if node.hash == ancestor.hash {
+                return Ok(true);
+            }
+            // everything still to come has a lower generation, so it cannot
+            // reach the ancestor either
+            if node.generation < ancestor.generation {
+                return Ok(false);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Newest first; criss-cross merges have more than one.
+    pub fn merge_bases(&mut self, first: &str, second: &str) -> Result<Vec<String>, CommitError> {
+        const FIRST: u8 = 1;
+        const SECOND: u8 = 2;
+        // reachable from a common ancestor, so not a best one
+        const STALE: u8 = 4;
+
+        let mut flags: HashMap<String, u8> = HashMap::new();
+        let mut queue: BinaryHeap<((u32, i64), i64, String)> = BinaryHeap::new();
+        // how often each commit is queued, and how many queued entries are
+        // of commits not stale yet
+        let mut queued: HashMap<String, usize> = HashMap::new();
+        let mut nonstale = 0;
+        let mut order = 0;
+        for (hash, flag) in [(first, FIRST), (second, SECOND)] {
+            let node = self.get(hash)?;
+            *flags.entry(node.hash.clone()).or_insert(0) |= flag;
+            *queued.entry(node.hash.clone()).or_insert(0) += 1;
+            nonstale += 1;
+            order -= 1;
+            queue.push((node.priority(), order, node.hash.clone()));
+        }
+
+        // walk down from both sides, newest first, until everything left is
+        // reachable from a common ancestor already found
+        let mut candidates: Vec<String> = Vec::new();
+        while nonstale > 0 {
+            let (_, _, hash) = match queue.pop() {
+                None => break,
+                Some(entry) => entry,
+            };
+            if let Some(count) = queued.get_mut(&hash) {
+                *count -= 1;
+            }
+            let mut flag = flags[&hash];
+            if flag & STALE == 0 {
+                nonstale -= 1;
+            }
+            if flag & (FIRST | SECOND) == FIRST | SECOND {
+                if flag & STALE == 0 {
+                    candidates.push(hash.clone());
+                    nonstale -= queued[&hash];
+                }
+                flag |= STALE;
+                flags.insert(hash.clone(), flag);
+            }
+            let node = self.get(&hash)?;
+            for parent in &node.parents {
+                let parent_flag = flags.get(parent).copied().unwrap_or(0);
+                if parent_flag & flag == flag {
+                    continue;
+                }
+                let parent_node = self.get(parent)?;
+                let count = queued.entry(parent.clone()).or_insert(0);
+                if parent_flag & STALE == 0 && flag & STALE != 0 {
+                    nonstale -= *count;
                 }
-                CommitNode {
-                    hash: String::from(""),
-                    tree_hash: String::from(""),
-                    parents: Vec::new(),
+                *count += 1;
+                if (parent_flag | flag) & STALE == 0 {
+                    nonstale += 1;
                 }
+                flags.insert(parent.clone(), parent_flag | flag);
+                order -= 1;
+                queue.push((parent_node.priority(), order, parent.clone()));
             }
         }
-    }
 
-    pub fn is_parent(self, commit_hash: &str) -> bool {
-        let mut is_parrent = false;
-        for parent in self.parents {
-            if parent.hash == commit_hash {
-                return true;
+        // with skewed commit dates a candidate can still be an ancestor of
+        // another one
+        let mut bases = Vec::new();
+        for candidate in &candidates {
+            let mut redundant = false;
+            for other in &candidates {
+                if other != candidate && self.is_ancestor(candidate, other)? {
+                    redundant = true;
+                    break;
+                }
             }
-            if !is_parrent {
-                is_parrent = is_parrent && parent.is_parent(commit_hash);
+            if !redundant {
+                bases.push(candidate.clone());
             }
         }
-        return is_parrent;
+        Ok(bases)
     }
 
-    pub fn get_most_recent_parent_commit(self, other: CommitNode) -> Option<CommitNode> {
-        if other.clone().is_parent(&self.clone().hash) {
-            return Some(self.clone());
+    pub fn merge_base(&mut self, first: &str, second: &str) -> Result<Option<String>, CommitError> {
+        Ok(self.merge_bases(first, second)?.into_iter().next())
+    }
+}
+
+pub struct Ancestors<'g, 'a> {
+    graph: &'g mut CommitGraph<'a>,
+    // ordered by `CommitNode::priority`, then by insertion so ties keep
+    // parent order
+    queue: BinaryHeap<((u32, i64), i64, String)>,
+    seen: HashSet<String>,
+    order: i64,
+    pending: Vec<String>,
+    first_parent: bool,
+}
+
+impl Ancestors<'_, '_> {
+    /// Leaves out `hashes`, and doesn't walk past them.
+    pub fn hide(mut self, hashes: impl IntoIterator<Item = String>) -> Self {
+        self.seen.extend(hashes);
+        self
+    }
+
+    pub fn first_parent(mut self) -> Self {
+        self.first_parent = true;
+        self
+    }
+}
+
+impl Iterator for Ancestors<'_, '_> {
+    type Item = Result<Rc<CommitNode>, CommitError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for hash in std::mem::take(&mut self.pending) {
+            if !self.seen.insert(hash.clone()) {
+                continue;
+            }
+            match self.graph.get(&hash) {
+                Err(err) => return Some(Err(err)),
+                Ok(node) => {
+                    self.order -= 1;
+                    self.queue.push((node.priority(), self.order, hash));
+                }
+            }
         }
-        let mut parent_option: Option<CommitNode> = None;
-        for parent in self.parents {
-            if parent_option.is_none() {
-                parent_option = parent.get_most_recent_parent_commit(other.clone());
+        let (_, _, hash) = self.queue.pop()?;
+        // already loaded when it was queued
+        let node = self.graph.get(&hash);
+        if let Ok(node) = &node {
+            let parents = if self.first_parent {
+                1
             } else {
-                return parent_option;
-            }
+                node.parents.len()
+            };
+            self.pending = node.parents.iter().take(parents).cloned().collect();
         }
-        return parent_option;
+        Some(node)
     }
 }
@@ -0,0 +1,243 @@
+use crate::commit;
+use crate::encoding;
+use crate::store;
+use std::collections::HashMap;
+use std::fs;
+
+#[derive(Debug)]
+pub enum CommitGraphError {
+    IOError,
+    Corrupt,
+    MissingCommit(String),
+}
+
+const GRAPH_MAGIC: [u8; 4] = *b"YCGR";
+const GRAPH_VERSION: u32 = 1;
+
+/// The file holds a `YCGR` header, the version and the number of commits,
+/// the sorted commit ids, then for each commit its tree id, parent count,
+/// parent positions, generation and commit time, and a trailing checksum.
+#[derive(Default)]
+pub struct GraphFile {
+    nodes: HashMap<String, commit::CommitNode>,
+}
+
+fn be_u32(data: &[u8], pos: &mut usize) -> Result<u32, CommitGraphError> {
+    match data.get(*pos..*pos + 4) {
+        None => Err(CommitGraphError::Corrupt),
+        Some(bytes) => {
+            *pos += 4;
+            Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+    }
+}
+
+fn be_i64(data: &[u8], pos: &mut usize) -> Result<i64, CommitGraphError> {
+    let high = be_u32(data, pos)? as u64;
+    let low = be_u32(data, pos)? as u64;
+    Ok(((high << 32) | low) as i64)
+}
+
+fn raw_id(data: &[u8], pos: &mut usize, id_len: usize) -> Result<String, CommitGraphError> {
+    match data.get(*pos..*pos + id_len) {
+        None => Err(CommitGraphError::Corrupt),
+        Some(raw) => {
+            *pos += id_len;
+            Ok(encoding::bytes_to_hex(raw))
+        }
+    }
+}
+
+fn checksum(format: encoding::ObjectFormat, data: &[u8]) -> Vec<u8> {
+    let mut hasher = format.hasher();
+    hasher.input(data);
+    let mut checksum = vec![0; format.raw_len()];
+    hasher.result(&mut checksum);
+    checksum
+}
+
+impl GraphFile {
+    pub fn new() -> Self {
+        GraphFile {
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn open(path: &str, format: encoding::ObjectFormat) -> Result<Self, CommitGraphError> {
+        let id_len = format.raw_len();
+        let data = match fs::read(path) {
+            Err(_) => return Err(CommitGraphError::IOError),
+            Ok(data) => data,
+        };
+        if data.len() < 12 + id_len || data[0..4] != GRAPH_MAGIC {
+            return Err(CommitGraphError::Corrupt);
+        }
+        let (content, trailer) = data.split_at(data.len() - id_len);
+        if checksum(format, content) != trailer {
+            return Err(CommitGraphError::Corrupt);
+        }
+        let mut pos = 4;
+        if be_u32(content, &mut pos)? != GRAPH_VERSION {
+            return Err(CommitGraphError::Corrupt);
+        }
+        let count = be_u32(content, &mut pos)? as usize;
+        let mut ids = Vec::new();
+        for _ in 0..count {
+            ids.push(raw_id(content, &mut pos, id_len)?);
+        }
+        let mut nodes = HashMap::new();
+        for hash in &ids {
+            let tree_hash = raw_id(content, &mut pos, id_len)?;
+            let mut parents = Vec::new();
+            for _ in 0..be_u32(content, &mut pos)? {
+                match ids.get(be_u32(content, &mut pos)? as usize) {
+                    None => return Err(CommitGraphError::Corrupt),
+                    Some(parent) => parents.push(parent.clone()),
+                }
+            }
+            let generation = be_u32(content, &mut pos)?;
+            let time = be_i64(content, &mut pos)?;
+            nodes.insert(
+                hash.clone(),
+                commit::CommitNode {
+                    hash: hash.clone(),
+                    tree_hash,
+                    parents,
+                    author: None,
+                    committer: None,
+                    time,
+                    generation,
+                },
+            );
+        }
+        if pos != content.len() {
+            return Err(CommitGraphError::Corrupt);
+        }
+        Ok(GraphFile { nodes })
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&commit::CommitNode> {
+        self.nodes.get(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns how many commits were added.
+    pub fn add(
+        &mut self,
+        store: &dyn store::ObjectStore,
+        tips: &[String],
+    ) -> Result<usize, CommitGraphError> {
+        let before = self.nodes.len();
+        // a commit is numbered once all of its parents are
+        let mut stack: Vec<commit::CommitNode> = Vec::new();
+        let mut pending: Vec<String> = tips.to_vec();
+        while let Some(hash) = pending.pop() {
+            if self.nodes.contains_key(&hash) {
+                continue;
+            }
+            match commit::CommitNode::read(store, &hash) {
+                Err(_) => return Err(CommitGraphError::MissingCommit(hash)),
+                Ok(node) => stack.push(node),
+            }
+            while let Some(node) = stack.pop() {
+                if self.nodes.contains_key(&node.hash) {
+                    continue;
+                }
+                let missing: Vec<String> = node
+                    .parents
+                    .iter()
+                    .filter(|parent| !self.nodes.contains_key(*parent))
+                    .cloned()
+                    .collect();
+                match missing.first() {
+                    Some(parent) => {
+                        let parent = match commit::CommitNode::read(store, parent) {
+                            Err(_) => return Err(CommitGraphError::MissingCommit(parent.clone())),
+                            Ok(parent) => parent,
+                        };
+                        stack.push(node);
+                        stack.push(parent);
+                    }
+                    None => {
+                        let generation = node
+                            .parents
+                            .iter()
+                            .map(|parent| self.nodes[parent].generation)
+                            .max()
+                            .unwrap_or(0)
+                            .saturating_add(1)
+                            .min(commit::GENERATION_INFINITY - 1);
+                        let node = commit::CommitNode {
+                            generation,
+                            author: None,
+                            committer: None,
+                            ..node
+                        };
+                        self.nodes.insert(node.hash.clone(), node);
+                    }
+                }
+            }
+        }
+        Ok(self.nodes.len() - before)
+    }
+
+    pub fn write(
+        &self,
+        path: &str,
+        format: encoding::ObjectFormat,
+    ) -> Result<(), CommitGraphError> {
+        let mut ids: Vec<&String> = self.nodes.keys().collect();
+        ids.sort();
+        let positions: HashMap<&String, u32> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, hash)| (*hash, i as u32))
+            .collect();
+        let mut data = Vec::new();
+        data.extend(&GRAPH_MAGIC);
+        data.extend(&GRAPH_VERSION.to_be_bytes());
+        data.extend(&(ids.len() as u32).to_be_bytes());
+        for hash in &ids {
+            data.extend(encoding::hex_to_bytes(hash).unwrap_or_default());
+        }
+        for hash in &ids {
+            let node = &self.nodes[*hash];
+            data.extend(encoding::hex_to_bytes(&node.tree_hash).unwrap_or_default());
+            data.extend(&(node.parents.len() as u32).to_be_bytes());
+            for parent in &node.parents {
+                data.extend(&positions[parent].to_be_bytes());
+            }
+            data.extend(&node.generation.to_be_bytes());
+            data.extend(&node.time.to_be_bytes());
+        }
+        let trailer = checksum(format, &data);
+        data.extend(trailer);
+        // written aside and renamed so readers never see half a file
+        let tmp_path = String::from(path) + ".lock";
+        if fs::write(&tmp_path, data).is_err() || fs::rename(&tmp_path, path).is_err() {
+            return Err(CommitGraphError::IOError);
+        }
+        Ok(())
+    }
+}
+
+/// Adds `tips` and their ancestors to the graph file at `path`, starting
+/// over when it is missing or unreadable.
+pub fn update(
+    store: &dyn store::ObjectStore,
+    path: &str,
+    tips: &[String],
+) -> Result<(), CommitGraphError> {
+    let mut graph = GraphFile::open(path, store.format()).unwrap_or_default();
+    if graph.add(store, tips)? > 0 {
+        graph.write(path, store.format())?;
+    }
+    Ok(())
+}
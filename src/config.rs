@@ -0,0 +1,130 @@
+use std::env;
+use std::fs;
+
+pub enum ConfigError {
+    IOError,
+}
+
+/// Repository settings stored in `.yit/config` using git's ini-like syntax.
+/// Keys are addressed as `section.key`, e.g. `core.gitcompat`.
+#[derive(Clone, Default)]
+pub struct Config {
+    entries: Vec<(String, String, String)>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn load(file_path: String) -> Result<Self, ConfigError> {
+        let contents = match fs::read_to_string(file_path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::new()),
+            Err(_) => return Err(ConfigError::IOError),
+            Ok(contents) => contents,
+        };
+        let mut config = Config::new();
+        let mut section = String::from("");
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_lowercase();
+                continue;
+            }
+            match line.find('=') {
+                None => config.set(&(section.clone() + "." + line), "true"),
+                Some(pos) => config.set(
+                    &(section.clone() + "." + line[..pos].trim()),
+                    line[pos + 1..].trim(),
+                ),
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn current() -> Self {
+        Config::load(String::from(".yit/config")).unwrap_or_default()
+    }
+
+    pub fn user() -> Self {
+        match env::var("HOME") {
+            Err(_) => Config::new(),
+            Ok(home) => Config::load(home + "/.yitconfig").unwrap_or_default(),
+        }
+    }
+
+    /// The user's settings, overridden by those of the current repository.
+    pub fn merged() -> Self {
+        let mut config = Config::user();
+        for (section, key, value) in Config::current().entries {
+            config.set(&(section + "." + &key), &value);
+        }
+        config
+    }
+
+    fn split_key(key: &str) -> (String, String) {
+        match key.rfind('.') {
+            None => (String::from(""), key.to_lowercase()),
+            Some(pos) => (key[..pos].to_lowercase(), key[pos + 1..].to_lowercase()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let (section, name) = Config::split_key(key);
+        self.entries
+            .iter()
+            .rev()
+            .find(|(s, k, _)| *s == section && *k == name)
+            .map(|(_, _, value)| value.clone())
+    }
+
+    pub fn get_bool(&self, key: &str) -> bool {
+        match self.get(key) {
+            None => false,
+            Some(value) => matches!(value.to_lowercase().as_str(), "true" | "yes" | "on" | "1"),
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        let (section, name) = Config::split_key(key);
+        match self
+            .entries
+            .iter_mut()
+            .find(|(s, k, _)| *s == section && *k == name)
+        {
+            Some(entry) => entry.2 = String::from(value),
+            None => self.entries.push((section, name, String::from(value))),
+        }
+    }
+
+    pub fn write(&self, file_path: String) -> Result<(), ConfigError> {
+        let mut sections: Vec<&String> = Vec::new();
+        for (section, _, _) in &self.entries {
+            if !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+        let mut content = String::from("");
+        for section in sections {
+            content.push('[');
+            content.push_str(section);
+            content.push_str("]\n");
+            for (_, key, value) in self.entries.iter().filter(|(s, _, _)| s == section) {
+                content.push('\t');
+                content.push_str(key);
+                content.push_str(" = ");
+                content.push_str(value);
+                content.push('\n');
+            }
+        }
+        match fs::write(file_path, content) {
+            Err(_) => Err(ConfigError::IOError),
+            Ok(_) => Ok(()),
+        }
+    }
+}
@@ -1,29 +1,339 @@
 use crate::file;
+use crate::store;
 
-pub fn get_diff_files(blob1_hash: &String, blob2_hash: &String) -> String {
-    match file::cat_file(String::from(blob1_hash)) {
+pub fn get_diff_files(
+    store: &dyn store::ObjectStore,
+    blob1_hash: &str,
+    blob2_hash: &str,
+) -> String {
+    match file::cat_file(store, String::from(blob1_hash)) {
         Err(_) => String::from(""),
-        Ok(blob1) => match file::cat_file(String::from(blob2_hash)) {
+        Ok(blob1) => match file::cat_file(store, String::from(blob2_hash)) {
             Err(_) => String::from(""),
             Ok(blob2) => {
-                let blob1_lines: Vec<&str> = blob1.split('\n').collect();
-                let blob2_lines: Vec<&str> = blob2.split('\n').collect();
+                let blob1_lines: Vec<&[u8]> = blob1.split(|byte| *byte == b'\n').collect();
+                let blob2_lines: Vec<&[u8]> = blob2.split(|byte| *byte == b'\n').collect();
                 let mut new_file: String = String::from("");
-                let mut cnt = 0;
-                for line in blob1_lines.clone() {
+                for (cnt, line) in blob1_lines.iter().enumerate() {
                     if cnt >= blob2_lines.len() {
-                        new_file.push_str(&(String::from(line) + "\n"));
-                    } else {
-                        if line == blob2_lines[cnt] {
-                        } else {
-                            new_file.push_str(&(String::from(blob1_lines[cnt]) + "\n"));
-                            new_file.push_str(&(String::from(blob2_lines[cnt]) + "\n"));
-                        }
+                        new_file.push_str(&(String::from_utf8_lossy(line) + "\n"));
+                    } else if *line != blob2_lines[cnt] {
+                        new_file.push_str(&(String::from_utf8_lossy(line) + "\n"));
+                        new_file.push_str(&(String::from_utf8_lossy(blob2_lines[cnt]) + "\n"));
                     }
-                    cnt += 1;
                 }
                 new_file
             }
         },
     }
 }
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Myers' algorithm in linear space. Within a run of changes, deletions come
+/// first.
+pub fn line_diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    diff_range(old, new, 0, 0, &mut edits);
+    let mut start = 0;
+    while start < edits.len() {
+        let end = edits[start..]
+            .iter()
+            .position(|edit| matches!(edit, Edit::Equal(_, _)))
+            .map_or(edits.len(), |len| start + len);
+        edits[start..end].sort_by_key(|edit| matches!(edit, Edit::Insert(_)));
+        start = end + 1;
+    }
+    edits
+}
+
+fn diff_range<T: PartialEq>(
+    old: &[T],
+    new: &[T],
+    old_pos: usize,
+    new_pos: usize,
+    edits: &mut Vec<Edit>,
+) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    edits.extend((0..prefix).map(|i| Edit::Equal(old_pos + i, new_pos + i)));
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let (old_pos, new_pos) = (old_pos + prefix, new_pos + prefix);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    if old.is_empty() {
+        edits.extend((0..new.len()).map(|j| Edit::Insert(new_pos + j)));
+    } else if new.is_empty() {
+        edits.extend((0..old.len()).map(|i| Edit::Delete(old_pos + i)));
+    } else {
+        let (x, y) = middle(old, new);
+        diff_range(&old[..x], &new[..y], old_pos, new_pos, edits);
+        diff_range(&old[x..], &new[y..], old_pos + x, new_pos + y, edits);
+    }
+    edits
+        .extend((0..suffix).map(|i| Edit::Equal(old_pos + old.len() + i, new_pos + new.len() + i)));
+}
+
+/// The furthest point on diagonal `k` a path with one more edit than those
+/// in `v` reaches, following equal lines as `equal` says; -1 if it would
+/// leave the `n` by `m` grid.
+fn furthest(
+    v: &[isize],
+    k: isize,
+    offset: isize,
+    n: isize,
+    m: isize,
+    equal: impl Fn(usize, usize) -> bool,
+) -> isize {
+    let i = (k + offset) as usize;
+    // a down move keeps x, a right move adds one
+    let down = if v[i + 1] >= 0 && v[i + 1] - k <= m {
+        v[i + 1]
+    } else {
+        -1
+    };
+    let right = if v[i - 1] >= 0 && v[i - 1] < n {
+        v[i - 1] + 1
+    } else {
+        -1
+    };
+    let mut x = down.max(right);
+    if x < 0 {
+        return -1;
+    }
+    while x < n && x - k < m && equal(x as usize, (x - k) as usize) {
+        x += 1;
+    }
+    x
+}
+
+/// A point a shortest edit script of `old` and `new`, both non-empty and
+/// differing in their first and last lines, passes through, away from both
+/// ends. Found by searching from both ends until the paths meet, keeping
+/// only the furthest point of each diagonal.
+fn middle<T: PartialEq>(old: &[T], new: &[T]) -> (usize, usize) {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let delta = n - m;
+    let offset = n + m + 1;
+    let mut forward = vec![-1isize; 2 * offset as usize + 1];
+    // x counted from the end, on diagonals of the reversed inputs
+    let mut backward = forward.clone();
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+    for d in 0..=(n + m + 1) / 2 {
+        for k in (-d..=d).step_by(2) {
+            let x = furthest(&forward, k, offset, n, m, |x, y| old[x] == new[y]);
+            forward[(k + offset) as usize] = x;
+            // the backward search has one round less to meet
+            if delta % 2 != 0 && (delta - k).abs() < d && x >= 0 {
+                let back = backward[(delta - k + offset) as usize];
+                if back >= 0 && x + back >= n {
+                    return (x as usize, (x - k) as usize);
+                }
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let x = furthest(&backward, k, offset, n, m, |x, y| {
+                old[(n - 1) as usize - x] == new[(m - 1) as usize - y]
+            });
+            backward[(k + offset) as usize] = x;
+            if delta % 2 == 0 && (delta - k).abs() <= d && x >= 0 {
+                let front = forward[(delta - k + offset) as usize];
+                if front >= 0 && x + front >= n {
+                    return (front as usize, (front - delta + k) as usize);
+                }
+            }
+        }
+    }
+    // not reached; deleting all of old first is still a valid script
+    (old.len(), 0)
+}
+
+/// `None` for binary content: with a NUL byte, as git decides, or not UTF-8.
+pub fn as_text(content: Vec<u8>) -> Option<String> {
+    if content.contains(&0) {
+        return None;
+    }
+    String::from_utf8(content).ok()
+}
+
+pub fn split_lines(content: &str) -> Vec<&str> {
+    content.split_inclusive('\n').collect()
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hunk {
+    /// position of the first old line, from 0
+    pub old_start: usize,
+    /// position of the first new line, from 0
+    pub new_start: usize,
+    /// `' '`, `'-'` or `'+'` and the line, including its newline if it has one
+    pub lines: Vec<(char, String)>,
+}
+
+impl Hunk {
+    pub fn old_len(&self) -> usize {
+        self.lines.iter().filter(|(kind, _)| *kind != '+').count()
+    }
+
+    pub fn new_len(&self) -> usize {
+        self.lines.iter().filter(|(kind, _)| *kind != '-').count()
+    }
+
+    /// Counts lines from 1; an empty side starts at the line before it.
+    pub fn header(&self) -> String {
+        let start = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        format!(
+            "@@ -{},{} +{},{} @@",
+            start(self.old_start, self.old_len()),
+            self.old_len(),
+            start(self.new_start, self.new_len()),
+            self.new_len()
+        )
+    }
+
+    /// Splits the hunk at the unchanged lines between its changes. Each part
+    /// keeps all the unchanged lines on both sides of its changes, so parts
+    /// next to each other share some. `None` when there is nothing to split.
+    pub fn split(&self) -> Option<Vec<Hunk>> {
+        // the ranges of lines holding consecutive changes
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for (i, (kind, _)) in self.lines.iter().enumerate() {
+            if *kind == ' ' {
+                continue;
+            }
+            match runs.last_mut() {
+                Some(run) if run.1 == i => run.1 = i + 1,
+                _ => runs.push((i, i + 1)),
+            }
+        }
+        if runs.len() < 2 {
+            return None;
+        }
+        let mut parts = Vec::new();
+        for r in 0..runs.len() {
+            let from = if r == 0 { 0 } else { runs[r - 1].1 };
+            let to = runs.get(r + 1).map_or(self.lines.len(), |next| next.0);
+            let (mut old_start, mut new_start) = (self.old_start, self.new_start);
+            for (kind, _) in &self.lines[..from] {
+                if *kind != '+' {
+                    old_start += 1;
+                }
+                if *kind != '-' {
+                    new_start += 1;
+                }
+            }
+            parts.push(Hunk {
+                old_start,
+                new_start,
+                lines: self.lines[from..to].to_vec(),
+            });
+        }
+        Some(parts)
+    }
+}
+
+impl std::fmt::Display for Hunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.header())?;
+        for (kind, line) in &self.lines {
+            match line.strip_suffix('\n') {
+                Some(line) => writeln!(f, "{}{}", kind, line)?,
+                None => {
+                    writeln!(f, "{}{}", kind, line)?;
+                    writeln!(f, "\\ No newline at end of file")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Changes closer than twice `context` share a hunk.
+pub fn hunks(old: &[&str], new: &[&str], context: usize) -> Vec<Hunk> {
+    let edits = line_diff(old, new);
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(_, _)))
+        .map(|(i, _)| i)
+        .collect();
+    // positions in old and new before each edit
+    let mut positions = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for edit in &edits {
+        positions.push((old_pos, new_pos));
+        match edit {
+            Edit::Equal(_, _) => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Edit::Delete(_) => old_pos += 1,
+            Edit::Insert(_) => new_pos += 1,
+        }
+    }
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for i in changes {
+        match groups.last_mut() {
+            Some(group) if i - group.1 <= 2 * context + 1 => group.1 = i,
+            _ => groups.push((i, i)),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(first, last)| {
+            let from = first.saturating_sub(context);
+            let to = (last + context + 1).min(edits.len());
+            let lines = edits[from..to]
+                .iter()
+                .map(|edit| match *edit {
+                    Edit::Equal(x, _) => (' ', String::from(old[x])),
+                    Edit::Delete(x) => ('-', String::from(old[x])),
+                    Edit::Insert(y) => ('+', String::from(new[y])),
+                })
+                .collect();
+            Hunk {
+                old_start: positions[from].0,
+                new_start: positions[from].1,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// `hunks` must be in order, but may share unchanged lines like the parts of
+/// a split hunk.
+pub fn apply_hunks(old: &[&str], hunks: &[Hunk]) -> String {
+    let mut content = String::new();
+    let mut next = 0;
+    for hunk in hunks {
+        if hunk.old_start > next {
+            content.extend(old[next..hunk.old_start].iter().copied());
+        }
+        let mut pos = hunk.old_start;
+        for (kind, line) in &hunk.lines {
+            match kind {
+                '+' => content.push_str(line),
+                _ => {
+                    if *kind == ' ' && pos >= next {
+                        content.push_str(line);
+                    }
+                    pos += 1;
+                }
+            }
+        }
+        next = next.max(pos);
+    }
+    content.extend(old[next.min(old.len())..].iter().copied());
+    content
+}
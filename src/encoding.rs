@@ -0,0 +1,311 @@
+use crate::config;
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use crypto::sha2::Sha256;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::deflate::core::{create_comp_flags_from_zip_params, CompressorOxide};
+use miniz_oxide::deflate::stream::deflate;
+use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::inflate::{decompress_to_vec, decompress_to_vec_zlib};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+use std::io;
+use std::io::prelude::*;
+
+/// zlib's default level, used when `core.compression` is unset or -1 as in
+/// git. Level 0 stores objects uncompressed.
+pub const DEFAULT_COMPRESSION: u8 = 6;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// `Yit` is the historical format (`"<type>\n"` headers, textual tree entries).
+/// `Git` is git's loose-object encoding, enabled with `core.gitcompat = true`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+    Yit,
+    Git,
+}
+
+impl Encoding {
+    pub fn current() -> Self {
+        Encoding::from_config(&config::Config::current())
+    }
+
+    pub fn from_config(config: &config::Config) -> Self {
+        if config.get_bool("core.gitcompat") {
+            Encoding::Git
+        } else {
+            Encoding::Yit
+        }
+    }
+
+    pub fn hash_input(self, kind: &str, content: &[u8]) -> Vec<u8> {
+        let mut input = self.hash_header(kind, content.len() as u64);
+        input.extend(content);
+        input
+    }
+
+    pub fn hash_header(self, kind: &str, len: u64) -> Vec<u8> {
+        match self {
+            Encoding::Git => self.header(kind, len),
+            Encoding::Yit => {
+                let mut header = Vec::new();
+                if kind == "blob" {
+                    header.extend(b"blob\n");
+                    header.extend(len.to_string().as_bytes());
+                    header.push(0);
+                } else if kind == "commit" {
+                    header.extend(b"commit\n");
+                }
+                header
+            }
+        }
+    }
+
+    pub fn encode(self, kind: &str, content: &[u8]) -> Vec<u8> {
+        let mut data = self.header(kind, content.len() as u64);
+        data.extend(content);
+        data
+    }
+
+    pub fn header(self, kind: &str, len: u64) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend(kind.as_bytes());
+        match self {
+            Encoding::Git => {
+                header.push(b' ');
+                header.extend(len.to_string().as_bytes());
+                header.push(0);
+            }
+            Encoding::Yit => header.push(b'\n'),
+        }
+        header
+    }
+}
+
+/// The hash function object ids are computed with. It is chosen when the
+/// repository is created and recorded as `extensions.objectformat`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ObjectFormat {
+    Sha1,
+    Sha256,
+}
+
+impl ObjectFormat {
+    pub fn current() -> Self {
+        ObjectFormat::from_config(&config::Config::current())
+    }
+
+    pub fn from_config(config: &config::Config) -> Self {
+        match config.get("extensions.objectformat") {
+            None => ObjectFormat::Sha1,
+            Some(name) => ObjectFormat::parse(&name).unwrap_or(ObjectFormat::Sha1),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "sha1" => Some(ObjectFormat::Sha1),
+            "sha256" => Some(ObjectFormat::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ObjectFormat::Sha1 => "sha1",
+            ObjectFormat::Sha256 => "sha256",
+        }
+    }
+
+    /// Length of an id in bytes; hex ids are twice as long.
+    pub fn raw_len(self) -> usize {
+        match self {
+            ObjectFormat::Sha1 => 20,
+            ObjectFormat::Sha256 => 32,
+        }
+    }
+
+    pub fn hex_len(self) -> usize {
+        self.raw_len() * 2
+    }
+
+    pub fn hasher(self) -> Box<dyn Digest> {
+        match self {
+            ObjectFormat::Sha1 => Box::new(Sha1::new()),
+            ObjectFormat::Sha256 => Box::new(Sha256::new()),
+        }
+    }
+}
+
+pub fn compression_level(config: &config::Config) -> u8 {
+    match config.get("core.compression") {
+        None => DEFAULT_COMPRESSION,
+        Some(value) => match value.trim().parse::<i32>() {
+            Ok(level) if (0..=9).contains(&level) => level as u8,
+            _ => DEFAULT_COMPRESSION,
+        },
+    }
+}
+
+pub fn compress(data: &[u8], level: u8) -> Vec<u8> {
+    compress_to_vec_zlib(data, level)
+}
+
+/// Objects written before zlib framing was introduced are raw deflate streams.
+pub fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    match decompress_to_vec_zlib(data) {
+        Ok(decompressed) => Some(decompressed),
+        Err(_) => decompress_to_vec(data).ok(),
+    }
+}
+
+fn stream_error(err: MZError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
+}
+
+pub struct Deflater<W: Write> {
+    inner: W,
+    compressor: Box<CompressorOxide>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Deflater<W> {
+    pub fn new(inner: W, level: u8) -> Self {
+        let flags = create_comp_flags_from_zip_params(i32::from(level), 1, 0);
+        Deflater {
+            inner,
+            compressor: Box::new(CompressorOxide::new(flags)),
+            buffer: vec![0; BUFFER_SIZE],
+        }
+    }
+
+    fn deflate(&mut self, mut data: &[u8], flush: MZFlush) -> io::Result<()> {
+        loop {
+            let res = deflate(&mut self.compressor, data, &mut self.buffer, flush);
+            self.inner.write_all(&self.buffer[..res.bytes_written])?;
+            data = &data[res.bytes_consumed..];
+            match res.status {
+                Ok(MZStatus::StreamEnd) => return Ok(()),
+                Ok(_) => {
+                    let output_full = res.bytes_written == self.buffer.len();
+                    if data.is_empty() && flush == MZFlush::None && !output_full {
+                        return Ok(());
+                    }
+                }
+                // nothing left to do until more input arrives
+                Err(MZError::Buf) if data.is_empty() && flush == MZFlush::None => return Ok(()),
+                Err(err) => return Err(stream_error(err)),
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.deflate(&[], MZFlush::Finish)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Deflater<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.deflate(data, MZFlush::None)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Old objects are raw deflate streams rather than zlib ones.
+pub struct Inflater<R: Read> {
+    inner: R,
+    state: Box<InflateState>,
+    buffer: Vec<u8>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: Read> Inflater<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut len = 0;
+        // two bytes are enough to recognise the zlib header
+        while len < 2 {
+            match inner.read(&mut buffer[len..])? {
+                0 => break,
+                read => len += read,
+            }
+        }
+        let zlib = len >= 2
+            && buffer[0] & 0x0f == 8
+            && (u16::from(buffer[0]) << 8 | u16::from(buffer[1])) % 31 == 0;
+        let format = if zlib {
+            DataFormat::Zlib
+        } else {
+            DataFormat::Raw
+        };
+        Ok(Inflater {
+            inner,
+            state: InflateState::new_boxed(format),
+            buffer,
+            pos: 0,
+            len,
+            eof: len == 0,
+            done: false,
+        })
+    }
+}
+
+impl<R: Read> Read for Inflater<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.done || out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.pos == self.len && !self.eof {
+                self.len = self.inner.read(&mut self.buffer)?;
+                self.pos = 0;
+                self.eof = self.len == 0;
+            }
+            let res = inflate(
+                &mut self.state,
+                &self.buffer[self.pos..self.len],
+                out,
+                MZFlush::None,
+            );
+            self.pos += res.bytes_consumed;
+            match res.status {
+                Ok(MZStatus::StreamEnd) => {
+                    self.done = true;
+                    return Ok(res.bytes_written);
+                }
+                Ok(_) | Err(MZError::Buf) if res.bytes_written > 0 => return Ok(res.bytes_written),
+                Ok(_) | Err(MZError::Buf) if !self.eof => continue,
+                Ok(_) | Err(MZError::Buf) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "truncated deflate stream",
+                    ))
+                }
+                Err(err) => return Err(stream_error(err)),
+            }
+        }
+    }
+}
+
+pub fn hex_to_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
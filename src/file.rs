@@ -1,38 +1,34 @@
-use miniz_oxide::inflate::decompress_to_vec;
-use std::fs;
-use std::fs::File;
+use crate::store;
+use std::io;
 use std::io::prelude::*;
-use std::str;
 
 pub enum ParseError {
     IOError,
+    NotABlob,
 }
 
-pub fn cat_file(hash: String) -> Result<String, ParseError> {
-    let dir = &hash[0..2];
-    let filename = &hash[2..];
-    match File::open(String::from(".yit/objects/") + dir + "/" + filename) {
+pub fn cat_file(store: &dyn store::ObjectStore, hash: String) -> Result<Vec<u8>, ParseError> {
+    let mut content = Vec::new();
+    cat_file_to(store, hash, &mut content)?;
+    Ok(content)
+}
+
+/// Copies the content of a blob into `writer` without loading it into memory.
+pub fn cat_file_to(
+    store: &dyn store::ObjectStore,
+    hash: String,
+    writer: &mut dyn Write,
+) -> Result<(), ParseError> {
+    let id = match store::ObjectId::from_hex(&hash, store.format()) {
+        None => return Err(ParseError::IOError),
+        Some(id) => id,
+    };
+    match store.stream(&id) {
         Err(_) => Err(ParseError::IOError),
-        Ok(mut file) => {
-            let metadata = fs::metadata(&(String::from(".yit/objects/") + dir + "/" + filename))
-                .expect("unable to read metadata");
-            let mut buffer = vec![0; metadata.len() as usize];
-            match file.read(&mut buffer) {
-                Err(_) => Err(ParseError::IOError),
-                Ok(_) => match decompress_to_vec(&buffer) {
-                    Err(_) => Err(ParseError::IOError),
-                    Ok(decompressed) => match str::from_utf8(&decompressed) {
-                        Err(_) => Err(ParseError::IOError),
-                        Ok(converted) => {
-                            let converted_string = String::from(converted);
-                            let lines: Vec<&str> = converted_string.split('\n').collect();
-                            let content = &lines[1..];
-                            let joined = content.join("\n");
-                            Ok(joined)
-                        }
-                    },
-                },
-            }
-        }
+        Ok((store::ObjectKind::Blob, mut reader)) => match io::copy(&mut reader, writer) {
+            Err(_) => Err(ParseError::IOError),
+            Ok(_) => Ok(()),
+        },
+        Ok(_) => Err(ParseError::NotABlob),
     }
 }
@@ -0,0 +1,223 @@
+use crate::object;
+use crate::store;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    HashMismatch {
+        id: store::ObjectId,
+        actual: store::ObjectId,
+    },
+    Corrupt {
+        id: store::ObjectId,
+        error: String,
+    },
+    Missing {
+        id: store::ObjectId,
+        kind: store::ObjectKind,
+        referenced_by: store::ObjectId,
+    },
+    WrongType {
+        id: store::ObjectId,
+        expected: store::ObjectKind,
+        actual: store::ObjectKind,
+        referenced_by: String,
+    },
+    BrokenRef {
+        name: String,
+        target: String,
+    },
+    Dangling {
+        id: store::ObjectId,
+        kind: store::ObjectKind,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::HashMismatch { id, actual } => {
+                write!(f, "hash mismatch {} (content hashes to {})", id, actual)
+            }
+            Problem::Corrupt { id, error } => write!(f, "corrupt object {}: {}", id, error),
+            Problem::Missing {
+                id,
+                kind,
+                referenced_by,
+            } => write!(
+                f,
+                "missing {} {} (referenced by {})",
+                kind.as_str(),
+                id,
+                referenced_by
+            ),
+            Problem::WrongType {
+                id,
+                expected,
+                actual,
+                referenced_by,
+            } => write!(
+                f,
+                "{} is a {} but {} expects a {}",
+                id,
+                actual.as_str(),
+                referenced_by,
+                expected.as_str()
+            ),
+            Problem::BrokenRef { name, target } => {
+                write!(f, "broken ref {} -> {}", name, target)
+            }
+            Problem::Dangling { id, kind } => write!(f, "dangling {} {}", kind.as_str(), id),
+        }
+    }
+}
+
+fn describe(err: store::StoreError) -> String {
+    match err {
+        store::StoreError::Corrupt(err) => err.to_string(),
+        store::StoreError::NotFound => String::from("object file is missing"),
+        store::StoreError::IOError => String::from("unable to read object"),
+        store::StoreError::Unsupported => String::from("not supported by the object store"),
+    }
+}
+
+/// Hashes an object the way it was written without holding it in memory. The
+/// content is streamed twice, since the hash header needs its length first.
+fn rehash(
+    store: &dyn store::ObjectStore,
+    id: &store::ObjectId,
+) -> Result<(store::ObjectKind, store::ObjectId), store::StoreError> {
+    let (_, _, mut reader) = store.stream_encoded(id)?;
+    let len = match io::copy(&mut reader, &mut io::sink()) {
+        Err(_) => return Err(store::StoreError::IOError),
+        Ok(len) => len,
+    };
+    let (kind, encoding, mut reader) = store.stream_encoded(id)?;
+    let actual = store::hash_reader(store.format(), encoding, kind, len, &mut reader)?;
+    Ok((kind, actual))
+}
+
+pub struct FsckReport {
+    pub checked: usize,
+    pub problems: Vec<Problem>,
+}
+
+/// Objects in `roots`, like staged blobs, are not reported as dangling.
+pub fn check(
+    store: &dyn store::ObjectStore,
+    refs: &[(String, String)],
+    roots: &[store::ObjectId],
+) -> FsckReport {
+    let mut problems = Vec::new();
+    let ids = store.list().unwrap_or_default();
+    let mut kinds: HashMap<store::ObjectId, store::ObjectKind> = HashMap::new();
+    // (referencing object, referenced id, expected type)
+    let mut references: Vec<(store::ObjectId, store::ObjectId, store::ObjectKind)> = Vec::new();
+
+    for id in &ids {
+        let (kind, actual) = match rehash(store, id) {
+            Err(err) => {
+                problems.push(Problem::Corrupt {
+                    id: id.clone(),
+                    error: describe(err),
+                });
+                continue;
+            }
+            Ok(hashed) => hashed,
+        };
+        kinds.insert(id.clone(), kind);
+        if actual != *id {
+            problems.push(Problem::HashMismatch {
+                id: id.clone(),
+                actual,
+            });
+        }
+        // blobs have nothing to parse, and may not fit in memory
+        if kind == store::ObjectKind::Blob {
+            continue;
+        }
+        match store.read(id) {
+            Err(err) => problems.push(Problem::Corrupt {
+                id: id.clone(),
+                error: describe(err),
+            }),
+            Ok(object::Object::Blob(_)) => (),
+            Ok(object::Object::Tree(entries)) => {
+                for entry in entries {
+                    references.push((id.clone(), entry.id.clone(), entry.kind()));
+                }
+            }
+            Ok(object::Object::Commit(commit)) => {
+                references.push((id.clone(), commit.tree, store::ObjectKind::Tree));
+                for parent in commit.parents {
+                    references.push((id.clone(), parent, store::ObjectKind::Commit));
+                }
+            }
+            Ok(object::Object::Tag(tag)) => references.push((id.clone(), tag.object, tag.kind)),
+        }
+    }
+
+    let mut referenced: HashSet<store::ObjectId> = roots.iter().cloned().collect();
+    for (from, to, expected) in references {
+        match kinds.get(&to) {
+            None => problems.push(Problem::Missing {
+                id: to.clone(),
+                kind: expected,
+                referenced_by: from,
+            }),
+            Some(actual) if *actual != expected => problems.push(Problem::WrongType {
+                id: to.clone(),
+                expected,
+                actual: *actual,
+                referenced_by: from.to_string(),
+            }),
+            Some(_) => (),
+        }
+        referenced.insert(to);
+    }
+
+    for (name, target) in refs {
+        let id = match store::ObjectId::from_hex(target, store.format()) {
+            None => {
+                problems.push(Problem::BrokenRef {
+                    name: name.clone(),
+                    target: target.clone(),
+                });
+                continue;
+            }
+            Some(id) => id,
+        };
+        match kinds.get(&id) {
+            None => problems.push(Problem::BrokenRef {
+                name: name.clone(),
+                target: target.clone(),
+            }),
+            Some(store::ObjectKind::Commit) | Some(store::ObjectKind::Tag) => (),
+            Some(actual) => problems.push(Problem::WrongType {
+                id: id.clone(),
+                expected: store::ObjectKind::Commit,
+                actual: *actual,
+                referenced_by: name.clone(),
+            }),
+        }
+        referenced.insert(id);
+    }
+
+    for id in &ids {
+        if let Some(kind) = kinds.get(id) {
+            if !referenced.contains(id) {
+                problems.push(Problem::Dangling {
+                    id: id.clone(),
+                    kind: *kind,
+                });
+            }
+        }
+    }
+
+    FsckReport {
+        checked: ids.len(),
+        problems,
+    }
+}
@@ -0,0 +1,160 @@
+use crate::pack;
+use crate::store;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug)]
+pub enum GcError {
+    InvalidExpire(String),
+    MissingObject,
+    IOError,
+    Unsupported,
+}
+
+pub const DEFAULT_EXPIRE: &str = "2.weeks";
+
+pub struct GcReport {
+    pub packed: usize,
+    pub pruned: usize,
+}
+
+/// Parses a grace period such as `now`, `never`, `3600`, `90.minutes` or
+/// `2.weeks.ago`. `None` means unreachable objects are never pruned.
+pub fn parse_expire(value: &str) -> Result<Option<Duration>, GcError> {
+    let value = value.trim().to_lowercase();
+    let value = value.trim_end_matches(".ago");
+    if value == "never" {
+        return Ok(None);
+    }
+    if value == "now" {
+        return Ok(Some(Duration::from_secs(0)));
+    }
+    let (amount, unit) = match value.find('.') {
+        None => (value, "seconds"),
+        Some(pos) => (&value[..pos], &value[pos + 1..]),
+    };
+    let amount: u64 = match amount.parse() {
+        Err(_) => return Err(GcError::InvalidExpire(String::from(value))),
+        Ok(amount) => amount,
+    };
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        _ => return Err(GcError::InvalidExpire(String::from(value))),
+    };
+    Ok(Some(Duration::from_secs(amount * seconds)))
+}
+
+fn is_expired(path: &Path, expire: Option<Duration>) -> bool {
+    let expire = match expire {
+        None => return false,
+        Some(expire) => expire,
+    };
+    match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+        Err(_) => false,
+        Ok(modified) => match SystemTime::now().duration_since(modified) {
+            Err(_) => expire.as_secs() == 0,
+            Ok(age) => age >= expire,
+        },
+    }
+}
+
+pub fn collect(
+    store: &dyn store::ObjectStore,
+    roots: &[store::ObjectId],
+    expire: Option<Duration>,
+) -> Result<GcReport, GcError> {
+    let reachable = match pack::reachable_objects(store, roots) {
+        Err(_) => return Err(GcError::MissingObject),
+        Ok(objects) => objects,
+    };
+    match store.prune(&reachable, expire) {
+        Err(store::StoreError::Unsupported) => Err(GcError::Unsupported),
+        Err(_) => Err(GcError::IOError),
+        Ok(pruned) => Ok(GcReport {
+            packed: reachable.len(),
+            pruned,
+        }),
+    }
+}
+
+pub fn prune_loose(
+    store: &dyn store::ObjectStore,
+    objects_dir: &str,
+    reachable_objects: &[(store::ObjectId, String)],
+    expire: Option<Duration>,
+) -> Result<usize, GcError> {
+    let reachable: HashSet<store::ObjectId> =
+        reachable_objects.iter().map(|(id, _)| id.clone()).collect();
+    let old_packs = pack::pack_index_paths(objects_dir);
+    let pack_dir = String::from(objects_dir) + "/pack";
+    let new_packs: Vec<String> = match pack::write_pack(store, reachable_objects, &pack_dir) {
+        Err(_) => return Err(GcError::IOError),
+        Ok(names) => names
+            .iter()
+            .map(|name| pack_dir.clone() + "/pack-" + name + ".idx")
+            .collect(),
+    };
+
+    let mut pruned = 0;
+    let dirs = match fs::read_dir(objects_dir) {
+        Err(_) => return Err(GcError::IOError),
+        Ok(dirs) => dirs,
+    };
+    for dir in dirs.flatten() {
+        let dir_name = dir.file_name().to_string_lossy().to_string();
+        if dir_name.len() != 2 {
+            continue;
+        }
+        if let Ok(files) = fs::read_dir(dir.path()) {
+            for file in files.flatten() {
+                let file_name = file.file_name().to_string_lossy().to_string();
+                let id = match store::ObjectId::from_hex(
+                    &(dir_name.clone() + &file_name),
+                    store.format(),
+                ) {
+                    None => continue,
+                    Some(id) => id,
+                };
+                let packed = reachable.contains(&id) && !new_packs.is_empty();
+                if packed || (!reachable.contains(&id) && is_expired(&file.path(), expire)) {
+                    if fs::remove_file(file.path()).is_err() {
+                        return Err(GcError::IOError);
+                    }
+                    if !packed {
+                        pruned += 1;
+                    }
+                }
+            }
+        }
+        // only succeeds for fan-out directories that are now empty
+        let _ = fs::remove_dir(dir.path());
+    }
+
+    for idx_path in old_packs {
+        if new_packs.contains(&idx_path) {
+            continue;
+        }
+        let superseded = match pack::Pack::open(&idx_path, store.format()) {
+            Err(_) => false,
+            Ok(old) => old.ids().iter().all(|id| reachable.contains(id)),
+        };
+        let pack_path = String::from(idx_path.trim_end_matches(".idx")) + ".pack";
+        let remove = superseded || is_expired(Path::new(&pack_path), expire);
+        if remove && (fs::remove_file(&idx_path).is_err() || fs::remove_file(&pack_path).is_err()) {
+            return Err(GcError::IOError);
+        }
+        if remove {
+            let encoding_path =
+                String::from(idx_path.trim_end_matches(".idx")) + pack::ENCODING_EXT;
+            let _ = fs::remove_file(encoding_path);
+        }
+    }
+
+    Ok(pruned)
+}
@@ -0,0 +1,139 @@
+use crate::config;
+use std::env;
+use std::fmt;
+use std::fs;
+
+#[derive(Debug)]
+pub enum IdentityError {
+    InvalidDate(String),
+}
+
+/// Who made a commit and when, stored as `Name <email> <seconds> <+hhmm>`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub time: i64,
+    /// minutes east of UTC
+    pub offset: i32,
+}
+
+fn local_offset(time: i64) -> i32 {
+    time::at(time::Timespec::new(time, 0)).tm_utcoff / 60
+}
+
+fn parse_offset(offset: &str) -> Option<i32> {
+    let (sign, digits) = match offset.as_bytes().first() {
+        Some(b'+') => (1, &offset[1..]),
+        Some(b'-') => (-1, &offset[1..]),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// Parses `<seconds> <+hhmm>`, optionally with a leading `@`. Without an
+/// offset the local one is used.
+pub fn parse_date(value: &str) -> Option<(i64, i32)> {
+    let mut words = value.split_whitespace();
+    let time: i64 = words.next()?.trim_start_matches('@').parse().ok()?;
+    let offset = match words.next() {
+        None => local_offset(time),
+        Some(offset) => parse_offset(offset)?,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some((time, offset))
+}
+
+impl Signature {
+    pub fn parse(value: &str) -> Option<Self> {
+        let email_start = value.find('<')?;
+        let email_end = email_start + value[email_start..].find('>')?;
+        let (time, offset) = parse_date(&value[email_end + 1..])?;
+        Some(Signature {
+            name: String::from(value[..email_start].trim()),
+            email: String::from(&value[email_start + 1..email_end]),
+            time,
+            offset,
+        })
+    }
+
+    /// The author of a new commit: `YIT_AUTHOR_NAME`, `YIT_AUTHOR_EMAIL` and
+    /// `YIT_AUTHOR_DATE` take precedence over `user.name` and `user.email`.
+    pub fn author(config: &config::Config) -> Result<Self, IdentityError> {
+        Signature::from_env(config, "AUTHOR")
+    }
+
+    pub fn committer(config: &config::Config) -> Result<Self, IdentityError> {
+        Signature::from_env(config, "COMMITTER")
+    }
+
+    fn from_env(config: &config::Config, role: &str) -> Result<Self, IdentityError> {
+        let var = |key: &str| env::var(format!("YIT_{}_{}", role, key)).ok();
+        let user = env::var("USER").unwrap_or_else(|_| String::from("unknown"));
+        let name = var("NAME")
+            .or_else(|| config.get("user.name"))
+            .unwrap_or_else(|| user.clone());
+        let email = match var("EMAIL")
+            .or_else(|| config.get("user.email"))
+            .or_else(|| env::var("EMAIL").ok())
+        {
+            Some(email) => email,
+            None => {
+                let host = fs::read_to_string("/etc/hostname")
+                    .map(|host| String::from(host.trim()))
+                    .unwrap_or_default();
+                let host = if host.is_empty() {
+                    String::from("localhost")
+                } else {
+                    host
+                };
+                user + "@" + &host
+            }
+        };
+        let (time, offset) = match var("DATE") {
+            None => {
+                let now = time::get_time().sec;
+                (now, local_offset(now))
+            }
+            Some(date) => match parse_date(&date) {
+                None => return Err(IdentityError::InvalidDate(date)),
+                Some(date) => date,
+            },
+        };
+        Ok(Signature {
+            name,
+            email,
+            time,
+            offset,
+        })
+    }
+
+    pub fn format_offset(&self) -> String {
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.abs();
+        format!("{}{:02}{:02}", sign, offset / 60, offset % 60)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.time,
+            self.format_offset()
+        )
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fs;
+
+pub const IGNORE_FILE: &str = ".yitignore";
+pub const EXCLUDE_FILE: &str = ".yit/info/exclude";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct IgnoreRule {
+    pub source: String,
+    pub line: usize,
+    pub text: String,
+    /// the directory of `source`, ending with `/` unless it is the root
+    base: String,
+    glob: Vec<char>,
+    pub negated: bool,
+    dir_only: bool,
+    /// a rule with a `/` before its end matches relative to `base` rather
+    /// than at any depth
+    anchored: bool,
+}
+
+impl IgnoreRule {
+    pub fn parse(text: &str, base: &str, source: &str, line: usize) -> Option<Self> {
+        let mut pattern = text.trim_end_matches('\r');
+        // trailing spaces are dropped unless escaped with a backslash
+        while pattern.ends_with(' ') && !pattern.ends_with("\\ ") {
+            pattern = &pattern[..pattern.len() - 1];
+        }
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return None;
+        }
+        let negated = pattern.starts_with('!');
+        // a leading `!` either negates or, escaped, is part of the pattern
+        if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+            pattern = &pattern[1..];
+        }
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+        Some(IgnoreRule {
+            source: String::from(source),
+            line,
+            text: String::from(text.trim_end()),
+            base: String::from(base),
+            glob: pattern.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// `path` is relative to the repository root.
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let relative = match path.strip_prefix(self.base.as_str()) {
+            None => return false,
+            Some(relative) => relative,
+        };
+        let text: Vec<char> = if self.anchored {
+            relative.chars().collect()
+        } else {
+            relative.rsplit('/').next().unwrap_or("").chars().collect()
+        };
+        glob_match(&self.glob, &text, true)
+    }
+}
+
+/// Returns the result and the rest of the pattern, or `None` when the class
+/// is not closed.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() {
+        let start = pattern[i];
+        if start == ']' && !first {
+            return Some((matched != negated, &pattern[i + 1..]));
+        }
+        first = false;
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|end| *end != ']') {
+            matched |= start <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= start == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Glob matching where, with `pathname`, `*` and `?` stay within a path
+/// component and only `**` spans several of them. Without it, `*` and `?`
+/// match `/` like any other character.
+fn glob_match(pattern: &[char], text: &[char], pathname: bool) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            // `**/` also matches no directory at all
+            if rest.first() == Some(&'/') && glob_match(&rest[1..], text, pathname) {
+                return true;
+            }
+            (0..=text.len()).any(|i| glob_match(rest, &text[i..], pathname))
+        }
+        Some('*') => {
+            for i in 0..=text.len() {
+                if glob_match(&pattern[1..], &text[i..], pathname) {
+                    return true;
+                }
+                if pathname && i < text.len() && text[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => match text.first() {
+            Some(c) if !pathname || *c != '/' => glob_match(&pattern[1..], &text[1..], pathname),
+            _ => false,
+        },
+        Some('[') => match text.first() {
+            None => false,
+            Some(c) => match match_class(pattern, *c) {
+                Some((true, rest)) => glob_match(rest, &text[1..], pathname),
+                Some((false, _)) => false,
+                None => *c == '[' && glob_match(&pattern[1..], &text[1..], pathname),
+            },
+        },
+        Some('\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..], pathname)
+        }
+        Some(c) => text.first() == Some(c) && glob_match(&pattern[1..], &text[1..], pathname),
+    }
+}
+
+pub fn wildmatch(pattern: &str, text: &str, pathname: bool) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match(&pattern, &text, pathname)
+}
+
+fn read_rules(path: &str, base: &str) -> Vec<IgnoreRule> {
+    match fs::read_to_string(path) {
+        Err(_) => Vec::new(),
+        Ok(content) => content
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| IgnoreRule::parse(line, base, path, i + 1))
+            .collect(),
+    }
+}
+
+/// The ignore file of a directory is read the first time a path inside it is
+/// looked up. The default value has no rules and reads no files.
+#[derive(Default)]
+pub struct IgnoreRules {
+    exclude: Vec<IgnoreRule>,
+    dirs: HashMap<String, Vec<IgnoreRule>>,
+    read_files: bool,
+}
+
+impl IgnoreRules {
+    pub fn load() -> Self {
+        IgnoreRules {
+            exclude: read_rules(EXCLUDE_FILE, ""),
+            dirs: HashMap::new(),
+            read_files: true,
+        }
+    }
+
+    pub fn add_rules(&mut self, dir: &str, content: &str) {
+        let source = String::from(dir) + IGNORE_FILE;
+        let rules = content
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| IgnoreRule::parse(line, dir, &source, i + 1));
+        self.dirs
+            .entry(String::from(dir))
+            .or_default()
+            .extend(rules);
+    }
+
+    fn dir_rules(&mut self, dir: &str) -> &Vec<IgnoreRule> {
+        let read_files = self.read_files;
+        self.dirs.entry(String::from(dir)).or_insert_with(|| {
+            if read_files {
+                read_rules(&(String::from(dir) + IGNORE_FILE), dir)
+            } else {
+                Vec::new()
+            }
+        })
+    }
+
+    /// The last rule matching `path` itself, looking at the deepest ignore
+    /// file first and at the exclude file last.
+    fn last_match(&mut self, path: &str, is_dir: bool) -> Option<IgnoreRule> {
+        let mut dirs: Vec<&str> = path.match_indices('/').map(|(i, _)| &path[..=i]).collect();
+        dirs.insert(0, "");
+        for dir in dirs.into_iter().rev() {
+            let rule = self
+                .dir_rules(dir)
+                .iter()
+                .rev()
+                .find(|rule| rule.matches(path, is_dir));
+            if let Some(rule) = rule {
+                return Some(rule.clone());
+            }
+        }
+        self.exclude
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path, is_dir))
+            .cloned()
+    }
+
+    /// Nothing inside an ignored directory can be re-included, so such a path
+    /// is decided by the directory's rule.
+    pub fn matching(&mut self, path: &str, is_dir: bool) -> Option<IgnoreRule> {
+        let path = path.trim_end_matches('/');
+        let parents: Vec<usize> = path.match_indices('/').map(|(i, _)| i).collect();
+        for i in parents {
+            if let Some(rule) = self.last_match(&path[..i], true) {
+                if !rule.negated {
+                    return Some(rule);
+                }
+            }
+        }
+        self.last_match(path, is_dir)
+    }
+
+    pub fn is_ignored(&mut self, path: &str, is_dir: bool) -> bool {
+        matches!(self.matching(path, is_dir), Some(rule) if !rule.negated)
+    }
+}
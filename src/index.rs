@@ -1,20 +1,256 @@
 use crate::blob;
-use std::collections::HashMap;
+use crate::encoding;
+use crate::store;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
 
+/// Stands in for the hash of a path the next commit deletes.
+pub const DELETED: &str = "-";
+
+const MAGIC: [u8; 4] = *b"YIDX";
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Stat {
+    pub size: u64,
+    pub mtime: (i64, u32),
+    pub ctime: (i64, u32),
+    pub inode: u64,
+    pub mode: u32,
+}
+
+impl Stat {
+    #[cfg(unix)]
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Stat {
+            size: metadata.len(),
+            mtime: (metadata.mtime(), metadata.mtime_nsec() as u32),
+            ctime: (metadata.ctime(), metadata.ctime_nsec() as u32),
+            inode: metadata.ino(),
+            mode: metadata.mode(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        let mtime = match metadata
+            .modified()
+            .map(|time| time.duration_since(std::time::UNIX_EPOCH))
+        {
+            Ok(Ok(since)) => (since.as_secs() as i64, since.subsec_nanos()),
+            _ => (0, 0),
+        };
+        Stat {
+            size: metadata.len(),
+            mtime,
+            ..Stat::default()
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CachedStat {
+    pub stat: Stat,
+    pub hash: String,
+}
+
 #[derive(std::clone::Clone)]
 pub struct Index {
+    /// staged blob ids by path, on top of the files of HEAD
     pub index_map: HashMap<String, String>,
+    pub stat_cache: HashMap<String, CachedStat>,
     pub file_path: String,
+    pub format: encoding::ObjectFormat,
+    /// when the file was last written; files changed in the same instant
+    /// might have changed after they were hashed
+    written: (i64, u32),
+    stat_changed: bool,
 }
 
+#[derive(Debug)]
 pub enum IndexError {
     IOError,
+    Corrupt,
+    UnsupportedVersion(u32),
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], IndexError> {
+        match self.data.get(self.pos..self.pos + len) {
+            None => Err(IndexError::Corrupt),
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, IndexError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, IndexError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, IndexError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn time(&mut self) -> Result<(i64, u32), IndexError> {
+        Ok((self.u64()? as i64, self.u32()?))
+    }
+
+    fn string(&mut self, len: usize) -> Result<String, IndexError> {
+        match String::from_utf8(self.take(len)?.to_vec()) {
+            Err(_) => Err(IndexError::Corrupt),
+            Ok(text) => Ok(text),
+        }
+    }
+}
+
+fn checksum(data: &[u8], format: encoding::ObjectFormat) -> Vec<u8> {
+    let mut hasher = format.hasher();
+    hasher.input(data);
+    let mut sum = vec![0; format.raw_len()];
+    hasher.result(&mut sum);
+    sum
+}
+
+/// Parses the binary index:
+///
+/// ```text
+/// "YIDX" version:u32 count:u32
+/// count entries, sorted by path:
+///   path_len:u16 path
+///   staged_len:u8 staged id, empty when the path isn't staged
+///   size:u64 mtime:(i64, u32) ctime:(i64, u32) inode:u64 mode:u32
+///   cached_len:u8 cached id, empty when no stat data is cached
+/// hash of everything before, in the repository's object format
+/// ```
+pub fn decode(
+    data: &[u8],
+    file_path: String,
+    format: encoding::ObjectFormat,
+) -> Result<Index, IndexError> {
+    let sum_len = format.raw_len();
+    if data.len() < MAGIC.len() + 8 + sum_len || data[..MAGIC.len()] != MAGIC {
+        return Err(IndexError::Corrupt);
+    }
+    let (content, sum) = data.split_at(data.len() - sum_len);
+    if checksum(content, format) != sum {
+        return Err(IndexError::Corrupt);
+    }
+    let mut reader = Reader {
+        data: content,
+        pos: MAGIC.len(),
+    };
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(IndexError::UnsupportedVersion(version));
+    }
+    let mut index = Index::empty(file_path, format);
+    for _ in 0..reader.u32()? {
+        let len = reader.u16()? as usize;
+        let path = reader.string(len)?;
+        let len = reader.take(1)?[0] as usize;
+        let staged = reader.string(len)?;
+        let stat = Stat {
+            size: reader.u64()?,
+            mtime: reader.time()?,
+            ctime: reader.time()?,
+            inode: reader.u64()?,
+            mode: reader.u32()?,
+        };
+        let len = reader.take(1)?[0] as usize;
+        let hash = reader.string(len)?;
+        if !staged.is_empty() {
+            index.index_map.insert(path.clone(), staged);
+        }
+        if !hash.is_empty() {
+            index.stat_cache.insert(path, CachedStat { stat, hash });
+        }
+    }
+    if reader.pos != content.len() {
+        return Err(IndexError::Corrupt);
+    }
+    Ok(index)
+}
+
+pub fn encode(
+    index_map: &HashMap<String, String>,
+    stat_cache: &HashMap<String, CachedStat>,
+    format: encoding::ObjectFormat,
+) -> Result<Vec<u8>, IndexError> {
+    let paths: BTreeSet<&String> = index_map.keys().chain(stat_cache.keys()).collect();
+    let mut data = Vec::new();
+    data.extend(&MAGIC);
+    data.extend(&VERSION.to_be_bytes());
+    data.extend(&(paths.len() as u32).to_be_bytes());
+    for path in paths {
+        let staged = index_map.get(path).map_or("", |hash| hash.as_str());
+        let cached = stat_cache.get(path);
+        let stat = cached.map_or(Stat::default(), |cached| cached.stat);
+        let hash = cached.map_or("", |cached| cached.hash.as_str());
+        if path.len() > u16::MAX as usize || staged.len() > 255 || hash.len() > 255 {
+            return Err(IndexError::Corrupt);
+        }
+        data.extend(&(path.len() as u16).to_be_bytes());
+        data.extend(path.as_bytes());
+        data.push(staged.len() as u8);
+        data.extend(staged.as_bytes());
+        data.extend(&stat.size.to_be_bytes());
+        for (secs, nanos) in [stat.mtime, stat.ctime] {
+            data.extend(&secs.to_be_bytes());
+            data.extend(&nanos.to_be_bytes());
+        }
+        data.extend(&stat.inode.to_be_bytes());
+        data.extend(&stat.mode.to_be_bytes());
+        data.push(hash.len() as u8);
+        data.extend(hash.as_bytes());
+    }
+    let sum = checksum(&data, format);
+    data.extend(sum);
+    Ok(data)
+}
+
+/// The index of the first versions: "path hash" lines without stat data.
+fn decode_text(contents: &str) -> HashMap<String, String> {
+    let mut index_map = HashMap::new();
+    for line in contents.lines() {
+        // the id never holds a space, the path may
+        if let Some((path, hash)) = line.rsplit_once(' ') {
+            index_map.insert(String::from(path), String::from(hash));
+        }
+    }
+    index_map
 }
 
 impl Index {
-    pub fn new(file_path: String) -> Result<Self, IndexError> {
+    pub fn empty(file_path: String, format: encoding::ObjectFormat) -> Self {
+        Index {
+            index_map: HashMap::new(),
+            stat_cache: HashMap::new(),
+            file_path,
+            format,
+            written: (0, 0),
+            stat_changed: false,
+        }
+    }
+
+    pub fn new(file_path: String, format: encoding::ObjectFormat) -> Result<Self, IndexError> {
         let mut res = File::open(file_path.clone());
         if res.is_err() {
             let res_cr = File::create(file_path.clone());
@@ -26,67 +262,167 @@ impl Index {
         match res {
             Err(_) => Err(IndexError::IOError),
             Ok(mut file) => {
-                let mut contents = String::new();
-                let read_res = file.read_to_string(&mut contents);
+                let mut contents = Vec::new();
+                let read_res = file.read_to_end(&mut contents);
                 if read_res.is_err() {
                     return Err(IndexError::IOError);
                 }
-                let mut words = contents.split_whitespace().peekable();
-                let mut index_map = HashMap::new();
-                while words.peek().is_some() {
-                    let chunk: Vec<&str> = words.by_ref().take(2).collect();
-                    index_map.insert(String::from(chunk[0]), String::from(chunk[1]));
+                if contents.starts_with(&MAGIC) {
+                    let mut index = decode(&contents, file_path, format)?;
+                    if let Ok(metadata) = file.metadata() {
+                        index.written = Stat::from_metadata(&metadata).mtime;
+                    }
+                    Ok(index)
+                } else {
+                    match String::from_utf8(contents) {
+                        Err(_) => Err(IndexError::Corrupt),
+                        Ok(contents) => Ok(Index {
+                            index_map: decode_text(&contents),
+                            ..Index::empty(file_path, format)
+                        }),
+                    }
                 }
-                Ok(Index {
-                    index_map,
-                    file_path: file_path.clone(),
-                })
             }
         }
     }
 
     pub fn tracks_file(self, path: String) -> bool {
-        return self.index_map.contains_key(&path);
+        self.index_map.contains_key(&path)
     }
 
-    pub fn has_different_hash(self, path: String) -> bool {
-        let blob = blob::Blob::new(path.clone());
-        match blob.hash_object(false) {
+    pub fn has_different_hash(mut self, store: &dyn store::ObjectStore, path: String) -> bool {
+        match self.hash_file(store, &path, false) {
             Err(_) => false,
+            Ok(hash) => self.index_map.get(&path) != Some(&hash),
+        }
+    }
+
+    /// Only hashes `path` when its stat data changed since it was last hashed.
+    pub fn hash_file(
+        &mut self,
+        store: &dyn store::ObjectStore,
+        path: &str,
+        write: bool,
+    ) -> Result<String, IndexError> {
+        let stat = match fs::symlink_metadata(path) {
+            Err(_) => return Err(IndexError::IOError),
+            Ok(metadata) => Stat::from_metadata(&metadata),
+        };
+        if let Some(cached) = self.stat_cache.get(path) {
+            // a file changed in the instant the index was written may have
+            // changed after it was hashed without its stat data showing it
+            let racy = stat.mtime >= self.written;
+            let stored = !write
+                || store::ObjectId::from_hex(&cached.hash, store.format())
+                    .is_some_and(|id| store.exists(&id));
+            if cached.stat == stat && !racy && stored {
+                return Ok(cached.hash.clone());
+            }
+        }
+        match blob::Blob::new(String::from(path)).hash_object(store, write) {
+            Err(_) => Err(IndexError::IOError),
             Ok(hash) => {
-                return self.index_map[&path] != hash;
+                let cached = CachedStat {
+                    stat,
+                    hash: hash.clone(),
+                };
+                if self.stat_cache.get(path) != Some(&cached) {
+                    self.stat_cache.insert(String::from(path), cached);
+                    self.stat_changed = true;
+                }
+                Ok(hash)
             }
         }
     }
 
+    pub fn cache_stat(&mut self, path: &str, hash: &str) {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            let cached = CachedStat {
+                stat: Stat::from_metadata(&metadata),
+                hash: String::from(hash),
+            };
+            self.stat_cache.insert(String::from(path), cached);
+            self.stat_changed = true;
+        }
+    }
+
+    pub fn retain_stats(&mut self, keep: impl Fn(&str) -> bool) {
+        let before = self.stat_cache.len();
+        self.stat_cache.retain(|path, _| keep(path));
+        self.stat_changed |= self.stat_cache.len() != before;
+    }
+
+    pub fn stat_changed(&self) -> bool {
+        self.stat_changed
+    }
+
     pub fn add_obj(
         mut self,
+        store: &dyn store::ObjectStore,
         path: String,
         full_path: String,
         index_path: String,
     ) -> Result<(), IndexError> {
         let blob = blob::Blob::new(full_path.clone());
-        match blob.hash_object(true) {
+        match blob.hash_object(store, true) {
             Err(_) => Err(IndexError::IOError),
             Ok(hash) => {
                 self.index_map.insert(path.clone(), hash);
-                match File::create(index_path) {
-                    Err(_) => Err(IndexError::IOError),
-                    Ok(mut file) => match file.set_len(0) {
-                        Err(_) => Err(IndexError::IOError),
-                        Ok(_) => {
-                            for (key, val) in self.index_map {
-                                let line = key + " " + &val + "\n";
-                                let res = file.write_all(line.as_bytes());
-                                if res.is_err() {
-                                    return Err(IndexError::IOError);
-                                }
-                            }
-                            Ok(())
-                        }
-                    },
-                }
+                self.file_path = index_path;
+                self.write()
             }
         }
     }
+
+    pub fn add_content(
+        &mut self,
+        store: &dyn store::ObjectStore,
+        path: String,
+        content: &[u8],
+    ) -> Result<(), IndexError> {
+        match store.put(store::ObjectKind::Blob, content) {
+            Err(_) => Err(IndexError::IOError),
+            Ok(id) => {
+                self.index_map.insert(path, id.to_string());
+                self.write()
+            }
+        }
+    }
+
+    /// Writes the index. Stat data of files changed in the instant the file
+    /// is written is dropped, since they could change again unnoticed.
+    pub fn write(&self) -> Result<(), IndexError> {
+        let data = encode(&self.index_map, &self.stat_cache, self.format)?;
+        let written = write_file(&self.file_path, &data)?;
+        let mut stat_cache = self.stat_cache.clone();
+        stat_cache.retain(|_, cached| cached.stat.mtime < written);
+        if stat_cache.len() != self.stat_cache.len() {
+            write_file(
+                &self.file_path,
+                &encode(&self.index_map, &stat_cache, self.format)?,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn write_file(path: &str, data: &[u8]) -> Result<(i64, u32), IndexError> {
+    match File::create(path) {
+        Err(_) => Err(IndexError::IOError),
+        Ok(mut file) => match file.write_all(data).and_then(|_| file.metadata()) {
+            Err(_) => Err(IndexError::IOError),
+            Ok(metadata) => Ok(Stat::from_metadata(&metadata).mtime),
+        },
+    }
+}
+
+/// Paths staged as `DELETED` are dropped from `tree_map`.
+pub fn apply(tree_map: &mut HashMap<String, String>, index_map: HashMap<String, String>) {
+    for (path, hash) in index_map {
+        if hash == DELETED {
+            tree_map.remove(&path);
+        } else {
+            tree_map.insert(path, hash);
+        }
+    }
 }
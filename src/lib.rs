@@ -1,10 +1,27 @@
 pub mod blob;
 pub mod branch;
+pub mod commandparser;
 pub mod commit;
+pub mod commitgraph;
+pub mod config;
+pub mod diff;
+pub mod encoding;
 pub mod file;
+pub mod fsck;
+pub mod gc;
+pub mod identity;
+pub mod ignore;
 pub mod index;
+pub mod log;
+pub mod merge;
+pub mod message;
+pub mod object;
+pub mod pack;
+pub mod patch;
+pub mod pathspec;
+pub mod reflog;
 pub mod repo;
+pub mod revision;
+pub mod status;
+pub mod store;
 pub mod tree;
-pub mod merge;
-pub mod commandparser;
-pub mod diff;
@@ -0,0 +1,362 @@
+use crate::commit;
+use crate::gc;
+use crate::identity;
+use crate::object;
+use crate::revision;
+use crate::store;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+pub enum LogError {
+    MissingCommit(String),
+    InvalidId,
+}
+
+fn commit_error(err: commit::CommitError) -> LogError {
+    match err {
+        commit::CommitError::MissingCommit(hash) => LogError::MissingCommit(hash),
+        _ => LogError::InvalidId,
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum Format {
+    #[default]
+    Medium,
+    Oneline,
+    Custom(String),
+}
+
+#[derive(Clone, Default)]
+pub struct LogOptions {
+    pub max_count: Option<usize>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    /// substring of the author's `Name <email>`
+    pub author: Option<String>,
+    pub paths: Vec<String>,
+    pub first_parent: bool,
+    pub format: Format,
+}
+
+pub struct LogEntry {
+    pub id: store::ObjectId,
+    pub commit: object::Commit,
+    pub author: Option<identity::Signature>,
+    pub committer: Option<identity::Signature>,
+}
+
+impl LogEntry {
+    fn new(id: store::ObjectId, commit: object::Commit) -> Self {
+        LogEntry {
+            author: commit.header("author").and_then(identity::Signature::parse),
+            committer: commit
+                .header("committer")
+                .and_then(identity::Signature::parse),
+            id,
+            commit,
+        }
+    }
+
+    /// Commits written before identities were recorded sort as the oldest.
+    fn time(&self) -> i64 {
+        self.committer
+            .as_ref()
+            .map_or(0, |committer| committer.time)
+    }
+}
+
+/// Parses `--since`/`--until` values: unix times (`1600000000`, `@1600000000`),
+/// `YYYY-MM-DD` (UTC midnight) or relative ones like `2.weeks.ago`.
+pub fn parse_time(value: &str) -> Option<i64> {
+    if let Some((time, _)) = identity::parse_date(value) {
+        return Some(time);
+    }
+    if let Ok(tm) = time::strptime(value, "%Y-%m-%d") {
+        return Some(tm.to_timespec().sec);
+    }
+    match gc::parse_expire(value) {
+        Ok(Some(duration)) => Some(time::get_time().sec - duration.as_secs() as i64),
+        _ => None,
+    }
+}
+
+fn read_commit(store: &dyn store::ObjectStore, id: &store::ObjectId) -> Result<LogEntry, LogError> {
+    match store.read(id) {
+        Ok(object::Object::Commit(commit)) => Ok(LogEntry::new(id.clone(), commit)),
+        _ => Err(LogError::MissingCommit(id.to_string())),
+    }
+}
+
+/// Only reads the subtrees on the way to `path`.
+fn id_at(
+    store: &dyn store::ObjectStore,
+    tree_id: &store::ObjectId,
+    path: &str,
+    cache: &mut HashMap<store::ObjectId, Vec<object::TreeEntry>>,
+) -> Option<store::ObjectId> {
+    let components: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    let mut id = tree_id.clone();
+    for (depth, component) in components.iter().enumerate() {
+        if !cache.contains_key(&id) {
+            match store.read(&id) {
+                Ok(object::Object::Tree(entries)) => cache.insert(id.clone(), entries),
+                _ => return None,
+            };
+        }
+        // yit trees name blobs by their full path, git trees by their base name
+        let full_path = components[..=depth].join("/");
+        let entry = cache[&id]
+            .iter()
+            .find(|entry| entry.name == *component || entry.name == full_path)?;
+        if depth + 1 < components.len() && entry.kind() != store::ObjectKind::Tree {
+            return None;
+        }
+        id = entry.id.clone();
+    }
+    Some(id)
+}
+
+fn ids_at(
+    store: &dyn store::ObjectStore,
+    tree_id: &store::ObjectId,
+    paths: &[String],
+    cache: &mut HashMap<store::ObjectId, Vec<object::TreeEntry>>,
+) -> Vec<Option<store::ObjectId>> {
+    paths
+        .iter()
+        .map(|path| id_at(store, tree_id, path, cache))
+        .collect()
+}
+
+/// Whether the commit changes anything under `paths` compared to each of the
+/// parents the walk follows; a root commit does if it contains any of them.
+fn touches_paths(
+    store: &dyn store::ObjectStore,
+    entry: &LogEntry,
+    options: &LogOptions,
+    cache: &mut HashMap<store::ObjectId, Vec<object::TreeEntry>>,
+) -> Result<bool, LogError> {
+    let ids = ids_at(store, &entry.commit.tree, &options.paths, cache);
+    if entry.commit.parents.is_empty() {
+        return Ok(ids.iter().any(Option::is_some));
+    }
+    let parents = if options.first_parent {
+        1
+    } else {
+        entry.commit.parents.len()
+    };
+    for parent in entry.commit.parents.iter().take(parents) {
+        let parent = read_commit(store, parent)?;
+        if ids_at(store, &parent.commit.tree, &options.paths, cache) == ids {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn matches(
+    store: &dyn store::ObjectStore,
+    entry: &LogEntry,
+    options: &LogOptions,
+    cache: &mut HashMap<store::ObjectId, Vec<object::TreeEntry>>,
+) -> Result<bool, LogError> {
+    if options.since.is_some_and(|since| entry.time() < since)
+        || options.until.is_some_and(|until| entry.time() > until)
+    {
+        return Ok(false);
+    }
+    if let Some(pattern) = &options.author {
+        let author = match &entry.author {
+            None => String::from(""),
+            Some(author) => format!("{} <{}>", author.name, author.email),
+        };
+        if !author.contains(pattern.as_str()) {
+            return Ok(false);
+        }
+    }
+    if options.paths.is_empty() {
+        return Ok(true);
+    }
+    touches_paths(store, entry, options, cache)
+}
+
+pub fn walk(
+    store: &dyn store::ObjectStore,
+    graph: &mut commit::CommitGraph,
+    set: &revision::CommitSet,
+    options: &LogOptions,
+) -> Result<Vec<LogEntry>, LogError> {
+    let ids = |ids: &[store::ObjectId]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+    // excluded commits are never walked, and neither are their ancestors
+    let mut excluded = HashSet::new();
+    for node in graph.ancestors_of(&ids(&set.exclude)) {
+        match node {
+            Err(err) => return Err(commit_error(err)),
+            Ok(node) => excluded.insert(node.hash.clone()),
+        };
+    }
+
+    let mut entries = Vec::new();
+    let mut cache = HashMap::new();
+    let mut walk = graph.ancestors_of(&ids(&set.include)).hide(excluded);
+    if options.first_parent {
+        walk = walk.first_parent();
+    }
+    for node in walk {
+        if options
+            .max_count
+            .is_some_and(|max_count| entries.len() >= max_count)
+        {
+            break;
+        }
+        let entry = match node {
+            Err(err) => return Err(commit_error(err)),
+            Ok(node) => match store::ObjectId::from_hex(&node.hash, store.format()) {
+                None => return Err(LogError::InvalidId),
+                Some(id) => read_commit(store, &id)?,
+            },
+        };
+        if matches(store, &entry, options, &mut cache)? {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Formats a time like git's default date format: `Mon Sep 14 02:26:40 2020 +0200`.
+pub fn format_date(signature: &identity::Signature) -> String {
+    let tm = time::at_utc(time::Timespec::new(
+        signature.time + i64::from(signature.offset) * 60,
+        0,
+    ));
+    let day = tm.strftime("%a %b").map(|day| day.to_string());
+    let clock = tm.strftime("%H:%M:%S %Y").map(|clock| clock.to_string());
+    format!(
+        "{} {} {} {}",
+        day.unwrap_or_default(),
+        tm.tm_mday,
+        clock.unwrap_or_default(),
+        signature.format_offset()
+    )
+}
+
+fn subject(message: &str) -> String {
+    message.lines().next().unwrap_or("").to_string()
+}
+
+fn body(message: &str) -> String {
+    match message.find('\n') {
+        None => String::from(""),
+        Some(pos) => String::from(message[pos + 1..].trim_start_matches('\n')),
+    }
+}
+
+fn expand(store: &dyn store::ObjectStore, entry: &LogEntry, template: &str) -> String {
+    let signature_field = |signature: &Option<identity::Signature>, field: char| {
+        let signature = match signature {
+            None => return String::from(""),
+            Some(signature) => signature,
+        };
+        match field {
+            'n' => signature.name.clone(),
+            'e' => signature.email.clone(),
+            'd' => format_date(signature),
+            't' => signature.time.to_string(),
+            _ => String::from(""),
+        }
+    };
+    let join = |ids: &[store::ObjectId], abbreviated: bool| {
+        ids.iter()
+            .map(|id| {
+                if abbreviated {
+                    revision::abbreviate(store, id)
+                } else {
+                    id.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    let mut output = String::from("");
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('H') => output.push_str(entry.id.as_str()),
+            Some('h') => output.push_str(&revision::abbreviate(store, &entry.id)),
+            Some('T') => output.push_str(entry.commit.tree.as_str()),
+            Some('t') => output.push_str(&revision::abbreviate(store, &entry.commit.tree)),
+            Some('P') => output.push_str(&join(&entry.commit.parents, false)),
+            Some('p') => output.push_str(&join(&entry.commit.parents, true)),
+            Some('s') => output.push_str(&subject(&entry.commit.message)),
+            Some('b') => output.push_str(&body(&entry.commit.message)),
+            Some('n') => output.push('\n'),
+            Some('%') => output.push('%'),
+            Some(role @ 'a') | Some(role @ 'c') => {
+                let signature = if role == 'a' {
+                    &entry.author
+                } else {
+                    &entry.committer
+                };
+                match chars.peek().copied() {
+                    Some(field @ ('n' | 'e' | 'd' | 't')) => {
+                        chars.next();
+                        output.push_str(&signature_field(signature, field));
+                    }
+                    _ => {
+                        output.push('%');
+                        output.push(role);
+                    }
+                }
+            }
+            // unknown placeholders are kept as they are
+            Some(other) => {
+                output.push('%');
+                output.push(other);
+            }
+            None => output.push('%'),
+        }
+    }
+    output
+}
+
+pub fn format_entry(store: &dyn store::ObjectStore, entry: &LogEntry, format: &Format) -> String {
+    match format {
+        Format::Oneline => expand(store, entry, "%h %s"),
+        Format::Custom(template) => expand(store, entry, template),
+        Format::Medium => {
+            let mut output = String::from("commit ") + entry.id.as_str() + "\n";
+            if entry.commit.parents.len() > 1 {
+                output.push_str(&expand(store, entry, "Merge: %p\n"));
+            }
+            if entry.author.is_some() {
+                output.push_str(&expand(store, entry, "Author: %an <%ae>\nDate:   %ad\n"));
+            }
+            output.push('\n');
+            for line in entry.commit.message.trim_end_matches('\n').lines() {
+                output.push_str("    ");
+                output.push_str(line);
+                output.push('\n');
+            }
+            output
+        }
+    }
+}
+
+pub fn format_log(store: &dyn store::ObjectStore, entries: &[LogEntry], format: &Format) -> String {
+    let mut output = String::from("");
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 && *format == Format::Medium {
+            output.push('\n');
+        }
+        output.push_str(&format_entry(store, entry, format));
+        if *format != Format::Medium {
+            output.push('\n');
+        }
+    }
+    output
+}
@@ -1,4 +1,5 @@
 use crate::file;
+use crate::store;
 use crate::tree;
 use std::collections::HashMap;
 use std::fs::File;
@@ -11,83 +12,82 @@ pub enum MergeError {
 }
 
 pub fn three_fold(
+    store: &dyn store::ObjectStore,
     parent_index_map: HashMap<String, String>,
     branch_index_map: HashMap<String, String>,
     into_branch_index_map: HashMap<String, String>,
 ) -> Result<String, MergeError> {
     let mut tree = tree::Tree::new(HashMap::new());
-    for (key, _) in branch_index_map.clone() {
+    for (key, val) in branch_index_map.clone() {
         if !parent_index_map.contains_key(&key) {
             if into_branch_index_map.contains_key(&key) {
                 return Err(MergeError::DoesNotHaveOrigin);
             }
-            tree.add_blob(key.clone(), key);
+            tree.add_blob(key, val);
         }
     }
 
-    for (key, _) in into_branch_index_map.clone() {
+    for (key, val) in into_branch_index_map.clone() {
         if !parent_index_map.contains_key(&key) {
             if branch_index_map.contains_key(&key) {
                 return Err(MergeError::DoesNotHaveOrigin);
             }
-            tree.add_blob(key.clone(), key);
+            tree.add_blob(key, val);
         }
     }
     for (key, _) in branch_index_map.clone() {
-        if into_branch_index_map.clone().contains_key(&key) {
+        if into_branch_index_map.contains_key(&key) {
             let new_file_content = merge_blobs(
+                store,
                 &parent_index_map[&key],
                 &branch_index_map[&key],
                 &into_branch_index_map[&key],
             )?;
             let mut file = File::create(key.clone()).unwrap();
-            let res = file.write_all(new_file_content.as_bytes());
+            let res = file.write_all(&new_file_content);
             if res.is_err() {
                 return Err(MergeError::IOError);
             }
-            tree.add_blob(key.clone(), key);
+            match store.put(store::ObjectKind::Blob, &new_file_content) {
+                Err(_) => return Err(MergeError::IOError),
+                Ok(id) => tree.add_blob(key, id.to_string()),
+            }
         }
     }
-    let tree_hash = tree.hash_tree();
+    let tree_hash = tree.hash_tree(store);
     Ok(tree_hash)
 }
 
 pub fn merge_blobs(
+    store: &dyn store::ObjectStore,
     hash_parent_blob: &str,
     hash_blob: &str,
     hash_into_blob: &str,
-) -> Result<String, MergeError> {
-    match file::cat_file(String::from(hash_parent_blob)) {
+) -> Result<Vec<u8>, MergeError> {
+    match file::cat_file(store, String::from(hash_parent_blob)) {
         Err(_) => Err(MergeError::IOError),
-        Ok(parent_blob) => match file::cat_file(String::from(hash_blob)) {
+        Ok(parent_blob) => match file::cat_file(store, String::from(hash_blob)) {
             Err(_) => Err(MergeError::IOError),
-            Ok(blob) => match file::cat_file(String::from(hash_into_blob)) {
+            Ok(blob) => match file::cat_file(store, String::from(hash_into_blob)) {
                 Err(_) => Err(MergeError::IOError),
                 Ok(into_blob) => {
-                    let parent_blob_lines: Vec<&str> = parent_blob.split('\n').collect();
-                    let blob_lines: Vec<&str> = blob.split('\n').collect();
-                    let into_blob_lines: Vec<&str> = into_blob.split('\n').collect();
-                    let mut new_file: String = String::from("");
-                    let mut cnt = 0;
-                    for line in into_blob_lines {
-                        if cnt >= blob_lines.len() {
-                            new_file.push_str(&(String::from(line) + "\n"));
+                    let parent_blob_lines: Vec<&[u8]> =
+                        parent_blob.split(|byte| *byte == b'\n').collect();
+                    let blob_lines: Vec<&[u8]> = blob.split(|byte| *byte == b'\n').collect();
+                    let into_blob_lines: Vec<&[u8]> =
+                        into_blob.split(|byte| *byte == b'\n').collect();
+                    let mut new_file: Vec<u8> = Vec::new();
+                    for (cnt, line) in into_blob_lines.iter().enumerate() {
+                        if cnt >= blob_lines.len()
+                            || *line == blob_lines[cnt]
+                            || (cnt < parent_blob_lines.len()
+                                && blob_lines[cnt] == parent_blob_lines[cnt])
+                        {
+                            new_file.extend_from_slice(line);
+                            new_file.push(b'\n');
                         } else {
-                            if line == blob_lines[cnt] {
-                                new_file.push_str(&(String::from(line) + "\n"));
-                            } else {
-                                if cnt >= parent_blob_lines.len() {
-                                    return Err(MergeError::Conflict);
-                                } else {
-                                    if blob_lines[cnt] == parent_blob_lines[cnt] {
-                                        new_file.push_str(&(String::from(line) + "\n"));
-                                    } else {
-                                        return Err(MergeError::Conflict);
-                                    }
-                                }
-                            }
+                            return Err(MergeError::Conflict);
                         }
-                        cnt += 1;
                     }
                     Ok(new_file)
                 }
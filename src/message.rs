@@ -0,0 +1,81 @@
+use crate::config;
+use std::env;
+use std::fs;
+use std::process::Command;
+
+#[derive(Debug)]
+pub enum MessageError {
+    IOError,
+    EditorFailed(String),
+}
+
+/// Squeezes blank lines and drops trailing whitespace, and with
+/// `strip_comments` the `#` lines of an edited message.
+pub fn cleanup(message: &str, strip_comments: bool) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in message.lines() {
+        if strip_comments && line.starts_with('#') {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+pub fn template(branch: Option<&str>, changes: &[(String, String)]) -> String {
+    let mut template = String::from("\n");
+    template.push_str("# Please enter the commit message for your changes. Lines starting\n");
+    template.push_str("# with '#' will be ignored, and an empty message aborts the commit.\n");
+    template.push_str("#\n");
+    match branch {
+        Some(branch) => template.push_str(&format!("# On branch {}\n", branch)),
+        None => template.push_str("# HEAD detached\n"),
+    }
+    if !changes.is_empty() {
+        template.push_str("#\n# Changes to be committed:\n");
+        for (status, path) in changes {
+            template.push_str(&format!("#\t{:<12}{}\n", format!("{}:", status), path));
+        }
+    }
+    template.push_str("#\n");
+    template
+}
+
+/// The editor to run: `YIT_EDITOR`, `core.editor`, `VISUAL`, `EDITOR` and
+/// finally `vi`, whichever is set first.
+pub fn editor() -> String {
+    let from_env = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+    from_env("YIT_EDITOR")
+        .or_else(|| config::Config::merged().get("core.editor"))
+        .or_else(|| from_env("VISUAL"))
+        .or_else(|| from_env("EDITOR"))
+        .unwrap_or_else(|| String::from("vi"))
+}
+
+/// The editor goes through the shell, so it may carry arguments.
+pub fn edit(path: &str, template: &str) -> Result<String, MessageError> {
+    if fs::write(path, template).is_err() {
+        return Err(MessageError::IOError);
+    }
+    let editor = editor();
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(editor.clone() + " \"$@\"")
+        .arg(editor.clone())
+        .arg(path)
+        .status();
+    match status {
+        Ok(status) if status.success() => match fs::read_to_string(path) {
+            Err(_) => Err(MessageError::IOError),
+            Ok(message) => Ok(message),
+        },
+        _ => Err(MessageError::EditorFailed(editor)),
+    }
+}
@@ -0,0 +1,495 @@
+use crate::encoding::{Encoding, ObjectFormat};
+use crate::store::{ObjectId, ObjectKind};
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum ObjectError {
+    InvalidCompression,
+    InvalidPackEntry,
+    MalformedHeader,
+    UnknownType(String),
+    LengthMismatch { expected: usize, actual: usize },
+    InvalidUtf8,
+    InvalidId(String),
+    MissingField(&'static str),
+    InvalidTreeEntry(usize),
+    InvalidMode(String),
+}
+
+impl fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjectError::InvalidCompression => write!(f, "object is not a valid zlib stream"),
+            ObjectError::InvalidPackEntry => write!(f, "corrupt pack entry"),
+            ObjectError::MalformedHeader => write!(f, "malformed object header"),
+            ObjectError::UnknownType(kind) => write!(f, "unknown object type '{}'", kind),
+            ObjectError::LengthMismatch { expected, actual } => write!(
+                f,
+                "header declares {} bytes but object has {}",
+                expected, actual
+            ),
+            ObjectError::InvalidUtf8 => write!(f, "object is not valid UTF-8"),
+            ObjectError::InvalidId(id) => write!(f, "invalid object id '{}'", id),
+            ObjectError::MissingField(field) => write!(f, "missing '{}' field", field),
+            ObjectError::InvalidTreeEntry(entry) => write!(f, "malformed tree entry #{}", entry),
+            ObjectError::InvalidMode(mode) => write!(f, "invalid tree entry mode '{}'", mode),
+        }
+    }
+}
+
+pub const MODE_FILE: u32 = 0o100644;
+pub const MODE_TREE: u32 = 0o40000;
+pub const MODE_GITLINK: u32 = 0o160000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeEntry {
+    pub mode: u32,
+    /// Base name of the entry. Blob entries of yit-encoded trees store the full path.
+    pub name: String,
+    pub id: ObjectId,
+}
+
+impl TreeEntry {
+    pub fn kind(&self) -> ObjectKind {
+        if self.mode == MODE_TREE {
+            ObjectKind::Tree
+        } else {
+            ObjectKind::Blob
+        }
+    }
+
+    /// Git sorts tree entries by name, comparing directories as if they ended with '/'.
+    pub fn sort_key(&self) -> String {
+        if self.kind() == ObjectKind::Tree {
+            self.name.clone() + "/"
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Commit {
+    pub tree: ObjectId,
+    pub parents: Vec<ObjectId>,
+    pub headers: Vec<(String, String)>,
+    pub message: String,
+}
+
+impl Commit {
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tag {
+    pub object: ObjectId,
+    pub kind: ObjectKind,
+    pub name: String,
+    pub tagger: Option<String>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Object {
+    Blob(Vec<u8>),
+    Tree(Vec<TreeEntry>),
+    Commit(Commit),
+    Tag(Tag),
+}
+
+/// Accepts both the yit header (`"<type>\n"`) and git's (`"<type> <len>\0"`).
+pub fn split_header(data: &[u8]) -> Result<(ObjectKind, Encoding, &[u8]), ObjectError> {
+    let kind_end = match data.iter().position(|byte| *byte == b' ' || *byte == b'\n') {
+        None => return Err(ObjectError::MalformedHeader),
+        Some(pos) => pos,
+    };
+    let kind_name = String::from_utf8_lossy(&data[..kind_end]).to_string();
+    let kind = match ObjectKind::parse(&kind_name) {
+        None => return Err(ObjectError::UnknownType(kind_name)),
+        Some(kind) => kind,
+    };
+    if data[kind_end] == b'\n' {
+        return Ok((kind, Encoding::Yit, &data[kind_end + 1..]));
+    }
+    let len_end = match data[kind_end..].iter().position(|byte| *byte == 0) {
+        None => return Err(ObjectError::MalformedHeader),
+        Some(pos) => kind_end + pos,
+    };
+    let expected: usize = match std::str::from_utf8(&data[kind_end + 1..len_end]) {
+        Err(_) => return Err(ObjectError::MalformedHeader),
+        Ok(len) => match len.parse() {
+            Err(_) => return Err(ObjectError::MalformedHeader),
+            Ok(len) => len,
+        },
+    };
+    let body = &data[len_end + 1..];
+    if body.len() != expected {
+        return Err(ObjectError::LengthMismatch {
+            expected,
+            actual: body.len(),
+        });
+    }
+    Ok((kind, Encoding::Git, body))
+}
+
+fn parse_id(hex: &str, format: ObjectFormat) -> Result<ObjectId, ObjectError> {
+    match ObjectId::from_hex(hex, format) {
+        None => Err(ObjectError::InvalidId(String::from(hex))),
+        Some(id) => Ok(id),
+    }
+}
+
+fn to_text(content: &[u8]) -> Result<&str, ObjectError> {
+    match std::str::from_utf8(content) {
+        Err(_) => Err(ObjectError::InvalidUtf8),
+        Ok(text) => Ok(text),
+    }
+}
+
+/// Whether a name has to be quoted in a yit tree, whose entries are
+/// separated by spaces and newlines.
+fn needs_quotes(name: &str) -> bool {
+    name.is_empty()
+        || name.starts_with('"')
+        || name
+            .chars()
+            .any(|c| c == ' ' || c == '\\' || c.is_control())
+}
+
+/// Quotes names the way git quotes unusual paths. Names that don't need it
+/// are written as they are, so trees of plain names keep their ids.
+pub fn quote_name(name: &str) -> String {
+    if !needs_quotes(name) {
+        return String::from(name);
+    }
+    let mut quoted = String::from("\"");
+    for c in name.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    quoted.push_str(&format!("\\{:03o}", byte));
+                }
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub fn unquote_name(text: &str) -> Option<(String, &str)> {
+    let rest = match text.strip_prefix('"') {
+        None => {
+            let end = text.find(' ').unwrap_or(text.len());
+            return Some((String::from(&text[..end]), &text[end..]));
+        }
+        Some(rest) => rest,
+    };
+    let mut bytes = Vec::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                return match String::from_utf8(bytes) {
+                    Err(_) => None,
+                    Ok(name) => Some((name, &rest[i + 1..])),
+                }
+            }
+            '\\' => match chars.next()?.1 {
+                'n' => bytes.push(b'\n'),
+                't' => bytes.push(b'\t'),
+                'r' => bytes.push(b'\r'),
+                digit @ '0'..='7' => {
+                    let mut value = digit.to_digit(8)?;
+                    for _ in 0..2 {
+                        value = value * 8 + chars.next()?.1.to_digit(8)?;
+                    }
+                    if value > 0xff {
+                        return None;
+                    }
+                    bytes.push(value as u8);
+                }
+                c => {
+                    let mut buf = [0; 4];
+                    bytes.extend(c.encode_utf8(&mut buf).bytes());
+                }
+            },
+            c => {
+                let mut buf = [0; 4];
+                bytes.extend(c.encode_utf8(&mut buf).bytes());
+            }
+        }
+    }
+    None
+}
+
+fn split_headers(text: &str) -> (Vec<&str>, String) {
+    match text.find("\n\n") {
+        None => (text.lines().collect(), String::from("")),
+        Some(pos) => (
+            text[..pos].lines().collect(),
+            String::from(&text[pos + 2..]),
+        ),
+    }
+}
+
+impl Object {
+    pub fn decode(data: &[u8], format: ObjectFormat) -> Result<Self, ObjectError> {
+        let (kind, encoding, body) = split_header(data)?;
+        Object::parse(kind, body, encoding, format)
+    }
+
+    /// Ids must have the length `format` gives them.
+    pub fn parse(
+        kind: ObjectKind,
+        content: &[u8],
+        encoding: Encoding,
+        format: ObjectFormat,
+    ) -> Result<Self, ObjectError> {
+        match kind {
+            ObjectKind::Blob => Ok(Object::Blob(content.to_vec())),
+            ObjectKind::Tree => match encoding {
+                Encoding::Yit => Object::parse_text_tree(content, format),
+                Encoding::Git => Object::parse_binary_tree(content, format),
+            },
+            ObjectKind::Commit => Object::parse_commit(content, encoding, format),
+            ObjectKind::Tag => Object::parse_tag(content, format),
+        }
+    }
+
+    fn parse_text_tree(content: &[u8], format: ObjectFormat) -> Result<Self, ObjectError> {
+        let mut entries = Vec::new();
+        for (num, line) in to_text(content)?.split('\n').enumerate() {
+            if line.is_empty() {
+                break;
+            }
+            let (mode, rest) = match line.split_once(' ') {
+                Some(("tree", rest)) => (MODE_TREE, rest),
+                Some(("blob", rest)) => (MODE_FILE, rest),
+                _ => return Err(ObjectError::InvalidTreeEntry(num)),
+            };
+            let (name, id) = match unquote_name(rest) {
+                Some((name, rest)) if !name.is_empty() => match rest.strip_prefix(' ') {
+                    Some(id) if !id.contains(' ') => (name, id),
+                    _ => return Err(ObjectError::InvalidTreeEntry(num)),
+                },
+                _ => return Err(ObjectError::InvalidTreeEntry(num)),
+            };
+            entries.push(TreeEntry {
+                mode,
+                name,
+                id: parse_id(id, format)?,
+            });
+        }
+        Ok(Object::Tree(entries))
+    }
+
+    fn parse_binary_tree(content: &[u8], format: ObjectFormat) -> Result<Self, ObjectError> {
+        let id_len = format.raw_len();
+        let mut entries = Vec::new();
+        let mut rest = content;
+        while !rest.is_empty() {
+            let num = entries.len();
+            let mode_end = match rest.iter().position(|byte| *byte == b' ') {
+                None => return Err(ObjectError::InvalidTreeEntry(num)),
+                Some(pos) => pos,
+            };
+            let name_end = match rest.iter().position(|byte| *byte == 0) {
+                None => return Err(ObjectError::InvalidTreeEntry(num)),
+                Some(pos) => pos,
+            };
+            if name_end < mode_end || name_end + 1 + id_len > rest.len() {
+                return Err(ObjectError::InvalidTreeEntry(num));
+            }
+            let mode_text = String::from_utf8_lossy(&rest[..mode_end]).to_string();
+            let mode = match u32::from_str_radix(&mode_text, 8) {
+                Err(_) => return Err(ObjectError::InvalidMode(mode_text)),
+                Ok(mode) => mode,
+            };
+            let name = to_text(&rest[mode_end + 1..name_end])?;
+            if name.is_empty() {
+                return Err(ObjectError::InvalidTreeEntry(num));
+            }
+            let id = crate::encoding::bytes_to_hex(&rest[name_end + 1..name_end + 1 + id_len]);
+            entries.push(TreeEntry {
+                mode,
+                name: String::from(name),
+                id: parse_id(&id, format)?,
+            });
+            rest = &rest[name_end + 1 + id_len..];
+        }
+        Ok(Object::Tree(entries))
+    }
+
+    fn parse_commit(
+        content: &[u8],
+        encoding: Encoding,
+        format: ObjectFormat,
+    ) -> Result<Self, ObjectError> {
+        let (lines, mut message) = split_headers(to_text(content)?);
+        // the first yit root commits were written with an empty parent line
+        if encoding == Encoding::Yit && lines.len() == 1 && message.starts_with('\n') {
+            message.remove(0);
+        }
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut headers = Vec::new();
+        for (num, line) in lines.into_iter().enumerate() {
+            // yit commits list the tree and parents as bare ids
+            if encoding == Encoding::Yit && !line.contains(' ') {
+                if num == 0 {
+                    tree = Some(parse_id(line, format)?);
+                } else {
+                    parents.push(parse_id(line, format)?);
+                }
+                continue;
+            }
+            let (key, value) = match line.find(' ') {
+                None => return Err(ObjectError::MalformedHeader),
+                Some(pos) => (&line[..pos], &line[pos + 1..]),
+            };
+            if key == "tree" && tree.is_none() {
+                tree = Some(parse_id(value, format)?);
+            } else if key == "parent" {
+                parents.push(parse_id(value, format)?);
+            } else {
+                headers.push((String::from(key), String::from(value)));
+            }
+        }
+        match tree {
+            None => Err(ObjectError::MissingField("tree")),
+            Some(tree) => Ok(Object::Commit(Commit {
+                tree,
+                parents,
+                headers,
+                message,
+            })),
+        }
+    }
+
+    fn parse_tag(content: &[u8], format: ObjectFormat) -> Result<Self, ObjectError> {
+        let (lines, message) = split_headers(to_text(content)?);
+        let mut object = None;
+        let mut kind = None;
+        let mut name = None;
+        let mut tagger = None;
+        for line in lines {
+            let (key, value) = match line.find(' ') {
+                None => return Err(ObjectError::MalformedHeader),
+                Some(pos) => (&line[..pos], &line[pos + 1..]),
+            };
+            match key {
+                "object" => object = Some(parse_id(value, format)?),
+                "type" => match ObjectKind::parse(value) {
+                    None => return Err(ObjectError::UnknownType(String::from(value))),
+                    Some(value) => kind = Some(value),
+                },
+                "tag" => name = Some(String::from(value)),
+                "tagger" => tagger = Some(String::from(value)),
+                _ => (),
+            }
+        }
+        match (object, kind, name) {
+            (None, _, _) => Err(ObjectError::MissingField("object")),
+            (_, None, _) => Err(ObjectError::MissingField("type")),
+            (_, _, None) => Err(ObjectError::MissingField("tag")),
+            (Some(object), Some(kind), Some(name)) => Ok(Object::Tag(Tag {
+                object,
+                kind,
+                name,
+                tagger,
+                message,
+            })),
+        }
+    }
+
+    pub fn kind(&self) -> ObjectKind {
+        match self {
+            Object::Blob(_) => ObjectKind::Blob,
+            Object::Tree(_) => ObjectKind::Tree,
+            Object::Commit(_) => ObjectKind::Commit,
+            Object::Tag(_) => ObjectKind::Tag,
+        }
+    }
+
+    pub fn serialize(&self, encoding: Encoding) -> Vec<u8> {
+        match self {
+            Object::Blob(content) => content.clone(),
+            Object::Tree(entries) => {
+                let mut content = Vec::new();
+                for entry in entries {
+                    match encoding {
+                        Encoding::Yit => {
+                            content.extend(entry.kind().as_str().as_bytes());
+                            content.push(b' ');
+                            content.extend(quote_name(&entry.name).as_bytes());
+                            content.push(b' ');
+                            content.extend(entry.id.as_str().as_bytes());
+                            content.push(b'\n');
+                        }
+                        Encoding::Git => {
+                            content.extend(format!("{:o} ", entry.mode).as_bytes());
+                            content.extend(entry.name.as_bytes());
+                            content.push(0);
+                            content.extend(
+                                crate::encoding::hex_to_bytes(entry.id.as_str())
+                                    .unwrap_or_default(),
+                            );
+                        }
+                    }
+                }
+                content
+            }
+            Object::Commit(commit) => {
+                let mut content = String::from("");
+                if encoding == Encoding::Git {
+                    content.push_str("tree ");
+                }
+                content.push_str(commit.tree.as_str());
+                content.push('\n');
+                for parent in &commit.parents {
+                    if encoding == Encoding::Git {
+                        content.push_str("parent ");
+                    }
+                    content.push_str(parent.as_str());
+                    content.push('\n');
+                }
+                for (key, value) in &commit.headers {
+                    content.push_str(key);
+                    content.push(' ');
+                    content.push_str(value);
+                    content.push('\n');
+                }
+                content.push('\n');
+                content.push_str(&commit.message);
+                content.into_bytes()
+            }
+            Object::Tag(tag) => {
+                let mut content = String::from("object ") + tag.object.as_str() + "\n";
+                content.push_str(&(String::from("type ") + tag.kind.as_str() + "\n"));
+                content.push_str(&(String::from("tag ") + &tag.name + "\n"));
+                if let Some(tagger) = &tag.tagger {
+                    content.push_str(&(String::from("tagger ") + tagger + "\n"));
+                }
+                content.push('\n');
+                content.push_str(&tag.message);
+                content.into_bytes()
+            }
+        }
+    }
+
+    pub fn encode(&self, encoding: Encoding) -> Vec<u8> {
+        encoding.encode(self.kind().as_str(), &self.serialize(encoding))
+    }
+}
@@ -0,0 +1,847 @@
+use crate::encoding;
+use crate::object;
+use crate::store;
+use crypto::digest::Digest;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+#[derive(Debug)]
+pub enum PackError {
+    IOError,
+    Corrupt,
+    NotFound,
+}
+
+const OBJ_COMMIT: u8 = 1;
+const OBJ_TREE: u8 = 2;
+const OBJ_BLOB: u8 = 3;
+const OBJ_TAG: u8 = 4;
+const OBJ_OFS_DELTA: u8 = 6;
+const OBJ_REF_DELTA: u8 = 7;
+
+const IDX_MAGIC: [u8; 4] = [0xff, b't', b'O', b'c'];
+
+/// Packs are plain git packs. The encoding of their objects is kept next to
+/// them in `pack-<name>.encoding`; packs without one use the repository's.
+pub const ENCODING_EXT: &str = ".encoding";
+
+/// How many preceding blobs are tried as delta bases.
+const DELTA_WINDOW: usize = 10;
+/// Bigger blobs are streamed into the pack whole, without looking for deltas.
+const DELTA_MAX_SIZE: u64 = 1 << 20;
+const MAX_DELTA_DEPTH: usize = 10;
+const DELTA_BLOCK: usize = 16;
+/// Copy instructions can address at most this many bytes at once.
+const MAX_COPY: usize = 0x10000;
+const MAX_INSERT: usize = 0x7f;
+
+fn type_code(kind: store::ObjectKind) -> u8 {
+    match kind {
+        store::ObjectKind::Commit => OBJ_COMMIT,
+        store::ObjectKind::Tree => OBJ_TREE,
+        store::ObjectKind::Blob => OBJ_BLOB,
+        store::ObjectKind::Tag => OBJ_TAG,
+    }
+}
+
+fn kind_from_code(code: u8) -> Option<store::ObjectKind> {
+    match code {
+        OBJ_COMMIT => Some(store::ObjectKind::Commit),
+        OBJ_TREE => Some(store::ObjectKind::Tree),
+        OBJ_BLOB => Some(store::ObjectKind::Blob),
+        OBJ_TAG => Some(store::ObjectKind::Tag),
+        _ => None,
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(0xffff_ffff, data)
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+fn write_copy(out: &mut Vec<u8>, offset: usize, size: usize) {
+    let mut command = 0x80u8;
+    let mut args = Vec::new();
+    for i in 0..4 {
+        let byte = ((offset >> (8 * i)) & 0xff) as u8;
+        if byte != 0 {
+            command |= 1 << i;
+            args.push(byte);
+        }
+    }
+    // a size of 0x10000 is encoded as zero
+    let size = if size == MAX_COPY { 0 } else { size };
+    for i in 0..3 {
+        let byte = ((size >> (8 * i)) & 0xff) as u8;
+        if byte != 0 {
+            command |= 0x10 << i;
+            args.push(byte);
+        }
+    }
+    out.push(command);
+    out.extend(args);
+}
+
+fn write_insert(out: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT) {
+        out.push(chunk.len() as u8);
+        out.extend(chunk);
+    }
+}
+
+/// Encodes `target` as copy/insert instructions against `base`, in git's delta format.
+pub fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, base.len());
+    write_varint(&mut delta, target.len());
+
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    let mut offset = 0;
+    while offset + DELTA_BLOCK <= base.len() {
+        blocks
+            .entry(&base[offset..offset + DELTA_BLOCK])
+            .or_insert(offset);
+        offset += DELTA_BLOCK;
+    }
+
+    let mut insert: Vec<u8> = Vec::new();
+    let mut pos = 0;
+    while pos < target.len() {
+        let found = if pos + DELTA_BLOCK <= target.len() {
+            blocks.get(&target[pos..pos + DELTA_BLOCK]).cloned()
+        } else {
+            None
+        };
+        match found {
+            None => {
+                insert.push(target[pos]);
+                pos += 1;
+            }
+            Some(mut start) => {
+                // grow the match backwards into pending literal bytes
+                let mut target_start = pos;
+                while start > 0 && !insert.is_empty() && base[start - 1] == insert[insert.len() - 1]
+                {
+                    start -= 1;
+                    target_start -= 1;
+                    insert.pop();
+                }
+                let mut len = pos + DELTA_BLOCK - target_start;
+                while start + len < base.len()
+                    && target_start + len < target.len()
+                    && base[start + len] == target[target_start + len]
+                {
+                    len += 1;
+                }
+                write_insert(&mut delta, &insert);
+                insert.clear();
+                let mut copied = 0;
+                while copied < len {
+                    let size = std::cmp::min(MAX_COPY, len - copied);
+                    write_copy(&mut delta, start + copied, size);
+                    copied += size;
+                }
+                pos = target_start + len;
+            }
+        }
+    }
+    write_insert(&mut delta, &insert);
+    delta
+}
+
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, PackError> {
+    let mut pos = 0;
+    let base_size = read_varint(delta, &mut pos).ok_or(PackError::Corrupt)?;
+    let result_size = read_varint(delta, &mut pos).ok_or(PackError::Corrupt)?;
+    if base_size != base.len() {
+        return Err(PackError::Corrupt);
+    }
+    let mut result = Vec::with_capacity(result_size);
+    while pos < delta.len() {
+        let command = delta[pos];
+        pos += 1;
+        if command & 0x80 != 0 {
+            let mut offset = 0;
+            let mut size = 0;
+            for i in 0..4 {
+                if command & (1 << i) != 0 {
+                    offset |= (*delta.get(pos).ok_or(PackError::Corrupt)? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if command & (0x10 << i) != 0 {
+                    size |= (*delta.get(pos).ok_or(PackError::Corrupt)? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            if size == 0 {
+                size = MAX_COPY;
+            }
+            match base.get(offset..offset + size) {
+                None => return Err(PackError::Corrupt),
+                Some(data) => result.extend(data),
+            }
+        } else if command != 0 {
+            let size = command as usize;
+            match delta.get(pos..pos + size) {
+                None => return Err(PackError::Corrupt),
+                Some(data) => result.extend(data),
+            }
+            pos += size;
+        } else {
+            return Err(PackError::Corrupt);
+        }
+    }
+    if result.len() != result_size {
+        return Err(PackError::Corrupt);
+    }
+    Ok(result)
+}
+
+fn write_entry_header(out: &mut Vec<u8>, code: u8, size: usize) {
+    let mut byte = (code << 4) | (size & 0x0f) as u8;
+    let mut size = size >> 4;
+    while size != 0 {
+        out.push(byte | 0x80);
+        byte = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+    out.push(byte);
+}
+
+fn write_delta_offset(out: &mut Vec<u8>, mut offset: usize) {
+    let mut bytes = vec![(offset & 0x7f) as u8];
+    offset >>= 7;
+    while offset != 0 {
+        offset -= 1;
+        bytes.push(0x80 | (offset & 0x7f) as u8);
+        offset >>= 7;
+    }
+    bytes.reverse();
+    out.extend(bytes);
+}
+
+struct PackObject {
+    id: store::ObjectId,
+    kind: store::ObjectKind,
+    name: String,
+    size: u64,
+    /// commits, trees and tags; blobs are read again when they are written
+    content: Option<Vec<u8>>,
+}
+
+/// A blob recently written in full or as a delta, kept as a delta base.
+struct DeltaBase {
+    offset: u64,
+    content: Vec<u8>,
+    depth: usize,
+}
+
+fn kind_order(kind: store::ObjectKind) -> u8 {
+    match kind {
+        store::ObjectKind::Commit => 0,
+        store::ObjectKind::Tag => 1,
+        store::ObjectKind::Tree => 2,
+        store::ObjectKind::Blob => 3,
+    }
+}
+
+fn best_delta<'b>(
+    window: &'b VecDeque<DeltaBase>,
+    target: &[u8],
+) -> Option<(&'b DeltaBase, Vec<u8>)> {
+    let mut best: Option<(&DeltaBase, Vec<u8>)> = None;
+    for base in window {
+        if base.depth >= MAX_DELTA_DEPTH
+            || base.content.len() < DELTA_BLOCK
+            || base.content.len() / 4 > target.len()
+            || target.len() / 4 > base.content.len()
+        {
+            continue;
+        }
+        let delta = create_delta(&base.content, target);
+        let best_len = match &best {
+            None => target.len() / 2,
+            Some((_, best_delta)) => best_delta.len(),
+        };
+        if delta.len() < best_len {
+            best = Some((base, delta));
+        }
+    }
+    best
+}
+
+/// Writes a pack file while hashing it and computing the CRC of the entry
+/// being written.
+struct PackWriter {
+    file: File,
+    hasher: Box<dyn Digest>,
+    len: u64,
+    crc: u32,
+}
+
+impl Write for PackWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.file.write_all(data)?;
+        self.hasher.input(data);
+        self.crc = crc32_update(self.crc, data);
+        self.len += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Writes one pack per encoding the objects were written in. The path of
+/// each object groups similar blobs.
+pub fn write_pack(
+    store: &dyn store::ObjectStore,
+    objects: &[(store::ObjectId, String)],
+    dir: &str,
+) -> Result<Vec<String>, PackError> {
+    let mut groups: Vec<(encoding::Encoding, Vec<PackObject>)> = Vec::new();
+    for (id, name) in objects {
+        let (kind, object_encoding, mut reader) = match store.stream_encoded(id) {
+            Err(_) => return Err(PackError::NotFound),
+            Ok(object) => object,
+        };
+        let (size, content) = if kind == store::ObjectKind::Blob {
+            match std::io::copy(&mut reader, &mut std::io::sink()) {
+                Err(_) => return Err(PackError::IOError),
+                Ok(size) => (size, None),
+            }
+        } else {
+            let mut content = Vec::new();
+            if reader.read_to_end(&mut content).is_err() {
+                return Err(PackError::IOError);
+            }
+            (content.len() as u64, Some(content))
+        };
+        let object = PackObject {
+            id: id.clone(),
+            kind,
+            name: String::from(name.rsplit('/').next().unwrap_or("")),
+            size,
+            content,
+        };
+        match groups
+            .iter_mut()
+            .find(|(group_encoding, _)| *group_encoding == object_encoding)
+        {
+            Some((_, group)) => group.push(object),
+            None => groups.push((object_encoding, vec![object])),
+        }
+    }
+    let mut names = Vec::new();
+    for (object_encoding, group) in groups {
+        names.push(write_single_pack(store, group, object_encoding, dir)?);
+    }
+    Ok(names)
+}
+
+/// Writes the entries of `pack_objects` to `writer` and returns the offset
+/// and CRC of each one. Only blobs up to `DELTA_MAX_SIZE` are held in
+/// memory, and no more than `DELTA_WINDOW` of them at a time.
+fn write_entries(
+    store: &dyn store::ObjectStore,
+    pack_objects: &[PackObject],
+    writer: &mut PackWriter,
+) -> Result<Vec<(u64, u32)>, PackError> {
+    let mut entries = Vec::new();
+    let mut window: VecDeque<DeltaBase> = VecDeque::new();
+    for object in pack_objects {
+        let offset = writer.len;
+        writer.crc = 0xffff_ffff;
+        let mut header = Vec::new();
+        if object.kind == store::ObjectKind::Blob && object.size > DELTA_MAX_SIZE {
+            let mut reader = match store.stream(&object.id) {
+                Err(_) => return Err(PackError::NotFound),
+                Ok((_, reader)) => reader,
+            };
+            write_entry_header(&mut header, type_code(object.kind), object.size as usize);
+            if writer.write_all(&header).is_err() {
+                return Err(PackError::IOError);
+            }
+            let mut deflater = encoding::Deflater::new(&mut *writer, store.compression());
+            match std::io::copy(&mut reader, &mut deflater) {
+                Err(_) => return Err(PackError::IOError),
+                Ok(size) if size != object.size => return Err(PackError::Corrupt),
+                Ok(_) => (),
+            }
+            if deflater.finish().is_err() {
+                return Err(PackError::IOError);
+            }
+        } else {
+            let content = match &object.content {
+                Some(content) => content.clone(),
+                None => match store.get(&object.id) {
+                    Err(_) => return Err(PackError::NotFound),
+                    Ok((_, content)) => content,
+                },
+            };
+            let mut depth = 0;
+            match best_delta(&window, &content) {
+                None => {
+                    write_entry_header(&mut header, type_code(object.kind), content.len());
+                    header.extend(encoding::compress(&content, store.compression()));
+                }
+                Some((base, delta)) => {
+                    depth = base.depth + 1;
+                    write_entry_header(&mut header, OBJ_OFS_DELTA, delta.len());
+                    write_delta_offset(&mut header, (offset - base.offset) as usize);
+                    header.extend(encoding::compress(&delta, store.compression()));
+                }
+            }
+            if writer.write_all(&header).is_err() {
+                return Err(PackError::IOError);
+            }
+            if object.kind == store::ObjectKind::Blob {
+                window.push_back(DeltaBase {
+                    offset,
+                    content,
+                    depth,
+                });
+                if window.len() > DELTA_WINDOW {
+                    window.pop_front();
+                }
+            }
+        }
+        entries.push((offset, !writer.crc));
+    }
+    Ok(entries)
+}
+
+fn write_single_pack(
+    store: &dyn store::ObjectStore,
+    mut pack_objects: Vec<PackObject>,
+    object_encoding: encoding::Encoding,
+    dir: &str,
+) -> Result<String, PackError> {
+    // similar blobs usually share a name, bigger ones make better bases
+    pack_objects.sort_by(|a, b| {
+        kind_order(a.kind)
+            .cmp(&kind_order(b.kind))
+            .then(a.name.cmp(&b.name))
+            .then(b.size.cmp(&a.size))
+    });
+    if fs::create_dir_all(dir).is_err() {
+        return Err(PackError::IOError);
+    }
+    let temp = format!("{}/tmp_pack_{}", dir, std::process::id());
+    let res = write_pack_files(store, &pack_objects, object_encoding, dir, &temp);
+    if res.is_err() {
+        let _ = fs::remove_file(temp.clone() + ".pack.lock");
+        let _ = fs::remove_file(temp.clone() + ENCODING_EXT + ".lock");
+        let _ = fs::remove_file(temp + ".idx.lock");
+    }
+    res
+}
+
+fn write_pack_files(
+    store: &dyn store::ObjectStore,
+    pack_objects: &[PackObject],
+    object_encoding: encoding::Encoding,
+    dir: &str,
+    temp: &str,
+) -> Result<String, PackError> {
+    let pack_lock = String::from(temp) + ".pack.lock";
+    let encoding_lock = String::from(temp) + ENCODING_EXT + ".lock";
+    let idx_lock = String::from(temp) + ".idx.lock";
+    // trailers use the repository's hash function, as in git
+    let format = store.format();
+    let mut writer = match File::create(&pack_lock) {
+        Err(_) => return Err(PackError::IOError),
+        Ok(file) => PackWriter {
+            file,
+            hasher: format.hasher(),
+            len: 0,
+            crc: 0,
+        },
+    };
+    let mut header = Vec::new();
+    header.extend(b"PACK");
+    header.extend(&2u32.to_be_bytes());
+    header.extend(&(pack_objects.len() as u32).to_be_bytes());
+    if writer.write_all(&header).is_err() {
+        return Err(PackError::IOError);
+    }
+    let entries = write_entries(store, pack_objects, &mut writer)?;
+    let mut checksum = vec![0; format.raw_len()];
+    writer.hasher.result(&mut checksum);
+    if writer.file.write_all(&checksum).is_err() || writer.file.sync_all().is_err() {
+        return Err(PackError::IOError);
+    }
+
+    let mut order: Vec<usize> = (0..pack_objects.len()).collect();
+    order.sort_by(|a, b| pack_objects[*a].id.cmp(&pack_objects[*b].id));
+    let mut idx = Vec::new();
+    idx.extend(&IDX_MAGIC);
+    idx.extend(&2u32.to_be_bytes());
+    let mut fanout = [0u32; 256];
+    for i in &order {
+        let first = encoding::hex_to_bytes(pack_objects[*i].id.dir()).unwrap_or_default();
+        for count in fanout.iter_mut().skip(first[0] as usize) {
+            *count += 1;
+        }
+    }
+    for count in fanout.iter() {
+        idx.extend(&count.to_be_bytes());
+    }
+    for i in &order {
+        idx.extend(encoding::hex_to_bytes(pack_objects[*i].id.as_str()).unwrap_or_default());
+    }
+    for i in &order {
+        idx.extend(&entries[*i].1.to_be_bytes());
+    }
+    let mut large_offsets = Vec::new();
+    for i in &order {
+        let offset = entries[*i].0;
+        if offset < 0x8000_0000 {
+            idx.extend(&(offset as u32).to_be_bytes());
+        } else {
+            idx.extend(&(0x8000_0000 | large_offsets.len() as u32).to_be_bytes());
+            large_offsets.push(offset);
+        }
+    }
+    for offset in large_offsets {
+        idx.extend(&offset.to_be_bytes());
+    }
+    idx.extend(&checksum);
+    let mut hasher = format.hasher();
+    hasher.input(&idx);
+    let mut idx_checksum = vec![0; format.raw_len()];
+    hasher.result(&mut idx_checksum);
+    idx.extend(&idx_checksum);
+
+    let name = encoding::bytes_to_hex(&checksum);
+    let path = String::from(dir) + "/pack-" + &name;
+    let encoding_name = match object_encoding {
+        encoding::Encoding::Yit => "yit",
+        encoding::Encoding::Git => "git",
+    };
+    // written aside and renamed, the index last, so readers never find an
+    // index whose pack is missing or half written
+    if fs::write(&encoding_lock, encoding_name).is_err()
+        || fs::write(&idx_lock, idx).is_err()
+        || fs::rename(&pack_lock, path.clone() + ".pack").is_err()
+        || fs::rename(&encoding_lock, path.clone() + ENCODING_EXT).is_err()
+        || fs::rename(&idx_lock, path + ".idx").is_err()
+    {
+        return Err(PackError::IOError);
+    }
+    Ok(name)
+}
+
+pub struct Pack {
+    pack_path: String,
+    ids: Vec<store::ObjectId>,
+    offsets: Vec<u64>,
+    /// every entry offset in file order, used to find where an entry ends
+    sorted_offsets: Vec<u64>,
+    data_end: u64,
+    format: encoding::ObjectFormat,
+    /// `None` for packs without an encoding file
+    encoding: Option<encoding::Encoding>,
+}
+
+fn be_u32(data: &[u8], pos: usize) -> Result<u32, PackError> {
+    match data.get(pos..pos + 4) {
+        None => Err(PackError::Corrupt),
+        Some(bytes) => Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+    }
+}
+
+impl Pack {
+    pub fn open(idx_path: &str, format: encoding::ObjectFormat) -> Result<Self, PackError> {
+        let id_len = format.raw_len();
+        let idx = match fs::read(idx_path) {
+            Err(_) => return Err(PackError::IOError),
+            Ok(idx) => idx,
+        };
+        if idx.len() < 8 + 256 * 4 || idx[0..4] != IDX_MAGIC || be_u32(&idx, 4)? != 2 {
+            return Err(PackError::Corrupt);
+        }
+        let count = be_u32(&idx, 8 + 255 * 4)? as usize;
+        let ids_start = 8 + 256 * 4;
+        let offsets_start = ids_start + count * id_len + count * 4;
+        let large_start = offsets_start + count * 4;
+        let mut ids = Vec::with_capacity(count);
+        let mut offsets = Vec::with_capacity(count);
+        for i in 0..count {
+            let raw = match idx.get(ids_start + i * id_len..ids_start + (i + 1) * id_len) {
+                None => return Err(PackError::Corrupt),
+                Some(raw) => raw,
+            };
+            match store::ObjectId::from_hex(&encoding::bytes_to_hex(raw), format) {
+                None => return Err(PackError::Corrupt),
+                Some(id) => ids.push(id),
+            }
+            let offset = be_u32(&idx, offsets_start + i * 4)?;
+            if offset & 0x8000_0000 == 0 {
+                offsets.push(offset as u64);
+            } else {
+                let pos = large_start + (offset & 0x7fff_ffff) as usize * 8;
+                let high = be_u32(&idx, pos)? as u64;
+                let low = be_u32(&idx, pos + 4)? as u64;
+                offsets.push((high << 32) | low);
+            }
+        }
+        let base_path = idx_path.trim_end_matches(".idx");
+        let pack_path = String::from(base_path) + ".pack";
+        let data_end = match fs::metadata(&pack_path) {
+            Err(_) => return Err(PackError::IOError),
+            Ok(metadata) => metadata.len().saturating_sub(id_len as u64),
+        };
+        let mut header = [0; 12];
+        match File::open(&pack_path) {
+            Err(_) => return Err(PackError::IOError),
+            Ok(mut file) => {
+                if file.read_exact(&mut header).is_err() {
+                    return Err(PackError::Corrupt);
+                }
+            }
+        }
+        if header[0..4] != *b"PACK" || be_u32(&header, 4)? != 2 {
+            return Err(PackError::Corrupt);
+        }
+        let encoding = match fs::read_to_string(String::from(base_path) + ENCODING_EXT) {
+            Err(_) => None,
+            Ok(name) => match name.trim() {
+                "yit" => Some(encoding::Encoding::Yit),
+                "git" => Some(encoding::Encoding::Git),
+                _ => return Err(PackError::Corrupt),
+            },
+        };
+        let mut sorted_offsets = offsets.clone();
+        sorted_offsets.sort_unstable();
+        Ok(Pack {
+            pack_path,
+            ids,
+            offsets,
+            sorted_offsets,
+            data_end,
+            format,
+            encoding,
+        })
+    }
+
+    pub fn encoding(&self) -> Option<encoding::Encoding> {
+        self.encoding
+    }
+
+    pub fn pack_path(&self) -> &str {
+        &self.pack_path
+    }
+
+    pub fn ids(&self) -> &[store::ObjectId] {
+        &self.ids
+    }
+
+    pub fn contains(&self, id: &store::ObjectId) -> bool {
+        self.ids.binary_search(id).is_ok()
+    }
+
+    pub fn get(&self, id: &store::ObjectId) -> Result<(store::ObjectKind, Vec<u8>), PackError> {
+        match self.ids.binary_search(id) {
+            Err(_) => Err(PackError::NotFound),
+            Ok(pos) => match File::open(&self.pack_path) {
+                Err(_) => Err(PackError::IOError),
+                Ok(mut file) => self.read_entry(&mut file, self.offsets[pos], 0),
+            },
+        }
+    }
+
+    fn read_entry(
+        &self,
+        file: &mut File,
+        offset: u64,
+        depth: usize,
+    ) -> Result<(store::ObjectKind, Vec<u8>), PackError> {
+        if depth > 2 * MAX_DELTA_DEPTH + 40 {
+            return Err(PackError::Corrupt);
+        }
+        let end = match self.sorted_offsets.binary_search(&offset) {
+            Err(_) => return Err(PackError::Corrupt),
+            Ok(pos) => match self.sorted_offsets.get(pos + 1) {
+                None => self.data_end,
+                Some(next) => *next,
+            },
+        };
+        let mut entry = vec![0; end.saturating_sub(offset) as usize];
+        if file.seek(SeekFrom::Start(offset)).is_err() || file.read_exact(&mut entry).is_err() {
+            return Err(PackError::IOError);
+        }
+
+        let mut byte = *entry.first().ok_or(PackError::Corrupt)?;
+        let code = (byte >> 4) & 0x07;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        let mut pos = 1;
+        while byte & 0x80 != 0 {
+            byte = *entry.get(pos).ok_or(PackError::Corrupt)?;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            pos += 1;
+        }
+
+        let base = match code {
+            OBJ_OFS_DELTA => {
+                let mut byte = *entry.get(pos).ok_or(PackError::Corrupt)?;
+                let mut distance = (byte & 0x7f) as u64;
+                pos += 1;
+                while byte & 0x80 != 0 {
+                    byte = *entry.get(pos).ok_or(PackError::Corrupt)?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                    pos += 1;
+                }
+                if distance > offset {
+                    return Err(PackError::Corrupt);
+                }
+                Some(self.read_entry(file, offset - distance, depth + 1)?)
+            }
+            OBJ_REF_DELTA => {
+                let id_len = self.format.raw_len();
+                let raw = entry.get(pos..pos + id_len).ok_or(PackError::Corrupt)?;
+                pos += id_len;
+                let base_id = store::ObjectId::from_hex(&encoding::bytes_to_hex(raw), self.format)
+                    .ok_or(PackError::Corrupt)?;
+                match self.ids.binary_search(&base_id) {
+                    Err(_) => return Err(PackError::NotFound),
+                    Ok(base_pos) => {
+                        Some(self.read_entry(file, self.offsets[base_pos], depth + 1)?)
+                    }
+                }
+            }
+            _ => None,
+        };
+        let data = encoding::decompress(&entry[pos..]).ok_or(PackError::Corrupt)?;
+        if data.len() != size {
+            return Err(PackError::Corrupt);
+        }
+        match base {
+            Some((kind, base)) => Ok((kind, apply_delta(&base, &data)?)),
+            None => match kind_from_code(code) {
+                None => Err(PackError::Corrupt),
+                Some(kind) => Ok((kind, data)),
+            },
+        }
+    }
+}
+
+pub fn pack_index_paths(objects_dir: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    if let Ok(entries) = fs::read_dir(String::from(objects_dir) + "/pack") {
+        for entry in entries.flatten() {
+            let path = entry.path().to_string_lossy().to_string();
+            if path.ends_with(".idx") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    paths
+}
+
+/// Every object reachable from `roots` through commits, trees and tags, each
+/// paired with the name it was found under (empty for commits and tags).
+pub fn reachable_objects(
+    store: &dyn store::ObjectStore,
+    roots: &[store::ObjectId],
+) -> Result<Vec<(store::ObjectId, String)>, PackError> {
+    let mut seen: HashSet<store::ObjectId> = HashSet::new();
+    let mut reachable = Vec::new();
+    let mut stack: Vec<(store::ObjectId, String)> = roots
+        .iter()
+        .rev()
+        .map(|root| (root.clone(), String::from("")))
+        .collect();
+    while let Some((id, name)) = stack.pop() {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        // blobs are only packed, never parsed
+        match store.stream(&id) {
+            Err(_) => return Err(PackError::NotFound),
+            Ok((store::ObjectKind::Blob, _)) => {
+                reachable.push((id, name));
+                continue;
+            }
+            Ok(_) => (),
+        }
+        let object = match store.read(&id) {
+            Err(_) => return Err(PackError::NotFound),
+            Ok(object) => object,
+        };
+        match object {
+            object::Object::Blob(_) => (),
+            object::Object::Tree(entries) => {
+                for entry in entries.into_iter().rev() {
+                    match entry.mode {
+                        // the commit of a submodule lives in its own repository
+                        object::MODE_GITLINK => (),
+                        object::MODE_TREE => stack.push((entry.id, entry.name)),
+                        _ => {
+                            if seen.insert(entry.id.clone()) {
+                                reachable.push((entry.id, entry.name));
+                            }
+                        }
+                    }
+                }
+            }
+            object::Object::Commit(commit) => {
+                for parent in commit.parents.into_iter().rev() {
+                    stack.push((parent, String::from("")));
+                }
+                stack.push((commit.tree, String::from("")));
+            }
+            object::Object::Tag(tag) => stack.push((tag.object, String::from(""))),
+        }
+        reachable.push((id, name));
+    }
+    Ok(reachable)
+}
@@ -0,0 +1,155 @@
+use crate::diff;
+use crate::message;
+use std::io::{BufRead, Write};
+
+#[derive(Debug)]
+pub enum PatchError {
+    IOError,
+}
+
+const EDIT_FILE: &str = ".yit/ADD_EDIT.patch";
+
+const HELP: &str = "y - stage this hunk
+n - do not stage this hunk
+q - quit; do not stage this hunk or any of the remaining ones
+a - stage this hunk and all later hunks in the file
+d - do not stage this hunk or any of the later hunks in the file
+s - split the current hunk into smaller hunks
+e - manually edit the current hunk
+? - print help
+";
+
+/// Reads an edited hunk back. Comment lines are dropped, an empty line
+/// counts as an unchanged empty line and a `\` line removes the newline of
+/// the line before it. `None` unless the unchanged and removed lines still
+/// are the lines of `old` that `hunk` replaced.
+pub fn parse_edited_hunk(hunk: &diff::Hunk, text: &str, old: &[&str]) -> Option<diff::Hunk> {
+    let mut lines: Vec<(char, String)> = Vec::new();
+    for line in text.split_inclusive('\n') {
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('\\') {
+            if let Some((_, last)) = lines.last_mut() {
+                if last.ends_with('\n') {
+                    last.pop();
+                }
+            }
+            continue;
+        }
+        let mut chars = line.chars();
+        match chars.next() {
+            Some('\n') => lines.push((' ', String::from("\n"))),
+            Some(kind) if kind == ' ' || kind == '-' || kind == '+' => {
+                let mut line = String::from(chars.as_str());
+                if !line.ends_with('\n') {
+                    line.push('\n');
+                }
+                lines.push((kind, line));
+            }
+            _ => return None,
+        }
+    }
+    let edited = diff::Hunk {
+        old_start: hunk.old_start,
+        new_start: hunk.new_start,
+        lines,
+    };
+    let replaced = old.get(hunk.old_start..hunk.old_start + edited.old_len())?;
+    let kept = edited.lines.iter().filter(|(kind, _)| *kind != '+');
+    if edited.old_len() != hunk.old_len() || !kept.map(|(_, line)| line).eq(replaced.iter()) {
+        return None;
+    }
+    Some(edited)
+}
+
+fn edit_hunk(hunk: &diff::Hunk, old: &[&str]) -> Result<Option<diff::Hunk>, PatchError> {
+    let mut template = String::from("# Manual hunk edit mode\n");
+    template.push_str(&hunk.to_string()[hunk.header().len() + 1..]);
+    template.push_str(
+        "# ---\n\
+         # To remove '-' lines, make them ' ' lines (context).\n\
+         # To remove '+' lines, delete them.\n\
+         # Lines starting with # will be removed.\n",
+    );
+    match message::edit(EDIT_FILE, &template) {
+        Err(_) => Err(PatchError::IOError),
+        Ok(text) => Ok(parse_edited_hunk(hunk, &text, old)),
+    }
+}
+
+/// Returns the hunks to stage, in order, and whether the user asked to quit.
+pub fn select_hunks(
+    old: &[&str],
+    hunks: Vec<diff::Hunk>,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(Vec<diff::Hunk>, bool), PatchError> {
+    let mut hunks = hunks;
+    let mut selected = Vec::new();
+    let mut i = 0;
+    while i < hunks.len() {
+        let options = if hunks[i].split().is_some() {
+            "y,n,q,a,d,s,e,?"
+        } else {
+            "y,n,q,a,d,e,?"
+        };
+        let prompt = format!(
+            "{}({}/{}) Stage this hunk [{}]? ",
+            hunks[i],
+            i + 1,
+            hunks.len(),
+            options
+        );
+        if output.write_all(prompt.as_bytes()).is_err() || output.flush().is_err() {
+            return Err(PatchError::IOError);
+        }
+        let mut answer = String::new();
+        match input.read_line(&mut answer) {
+            Err(_) => return Err(PatchError::IOError),
+            // no more answers, as if asked to quit
+            Ok(0) => return Ok((selected, true)),
+            Ok(_) => {}
+        }
+        let message = match answer.trim() {
+            "y" => {
+                selected.push(hunks[i].clone());
+                i += 1;
+                None
+            }
+            "n" => {
+                i += 1;
+                None
+            }
+            "a" => {
+                selected.extend(hunks.drain(i..));
+                None
+            }
+            "d" => break,
+            "q" => return Ok((selected, true)),
+            "s" => match hunks[i].split() {
+                None => Some(String::from("Sorry, cannot split this hunk\n")),
+                Some(parts) => {
+                    let count = parts.len();
+                    hunks.splice(i..=i, parts);
+                    Some(format!("Split into {} hunks.\n", count))
+                }
+            },
+            "e" => match edit_hunk(&hunks[i], old)? {
+                None => Some(String::from("Your edited hunk does not apply.\n")),
+                Some(edited) => {
+                    selected.push(edited);
+                    i += 1;
+                    None
+                }
+            },
+            _ => Some(String::from(HELP)),
+        };
+        if let Some(message) = message {
+            if output.write_all(message.as_bytes()).is_err() {
+                return Err(PatchError::IOError);
+            }
+        }
+    }
+    Ok((selected, false))
+}
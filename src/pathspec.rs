@@ -0,0 +1,49 @@
+use crate::ignore;
+
+/// Relative to the repository root. In a glob `*` also matches `/`, so that
+/// `*.rs` selects `src/lib.rs`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pathspec {
+    /// without a leading `./` or trailing `/`; empty for the whole tree
+    pub spec: String,
+}
+
+impl Pathspec {
+    pub fn new(spec: &str) -> Self {
+        let mut spec = spec;
+        while let Some(rest) = spec.strip_prefix("./") {
+            spec = rest;
+        }
+        let spec = spec.trim_end_matches('/');
+        Pathspec {
+            spec: String::from(if spec == "." { "" } else { spec }),
+        }
+    }
+
+    pub fn is_glob(&self) -> bool {
+        self.spec.contains(['*', '?', '['])
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.spec.is_empty()
+            || path == self.spec
+            || path
+                .strip_prefix(self.spec.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+            || (self.is_glob() && ignore::wildmatch(&self.spec, path, false))
+    }
+
+    /// The directory holding everything the spec can select. Empty for the
+    /// root, otherwise ending with `/`.
+    pub fn root(&self) -> String {
+        let literal = match self.spec.find(['*', '?', '[']) {
+            None if self.spec.is_empty() => String::new(),
+            None => self.spec.clone() + "/",
+            Some(i) => String::from(&self.spec[..i]),
+        };
+        match literal.rfind('/') {
+            None => String::new(),
+            Some(i) => String::from(&literal[..=i]),
+        }
+    }
+}
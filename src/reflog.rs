@@ -0,0 +1,107 @@
+use crate::identity;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ReflogError {
+    IOError,
+}
+
+/// One update of a ref: `<old> <new> <committer>\t<message>`, as in git.
+#[derive(Clone, Debug)]
+pub struct ReflogEntry {
+    /// all zeros when the ref was created
+    pub old: String,
+    pub new: String,
+    pub committer: identity::Signature,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let (update, message) = line.split_once('\t').unwrap_or((line, ""));
+        let mut words = update.splitn(3, ' ');
+        let old = String::from(words.next()?);
+        let new = String::from(words.next()?);
+        let committer = identity::Signature::parse(words.next()?)?;
+        Some(ReflogEntry {
+            old,
+            new,
+            committer,
+            message: String::from(message),
+        })
+    }
+}
+
+fn log_path(refname: &str) -> String {
+    String::from(".yit/logs/") + refname
+}
+
+/// An empty `old` is written as the all-zero id.
+pub fn append(
+    refname: &str,
+    old: &str,
+    new: &str,
+    committer: &identity::Signature,
+    message: &str,
+) -> Result<(), ReflogError> {
+    let path = log_path(refname);
+    if let Some(dir) = Path::new(&path).parent() {
+        if fs::create_dir_all(dir).is_err() {
+            return Err(ReflogError::IOError);
+        }
+    }
+    let old = if old.is_empty() {
+        "0".repeat(new.len())
+    } else {
+        String::from(old)
+    };
+    // only the first line of the message, as the log is line based
+    let message = message.lines().next().unwrap_or("");
+    let line = format!("{} {} {}\t{}\n", old, new, committer, message);
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Err(_) => Err(ReflogError::IOError),
+        Ok(mut file) => match file.write_all(line.as_bytes()) {
+            Err(_) => Err(ReflogError::IOError),
+            Ok(_) => Ok(()),
+        },
+    }
+}
+
+pub fn read(refname: &str) -> Result<Vec<ReflogEntry>, ReflogError> {
+    let path = log_path(refname);
+    if !Path::new(&path).exists() {
+        return Ok(Vec::new());
+    }
+    match fs::read_to_string(path) {
+        Err(_) => Err(ReflogError::IOError),
+        Ok(content) => Ok(content.lines().filter_map(ReflogEntry::parse).collect()),
+    }
+}
+
+pub fn exists(refname: &str) -> bool {
+    Path::new(&log_path(refname)).exists()
+}
+
+pub fn all_refnames() -> Vec<String> {
+    let mut names = Vec::new();
+    let mut dirs = vec![String::from("")];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(log_path(&dir)) {
+            Err(_) => continue,
+            Ok(entries) => entries,
+        };
+        for entry in entries.flatten() {
+            let name = dir.clone() + &entry.file_name().to_string_lossy();
+            if entry.path().is_dir() {
+                dirs.push(name + "/");
+            } else {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    names
+}
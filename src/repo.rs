@@ -1,18 +1,47 @@
 use crate::branch;
 use crate::commit;
+use crate::commitgraph;
+use crate::config;
 use crate::diff;
+use crate::encoding;
+use crate::fsck;
+use crate::gc;
+use crate::identity;
+use crate::ignore;
 use crate::index;
+use crate::log;
 use crate::merge;
+use crate::message;
+use crate::pack;
+use crate::patch;
+use crate::pathspec;
+use crate::reflog;
+use crate::revision;
+use crate::status;
+use crate::store;
 use crate::tree;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::path::Path;
+use std::sync::Arc;
 
-#[derive(std::clone::Clone)]
-pub struct Repository {}
+const COMMIT_GRAPH: &str = ".yit/commit-graph";
+const COMMIT_EDITMSG: &str = ".yit/COMMIT_EDITMSG";
+
+#[derive(Clone, Copy, Default)]
+pub struct AddOptions {
+    pub all: bool,
+    pub update: bool,
+    pub force: bool,
+}
+
+#[derive(std::clone::Clone, Default)]
+pub struct Repository {
+    store: Option<Arc<dyn store::ObjectStore>>,
+}
 
 pub enum RepoError {
     RepoAlreadyExists,
@@ -22,6 +51,14 @@ pub enum RepoError {
     CommitError,
     MergeError,
     CheckoutError,
+    PackError,
+    GcError,
+    LogError,
+    EditorError(message::MessageError),
+    UntrackedPath(String),
+    IgnoredPath(String),
+    DestinationExists(String),
+    RevisionError(revision::RevisionError),
 }
 
 fn rollback(path: String) -> io::Result<()> {
@@ -30,10 +67,27 @@ fn rollback(path: String) -> io::Result<()> {
 
 impl Repository {
     pub fn new() -> Self {
-        Repository {}
+        Repository { store: None }
+    }
+
+    /// Refs, the index and config stay under `.yit`; `gc` and `repack` fail
+    /// unless `store` can pack.
+    pub fn with_store(store: Arc<dyn store::ObjectStore>) -> Self {
+        Repository { store: Some(store) }
+    }
+
+    fn store(&self) -> Arc<dyn store::ObjectStore> {
+        match &self.store {
+            Some(store) => store.clone(),
+            None => Arc::new(store::LooseStore::open()),
+        }
     }
 
     pub fn init(self) -> Result<(), RepoError> {
+        self.init_with(config::Config::new())
+    }
+
+    pub fn init_with(self, config: config::Config) -> Result<(), RepoError> {
         let path = String::from(".yit");
         if Path::new(&path).exists() {
             return Err(RepoError::RepoAlreadyExists);
@@ -83,7 +137,7 @@ impl Repository {
             Err(_) => Err(RepoError::IOError),
             Ok(mut file) => {
                 res = file.write_all(b".yit/refs/heads/master");
-                if res.is_err() {
+                if res.is_err() || config.write(path.clone() + "/config").is_err() {
                     res = rollback(path);
                     if res.is_err() {
                         return Err(RepoError::RollbackError);
@@ -95,97 +149,502 @@ impl Repository {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn add(self, file_path: String) -> Result<(), RepoError> {
-        let index_file_path = String::from(".yit/index");
-        let full_file_path = file_path.clone();
-        match index::Index::new(index_file_path.clone()) {
-            Err(_) => Err(RepoError::IndexParsingError),
-            Ok(index_obj) => {
-                if index_obj.clone().tracks_file(file_path.clone()) {
-                    if index_obj.clone().has_different_hash(file_path.clone()) {
-                        let res =
-                            index_obj
-                                .clone()
-                                .add_obj(file_path, full_file_path, index_file_path);
-                        if res.is_err() {
-                            return Err(RepoError::IndexParsingError);
-                        }
-                    }
-                } else {
-                    let res = index_obj
-                        .clone()
-                        .add_obj(file_path, full_file_path, index_file_path);
-                    if res.is_err() {
-                        return Err(RepoError::IndexParsingError);
+        match self.add_paths(vec![file_path], AddOptions::default()) {
+            Err(err) => Err(err),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn add_paths(
+        self,
+        pathspecs: Vec<String>,
+        options: AddOptions,
+    ) -> Result<Vec<(String, String)>, RepoError> {
+        let store = self.store();
+        let specs: Vec<pathspec::Pathspec> = if pathspecs.is_empty() {
+            if !options.all && !options.update {
+                return Ok(Vec::new());
+            }
+            vec![pathspec::Pathspec::new(".")]
+        } else {
+            pathspecs
+                .iter()
+                .map(|spec| pathspec::Pathspec::new(spec))
+                .collect()
+        };
+        let mut index_obj = Repository::read_index(&*store)?;
+        let tracked = Repository::tracked_files(&*store, &index_obj)?;
+        let mut rules = if options.force {
+            ignore::IgnoreRules::default()
+        } else {
+            ignore::IgnoreRules::load()
+        };
+        let mut files: BTreeSet<String> = BTreeSet::new();
+        for spec in &specs {
+            let path = Path::new(&spec.spec);
+            let holds_tracked = tracked.keys().any(|tracked| spec.matches(tracked));
+            if !spec.is_glob()
+                && !spec.spec.is_empty()
+                && !holds_tracked
+                && path.exists()
+                && rules.is_ignored(&spec.spec, path.is_dir())
+            {
+                return Err(RepoError::IgnoredPath(spec.spec.clone()));
+            }
+            if !spec.is_glob() && path.is_file() {
+                files.insert(spec.spec.clone());
+            } else if Path::new(&spec.root()).is_dir() || spec.root().is_empty() {
+                match status::worktree_files(&spec.root(), &mut rules, &tracked) {
+                    Err(_) => return Err(RepoError::IOError),
+                    Ok(found) => files.extend(found.into_iter().filter(|path| spec.matches(path))),
+                }
+            }
+            if !spec.spec.is_empty()
+                && !holds_tracked
+                && !files.iter().any(|path| spec.matches(path))
+            {
+                return Err(RepoError::UntrackedPath(spec.spec.clone()));
+            }
+        }
+        let last_commit = Repository::get_current_head_last_commit()?;
+        let head_map = Repository::commit_index_map(&*store, &last_commit)?;
+        let mut staged = Vec::new();
+        for path in files {
+            if options.update && !tracked.contains_key(&path) {
+                continue;
+            }
+            match index_obj.hash_file(&*store, &path, true) {
+                Err(_) => return Err(RepoError::IOError),
+                Ok(hash) => {
+                    if tracked.get(&path) != Some(&hash) {
+                        index_obj.index_map.insert(path.clone(), hash);
+                        staged.push((String::from("add"), path));
                     }
                 }
-                Ok(())
             }
         }
+        for path in tracked.keys() {
+            if specs.iter().any(|spec| spec.matches(path)) && !Path::new(path).exists() {
+                Repository::unstage_path(&mut index_obj, &head_map, path);
+                staged.push((String::from("remove"), path.clone()));
+            }
+        }
+        staged.sort_by(|a, b| a.1.cmp(&b.1));
+        match index_obj.write() {
+            Err(_) => Err(RepoError::IndexParsingError),
+            Ok(_) => Ok(staged),
+        }
     }
 
-    pub fn commit(self, message: String) -> Result<(), RepoError> {
-        let res;
-        if !Path::new(".yit/index").exists() {
-            res = File::create(".yit/index");
+    pub fn add_patch(
+        self,
+        pathspecs: Vec<String>,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Result<Vec<String>, RepoError> {
+        let store = self.store();
+        let specs: Vec<pathspec::Pathspec> = if pathspecs.is_empty() {
+            vec![pathspec::Pathspec::new(".")]
         } else {
-            res = File::open(".yit/index");
+            pathspecs
+                .iter()
+                .map(|spec| pathspec::Pathspec::new(spec))
+                .collect()
+        };
+        let mut index_obj = Repository::read_index(&*store)?;
+        let tracked = Repository::tracked_files(&*store, &index_obj)?;
+        let mut paths: Vec<&String> = tracked
+            .keys()
+            .filter(|path| specs.iter().any(|spec| spec.matches(path)))
+            .collect();
+        paths.sort();
+        let mut staged = Vec::new();
+        for path in paths {
+            // deletions and binary files are left to `add`
+            let new = match fs::read(path) {
+                Err(_) => continue,
+                Ok(content) => diff::as_text(content),
+            };
+            let old = match store::ObjectId::from_hex(&tracked[path], store.format()) {
+                None => return Err(RepoError::IndexParsingError),
+                Some(id) => match store.get(&id) {
+                    Err(_) => return Err(RepoError::IOError),
+                    Ok((_, content)) => diff::as_text(content),
+                },
+            };
+            let (old, new) = match (old, new) {
+                (Some(old), Some(new)) => (old, new),
+                _ => {
+                    let _ = writeln!(output, "Skipping binary file {}", path);
+                    continue;
+                }
+            };
+            if old == new {
+                continue;
+            }
+            let old_lines = diff::split_lines(&old);
+            let hunks = diff::hunks(&old_lines, &diff::split_lines(&new), 3);
+            let header = format!("diff --yit a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n", path);
+            if output.write_all(header.as_bytes()).is_err() {
+                return Err(RepoError::IOError);
+            }
+            let (selected, quit) = match patch::select_hunks(&old_lines, hunks, input, output) {
+                Err(_) => return Err(RepoError::IOError),
+                Ok(result) => result,
+            };
+            if !selected.is_empty() {
+                let content = diff::apply_hunks(&old_lines, &selected);
+                if index_obj
+                    .add_content(&*store, path.clone(), content.as_bytes())
+                    .is_err()
+                {
+                    return Err(RepoError::IndexParsingError);
+                }
+                staged.push(path.clone());
+            }
+            if quit {
+                break;
+            }
         }
+        Ok(staged)
+    }
+
+    pub fn commit(self, message: String) -> Result<(), RepoError> {
+        self.write_tip(Some(message), false)
+    }
+
+    /// Keeps the tip's parents and author, and its message when none is given.
+    pub fn amend(self, message: Option<String>) -> Result<(), RepoError> {
+        self.write_tip(message, true)
+    }
+
+    fn write_tip(self, message: Option<String>, amend: bool) -> Result<(), RepoError> {
+        let store = self.store();
+        let mut index_obj = match index::Index::new(String::from(".yit/index"), store.format()) {
+            Err(_) => return Err(RepoError::IndexParsingError),
+            Ok(index_obj) => index_obj,
+        };
+        let index_map = std::mem::take(&mut index_obj.index_map);
         match Repository::get_current_head_last_commit() {
-            Err(_) => return Err(RepoError::CommitError),
-            Ok(last_commit) => {
-                let index_file_path = String::from(".yit/index");
-                match res {
-                    Err(_) => Err(RepoError::IOError),
-                    Ok(_) => match index::Index::new(index_file_path.clone()) {
-                        Err(_) => Err(RepoError::IndexParsingError),
-                        Ok(index_obj) => match Repository::get_current_head() {
-                            Err(_) => Err(RepoError::IOError),
-                            Ok(head) => {
-                                let mut index_map: HashMap<String, String> = HashMap::new();
-                                if Path::new(&head).exists() {
-                                    let commit_tree = commit::CommitNode::new(last_commit);
-                                    index_map =
-                                        tree::Tree::tree_to_index_map(commit_tree.tree_hash);
-                                }
-                                index_map.extend(index_obj.index_map);
-                                let tr = tree::Tree::new(index_map);
-                                let hash = tr.hash_tree();
-                                match Repository::get_current_head_last_commit() {
-                                    Err(_) => Err(RepoError::CommitError),
-                                    Ok(parent_commit) => {
-                                        match commit::write_commit(
-                                            message,
-                                            vec![parent_commit],
-                                            hash,
-                                        ) {
-                                            Err(_) => Err(RepoError::CommitError),
-                                            Ok(hash) => match File::create(head) {
-                                                Err(_) => Err(RepoError::IOError),
-                                                Ok(mut file) => {
-                                                    let content = String::from("ref\n") + &hash;
-                                                    match file.write_all(content.as_bytes()) {
-                                                        Err(_) => Err(RepoError::CommitError),
-                                                        Ok(_) => {
-                                                            match fs::remove_file(".yit/index") {
-                                                                Err(_) => {
-                                                                    Err(RepoError::CommitError)
-                                                                }
-                                                                Ok(_) => Ok(()),
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            },
-                                        }
+            Err(_) => Err(RepoError::CommitError),
+            Ok(last_commit) => match Repository::get_current_head() {
+                Err(_) => Err(RepoError::IOError),
+                Ok(head) => {
+                    let tip = if last_commit.is_empty() {
+                        None
+                    } else {
+                        match commit::CommitNode::read(&*store, &last_commit) {
+                            Err(_) => return Err(RepoError::CommitError),
+                            Ok(node) => Some(node),
+                        }
+                    };
+                    let message = match message {
+                        Some(message) => message,
+                        None => match commit::read_message(&*store, &last_commit) {
+                            Err(_) => return Err(RepoError::CommitError),
+                            Ok(message) => message,
+                        },
+                    };
+                    let subject = String::from(message.lines().next().unwrap_or(""));
+                    let mut tree_map = Repository::commit_index_map(&*store, &last_commit)?;
+                    index::apply(&mut tree_map, index_map);
+                    let hash = tree::Tree::new(tree_map).hash_tree(&*store);
+                    let (author, committer) = Repository::signatures()?;
+                    let (parents, author, reflog_message) = match tip {
+                        None if amend => return Err(RepoError::CommitError),
+                        Some(tip) if amend => (
+                            tip.parents,
+                            tip.author.unwrap_or(author),
+                            String::from("commit (amend): ") + &subject,
+                        ),
+                        None => (
+                            Vec::new(),
+                            author,
+                            String::from("commit (initial): ") + &subject,
+                        ),
+                        Some(_) => (
+                            vec![last_commit.clone()],
+                            author,
+                            String::from("commit: ") + &subject,
+                        ),
+                    };
+                    match commit::write_commit(&*store, message, parents, hash, &author, &committer)
+                    {
+                        Err(_) => Err(RepoError::CommitError),
+                        Ok(hash) => {
+                            Repository::update_commit_graph(&*store, &hash);
+                            match Repository::update_head(head, &hash) {
+                                Err(_) => Err(RepoError::CommitError),
+                                Ok(_) => {
+                                    Repository::log_ref_update(
+                                        &Repository::head_refname(),
+                                        &last_commit,
+                                        &hash,
+                                        &reflog_message,
+                                    )?;
+                                    // nothing is staged any more, but the
+                                    // stat data still holds
+                                    match index_obj.write() {
+                                        Err(_) => Err(RepoError::CommitError),
+                                        Ok(_) => Ok(()),
                                     }
                                 }
                             }
-                        },
-                    },
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    fn commit_index_map(
+        store: &dyn store::ObjectStore,
+        hash: &str,
+    ) -> Result<HashMap<String, String>, RepoError> {
+        if hash.is_empty() {
+            return Ok(HashMap::new());
+        }
+        match commit::CommitNode::read(store, hash) {
+            Err(_) => Err(RepoError::CommitError),
+            Ok(node) => Ok(tree::Tree::tree_to_index_map(store, node.tree_hash)),
+        }
+    }
+
+    pub fn staged_changes(self) -> Result<Vec<(String, String)>, RepoError> {
+        Repository::changes_to_commit(&*self.store(), false)
+    }
+
+    fn read_index(store: &dyn store::ObjectStore) -> Result<index::Index, RepoError> {
+        if Path::new(".yit/index").exists() {
+            match index::Index::new(String::from(".yit/index"), store.format()) {
+                Err(_) => Err(RepoError::IndexParsingError),
+                Ok(index) => Ok(index),
+            }
+        } else {
+            Ok(index::Index::empty(
+                String::from(".yit/index"),
+                store.format(),
+            ))
+        }
+    }
+
+    fn tracked_files(
+        store: &dyn store::ObjectStore,
+        index_obj: &index::Index,
+    ) -> Result<HashMap<String, String>, RepoError> {
+        let last_commit = Repository::get_current_head_last_commit()?;
+        let mut tracked = Repository::commit_index_map(store, &last_commit)?;
+        index::apply(&mut tracked, index_obj.index_map.clone());
+        Ok(tracked)
+    }
+
+    fn changes_to_commit(
+        store: &dyn store::ObjectStore,
+        amend: bool,
+    ) -> Result<Vec<(String, String)>, RepoError> {
+        let last_commit = Repository::get_current_head_last_commit()?;
+        let base_map = if amend && !last_commit.is_empty() {
+            match commit::CommitNode::read(store, &last_commit) {
+                Err(_) => return Err(RepoError::CommitError),
+                Ok(node) => Repository::commit_index_map(
+                    store,
+                    node.parents.first().map_or("", |parent| parent.as_str()),
+                )?,
+            }
+        } else {
+            Repository::commit_index_map(store, &last_commit)?
+        };
+        let next_map = Repository::tracked_files(store, &Repository::read_index(store)?)?;
+        let mut changes: Vec<(String, String)> = base_map
+            .keys()
+            .filter(|path| !next_map.contains_key(*path))
+            .map(|path| (String::from("deleted"), path.clone()))
+            .collect();
+        changes.extend(
+            next_map
+                .into_iter()
+                .filter_map(|(path, hash)| match base_map.get(&path) {
+                    None => Some((String::from("new file"), path)),
+                    Some(base_hash) if *base_hash != hash => Some((String::from("modified"), path)),
+                    Some(_) => None,
+                }),
+        );
+        changes.sort_by(|a, b| a.1.cmp(&b.1));
+        Ok(changes)
+    }
+
+    fn unstage_path(index_obj: &mut index::Index, head_map: &HashMap<String, String>, path: &str) {
+        if head_map.contains_key(path) {
+            index_obj
+                .index_map
+                .insert(String::from(path), String::from(index::DELETED));
+        } else {
+            index_obj.index_map.remove(path);
+        }
+    }
+
+    fn remove_empty_parents(path: &Path) {
+        let mut dir = path.parent();
+        while let Some(parent) = dir {
+            if parent.as_os_str().is_empty() || fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+
+    pub fn rm(self, paths: Vec<String>, cached: bool) -> Result<(), RepoError> {
+        let store = self.store();
+        let mut index_obj = Repository::read_index(&*store)?;
+        let tracked = Repository::tracked_files(&*store, &index_obj)?;
+        if let Some(path) = paths.iter().find(|path| !tracked.contains_key(*path)) {
+            return Err(RepoError::UntrackedPath(path.clone()));
+        }
+        let last_commit = Repository::get_current_head_last_commit()?;
+        let head_map = Repository::commit_index_map(&*store, &last_commit)?;
+        for path in &paths {
+            Repository::unstage_path(&mut index_obj, &head_map, path);
+        }
+        if index_obj.write().is_err() {
+            return Err(RepoError::IndexParsingError);
+        }
+        if !cached {
+            for path in &paths {
+                match fs::remove_file(path) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                        return Err(RepoError::IOError)
+                    }
+                    _ => Repository::remove_empty_parents(Path::new(path)),
                 }
             }
         }
+        Ok(())
+    }
+
+    /// The staged content moves along, even if the worktree file differs.
+    pub fn mv(self, source: String, destination: String) -> Result<(), RepoError> {
+        let store = self.store();
+        let source = String::from(source.trim_end_matches('/'));
+        let destination = match Path::new(&source).file_name() {
+            Some(name) if Path::new(&destination).is_dir() => {
+                String::from(destination.trim_end_matches('/')) + "/" + &name.to_string_lossy()
+            }
+            _ => destination,
+        };
+        if Path::new(&destination).exists() {
+            return Err(RepoError::DestinationExists(destination));
+        }
+        let mut index_obj = Repository::read_index(&*store)?;
+        let tracked = Repository::tracked_files(&*store, &index_obj)?;
+        let prefix = source.clone() + "/";
+        let moved: Vec<(String, String)> = tracked
+            .into_iter()
+            .filter(|(path, _)| *path == source || path.starts_with(&prefix))
+            .collect();
+        if moved.is_empty() {
+            return Err(RepoError::UntrackedPath(source));
+        }
+        if let Some(parent) = Path::new(&destination).parent() {
+            if fs::create_dir_all(parent).is_err() {
+                return Err(RepoError::IOError);
+            }
+        }
+        if fs::rename(&source, &destination).is_err() {
+            return Err(RepoError::IOError);
+        }
+        let last_commit = Repository::get_current_head_last_commit()?;
+        let head_map = Repository::commit_index_map(&*store, &last_commit)?;
+        for (path, hash) in moved {
+            Repository::unstage_path(&mut index_obj, &head_map, &path);
+            let new_path = destination.clone() + &path[source.len()..];
+            index_obj.index_map.insert(new_path, hash);
+        }
+        match index_obj.write() {
+            Err(_) => Err(RepoError::IndexParsingError),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    fn current_branch() -> Result<Option<String>, RepoError> {
+        match branch::head_ref() {
+            Err(_) => Err(RepoError::IOError),
+            Ok(head_ref) => {
+                Ok(head_ref.map(|name| String::from(name.trim_start_matches("refs/heads/"))))
+            }
+        }
+    }
+
+    /// A negated rule means the path is not ignored. Tracked files are never
+    /// ignored unless `no_index` is set.
+    pub fn check_ignore(
+        self,
+        paths: Vec<String>,
+        no_index: bool,
+    ) -> Result<Vec<(String, Option<ignore::IgnoreRule>)>, RepoError> {
+        let store = self.store();
+        let tracked = if no_index {
+            HashMap::new()
+        } else {
+            Repository::tracked_files(&*store, &Repository::read_index(&*store)?)?
+        };
+        let mut rules = ignore::IgnoreRules::load();
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                let path = String::from(path.trim_start_matches("./"));
+                let rule = if tracked.contains_key(&path) {
+                    None
+                } else {
+                    let is_dir = path.ends_with('/') || Path::new(&path).is_dir();
+                    rules.matching(&path, is_dir)
+                };
+                (path, rule)
+            })
+            .collect())
+    }
+
+    pub fn status(self) -> Result<status::Status, RepoError> {
+        let store = self.store();
+        let head = Repository::get_current_head_last_commit()?;
+        let head_map = Repository::commit_index_map(&*store, &head)?;
+        let mut index_obj = Repository::read_index(&*store)?;
+        let tracked = Repository::tracked_files(&*store, &index_obj)?;
+        let worktree = match status::hash_worktree(&*store, &tracked, &mut index_obj) {
+            Err(_) => return Err(RepoError::IOError),
+            Ok(worktree) => worktree,
+        };
+        // keep what was learned about the files for the next time
+        if index_obj.stat_changed() && index_obj.write().is_err() {
+            return Err(RepoError::IndexParsingError);
+        }
+        let (entries, untracked) = status::compare(&head_map, &index_obj.index_map, &worktree);
+        Ok(status::Status {
+            branch: Repository::current_branch()?,
+            head,
+            entries,
+            untracked,
+        })
+    }
+
+    /// An empty message means the commit should be aborted.
+    pub fn edit_commit_message(self, amend: bool) -> Result<String, RepoError> {
+        let store = self.store();
+        let branch = Repository::current_branch()?;
+        let changes = Repository::changes_to_commit(&*store, amend)?;
+        let mut template = message::template(branch.as_deref(), &changes);
+        if amend {
+            let last_commit = Repository::get_current_head_last_commit()?;
+            match commit::read_message(&*store, &last_commit) {
+                Err(_) => return Err(RepoError::CommitError),
+                Ok(old) => template = message::cleanup(&old, false) + "\n" + &template,
+            }
+        }
+        match message::edit(COMMIT_EDITMSG, &template) {
+            Err(err) => Err(RepoError::EditorError(err)),
+            Ok(edited) => Ok(message::cleanup(&edited, true)),
+        }
     }
 
     fn change_head_last_commit(path: String) -> Result<(), RepoError> {
@@ -218,118 +677,225 @@ impl Repository {
         }
     }
 
+    fn signatures() -> Result<(identity::Signature, identity::Signature), RepoError> {
+        let config = config::Config::merged();
+        match (
+            identity::Signature::author(&config),
+            identity::Signature::committer(&config),
+        ) {
+            (Ok(author), Ok(committer)) => Ok((author, committer)),
+            _ => Err(RepoError::CommitError),
+        }
+    }
+
+    /// A detached HEAD holds a commit id instead of the path of a branch.
+    fn is_detached(head: &str) -> bool {
+        store::ObjectId::from_hex(head.trim(), encoding::ObjectFormat::current()).is_some()
+    }
+
+    fn update_head(head: String, hash: &str) -> Result<(), RepoError> {
+        let (path, content) = if Repository::is_detached(&head) {
+            (String::from(".yit/HEAD"), String::from(hash))
+        } else {
+            (head, String::from("ref\n") + hash)
+        };
+        match fs::write(path, content) {
+            Err(_) => Err(RepoError::IOError),
+            Ok(_) => Ok(()),
+        }
+    }
+
     fn get_current_head_last_commit() -> Result<String, RepoError> {
-        match File::open(String::from(".yit/HEAD")) {
+        match branch::head_commit() {
             Err(_) => Err(RepoError::IOError),
-            Ok(mut head_file) => {
-                let mut contents = String::new();
-                let read_res = head_file.read_to_string(&mut contents);
-                if read_res.is_err() {
-                    return Err(RepoError::IOError);
-                }
-                match File::open(contents.clone()) {
-                    Err(_) => Ok(String::from("")),
-                    Ok(mut ref_file) => {
-                        let mut contents = String::new();
-                        let read_res = ref_file.read_to_string(&mut contents);
-                        if read_res.is_err() {
-                            return Err(RepoError::IOError);
-                        }
-                        let words: Vec<&str> = contents.split('\n').collect();
-                        Ok(String::from(words[1]))
-                    }
-                }
-            }
+            Ok(commit) => Ok(commit),
         }
     }
 
+    /// A missing branch is created at the current commit, unless the name
+    /// resolves to another revision, which is checked out detached.
     pub fn checkout(self, branch_name: String) -> Result<(), RepoError> {
-        if !Path::new(&(String::from(".yit/refs/heads/") + &branch_name)).exists() {
-            let res = Repository::change_head_last_commit(
-                String::from(".yit/refs/heads/") + &branch_name,
-            );
-            if res.is_err() {
-                return Err(RepoError::CheckoutError);
+        let store = self.store();
+        let staged = Repository::read_index(&*store)?.index_map;
+        // `@{-n}` switches back to the branch it names rather than detaching
+        let previous = branch_name
+            .strip_prefix("@{-")
+            .and_then(|n| n.strip_suffix('}'))
+            .and_then(|n| n.parse::<usize>().ok());
+        let branch_name = match previous {
+            None => branch_name,
+            Some(n) => match revision::previous_checkout(n) {
+                None => {
+                    return Err(RepoError::RevisionError(revision::RevisionError::Invalid(
+                        branch_name,
+                    )))
+                }
+                Some(previous) => previous,
+            },
+        };
+        let old_commit = Repository::get_current_head_last_commit()?;
+        let old_name = match branch::head_ref() {
+            Ok(Some(refname)) => String::from(refname.trim_start_matches("refs/heads/")),
+            _ => old_commit.clone(),
+        };
+        let branch_path = String::from(".yit/refs/heads/") + &branch_name;
+        let mut head = branch_path.clone();
+        if !Path::new(&branch_path).exists() {
+            match revision::resolve_commit(&*store, &branch_name) {
+                Ok(commit) => head = commit.to_string(),
+                Err(revision::RevisionError::NotFound(_)) => {
+                    let res = Repository::change_head_last_commit(branch_path);
+                    if res.is_err() {
+                        return Err(RepoError::CheckoutError);
+                    }
+                    if !old_commit.is_empty() {
+                        Repository::log_ref_update(
+                            &(String::from("refs/heads/") + &branch_name),
+                            "",
+                            &old_commit,
+                            "branch: Created from HEAD",
+                        )?;
+                    }
+                }
+                Err(err) => return Err(RepoError::RevisionError(err)),
             }
         }
         let head_file_res = File::create(String::from(".yit/HEAD"));
         match head_file_res {
             Err(_) => Err(RepoError::IOError),
             Ok(mut head_file) => {
-                let res = head_file
-                    .write_all((String::from(".yit/refs/heads/") + &branch_name).as_bytes());
+                let res = head_file.write_all(head.as_bytes());
                 if res.is_err() {
                     return Err(RepoError::IOError);
                 }
-                if Path::new(".yit/index").exists() {
-                    let res = std::fs::remove_file(".yit/index");
-                    if res.is_err() {
-                        return Err(RepoError::IOError);
-                    }
+                let new_commit = Repository::get_current_head_last_commit()?;
+                if !new_commit.is_empty() {
+                    Repository::log_ref_update(
+                        "HEAD",
+                        &old_commit,
+                        &new_commit,
+                        &format!("checkout: moving from {} to {}", old_name, branch_name),
+                    )?;
                 }
-                match branch::get_commit(branch_name) {
+                // staged changes are carried over, the stat data is that of
+                // the files checked out below
+                let mut index_obj = index::Index::empty(String::from(".yit/index"), store.format());
+                index_obj.index_map = staged;
+                let commit = if Repository::is_detached(&head) {
+                    Ok(head)
+                } else {
+                    branch::get_commit(branch_name)
+                };
+                match commit {
                     Err(_) => Err(RepoError::CheckoutError),
-                    Ok(commit) => {
-                        let commit_tree = commit::CommitNode::new(commit.clone());
-                        let tree_index_map = tree::Tree::tree_to_index_map(commit_tree.tree_hash);
-                        tree::Tree::load_index_map(tree_index_map);
-                        Ok(())
-                    }
+                    // a branch without commits has nothing to check out
+                    Ok(commit) if commit.is_empty() => match index_obj.write() {
+                        Err(_) => Err(RepoError::IndexParsingError),
+                        Ok(_) => Ok(()),
+                    },
+                    Ok(commit) => match commit::CommitNode::read(&*store, &commit) {
+                        Err(_) => Err(RepoError::CheckoutError),
+                        Ok(node) => {
+                            let mut tree_index_map =
+                                tree::Tree::tree_to_index_map(&*store, node.tree_hash);
+                            // staged paths keep their working files
+                            tree_index_map
+                                .retain(|path, _| !index_obj.index_map.contains_key(path));
+                            for (path, hash) in tree::Tree::load_index_map(&*store, tree_index_map)
+                            {
+                                index_obj.cache_stat(&path, &hash);
+                            }
+                            match index_obj.write() {
+                                Err(_) => Err(RepoError::IndexParsingError),
+                                Ok(_) => Ok(()),
+                            }
+                        }
+                    },
                 }
             }
         }
     }
 
     pub fn merge(self, branch: String, into_branch: String) -> Result<(), RepoError> {
-        match branch::get_commit(branch.clone()) {
-            Err(_) => Err(RepoError::MergeError),
+        let store = self.store();
+        let into_ref = String::from("refs/heads/") + &into_branch;
+        let three_way_message = format!("merge {}: Merge made by three-way merge.", branch);
+        match revision::resolve_commit(&*store, &branch) {
+            Err(err) => Err(RepoError::RevisionError(err)),
             Ok(commit) => {
-                let commit_tree = commit::CommitNode::new(commit.clone());
+                let commit = commit.to_string();
                 match branch::get_commit(into_branch.clone()) {
                     Err(_) => Err(RepoError::MergeError),
                     Ok(into_commit) => {
-                        let into_commit_tree = commit::CommitNode::new(into_commit.clone());
-                        if commit_tree.clone().is_parent(&into_commit) {
+                        let mut graph = Repository::commit_graph(&*store);
+                        let (up_to_date, fast_forward) = match (
+                            graph.is_ancestor(&commit, &into_commit),
+                            graph.is_ancestor(&into_commit, &commit),
+                        ) {
+                            (Ok(up_to_date), Ok(fast_forward)) => (up_to_date, fast_forward),
+                            _ => return Err(RepoError::MergeError),
+                        };
+                        if up_to_date {
+                            //nothing
+                            Ok(())
+                        } else if fast_forward {
                             println!("Fastforward");
-                            let res = branch::set_last_commit(into_branch, commit);
+                            let res = branch::set_last_commit(into_branch.clone(), commit.clone());
                             if res.is_err() {
                                 return Err(RepoError::MergeError);
                             }
-                            return Ok(());
-                        } else if into_commit_tree.clone().is_parent(&commit) {
-                            //nothing
-                            return Ok(());
+                            Repository::log_ref_update(
+                                &into_ref,
+                                &into_commit,
+                                &commit,
+                                &format!("merge {}: Fast-forward", branch),
+                            )
                         } else {
                             println!("Non-fastforward (3way merge)");
-                            match commit_tree
-                                .clone()
-                                .get_most_recent_parent_commit(into_commit_tree.clone())
-                            {
-                                None => Err(RepoError::CommitError),
-                                Some(parent) => {
+                            let trees = (
+                                graph.merge_base(&commit, &into_commit),
+                                graph.get(&commit),
+                                graph.get(&into_commit),
+                            );
+                            match trees {
+                                (Ok(Some(parent)), Ok(commit_tree), Ok(into_commit_tree)) => {
+                                    let parent_tree = match graph.get(&parent) {
+                                        Err(_) => return Err(RepoError::CommitError),
+                                        Ok(parent) => parent.tree_hash.clone(),
+                                    };
                                     let parent_index_map =
-                                        tree::Tree::tree_to_index_map(parent.tree_hash);
-                                    let branch_index_map =
-                                        tree::Tree::tree_to_index_map(commit_tree.tree_hash);
-                                    let into_branch_index_map =
-                                        tree::Tree::tree_to_index_map(into_commit_tree.tree_hash);
+                                        tree::Tree::tree_to_index_map(&*store, parent_tree);
+                                    let branch_index_map = tree::Tree::tree_to_index_map(
+                                        &*store,
+                                        commit_tree.tree_hash.clone(),
+                                    );
+                                    let into_branch_index_map = tree::Tree::tree_to_index_map(
+                                        &*store,
+                                        into_commit_tree.tree_hash.clone(),
+                                    );
                                     match merge::three_fold(
+                                        &*store,
                                         parent_index_map,
                                         branch_index_map,
                                         into_branch_index_map,
                                     ) {
                                         Err(_) => Err(RepoError::MergeError),
                                         Ok(new_tree) => {
+                                            let (author, committer) = Repository::signatures()?;
                                             match commit::write_commit(
+                                                &*store,
                                                 String::from("Merge ")
                                                     + &branch
                                                     + " into "
                                                     + &into_branch,
-                                                vec![branch, into_branch.clone()],
+                                                vec![into_commit.clone(), commit.clone()],
                                                 new_tree,
+                                                &author,
+                                                &committer,
                                             ) {
                                                 Err(_) => Err(RepoError::CommitError),
                                                 Ok(hash) => {
+                                                    Repository::update_commit_graph(&*store, &hash);
                                                     let file_res = File::create(
                                                         String::from(".yit/refs/heads/")
                                                             + &into_branch,
@@ -344,7 +910,12 @@ impl Repository {
                                                             if res.is_err() {
                                                                 return Err(RepoError::IOError);
                                                             }
-                                                            Ok(())
+                                                            Repository::log_ref_update(
+                                                                &into_ref,
+                                                                &into_commit,
+                                                                &hash,
+                                                                &three_way_message,
+                                                            )
                                                         }
                                                     }
                                                 }
@@ -352,6 +923,7 @@ impl Repository {
                                         }
                                     }
                                 }
+                                _ => Err(RepoError::CommitError),
                             }
                         }
                     }
@@ -361,35 +933,228 @@ impl Repository {
     }
 
     pub fn diff(self, branch1: String, branch2: String) -> Result<(), RepoError> {
-        match branch::get_commit(branch1.clone()) {
-            Err(_) => Err(RepoError::MergeError),
-            Ok(commit1) => {
-                let commit1_tree = commit::CommitNode::new(commit1.clone());
-                match branch::get_commit(branch2.clone()) {
-                    Err(_) => Err(RepoError::MergeError),
-                    Ok(commit2) => {
-                        let commit2_tree = commit::CommitNode::new(commit2.clone());
-                        let commit1_index_map =
-                            tree::Tree::tree_to_index_map(commit1_tree.tree_hash);
-                        let commit2_index_map =
-                            tree::Tree::tree_to_index_map(commit2_tree.tree_hash);
-                        let mut result = String::from("");
-                        for (key, _) in commit1_index_map.clone() {
-                            if commit2_index_map.contains_key(&key) {
-                                let diff = diff::get_diff_files(
-                                    &commit1_index_map[&key],
-                                    &commit2_index_map[&key],
-                                );
-                                result.push_str(&key);
-                                result.push_str(": \n");
-                                result.push_str(&diff);
+        let store = self.store();
+        match revision::resolve_commit(&*store, &branch1) {
+            Err(err) => Err(RepoError::RevisionError(err)),
+            Ok(commit1) => match revision::resolve_commit(&*store, &branch2) {
+                Err(err) => Err(RepoError::RevisionError(err)),
+                Ok(commit2) => {
+                    let mut graph = Repository::commit_graph(&*store);
+                    let (commit1_tree, commit2_tree) =
+                        match (graph.get(commit1.as_str()), graph.get(commit2.as_str())) {
+                            (Ok(node1), Ok(node2)) => {
+                                (node1.tree_hash.clone(), node2.tree_hash.clone())
                             }
+                            _ => return Err(RepoError::IOError),
+                        };
+                    let commit1_index_map = tree::Tree::tree_to_index_map(&*store, commit1_tree);
+                    let commit2_index_map = tree::Tree::tree_to_index_map(&*store, commit2_tree);
+                    let mut result = String::from("");
+                    for (key, _) in commit1_index_map.clone() {
+                        if commit2_index_map.contains_key(&key) {
+                            let diff = diff::get_diff_files(
+                                &*store,
+                                &commit1_index_map[&key],
+                                &commit2_index_map[&key],
+                            );
+                            result.push_str(&key);
+                            result.push_str(": \n");
+                            result.push_str(&diff);
                         }
-                        println!("{}", result);
-                        Ok(())
                     }
+                    println!("{}", result);
+                    Ok(())
                 }
+            },
+        }
+    }
+
+    fn commit_graph(store: &dyn store::ObjectStore) -> commit::CommitGraph<'_> {
+        let graph = commit::CommitGraph::new(store);
+        match commitgraph::GraphFile::open(COMMIT_GRAPH, store.format()) {
+            Err(_) => graph,
+            Ok(cache) => graph.with_cache(cache),
+        }
+    }
+
+    fn head_refname() -> String {
+        match branch::head_ref() {
+            Ok(Some(refname)) => refname,
+            _ => String::from("HEAD"),
+        }
+    }
+
+    fn log_ref_update(refname: &str, old: &str, new: &str, message: &str) -> Result<(), RepoError> {
+        let (_, committer) = Repository::signatures()?;
+        let mut refnames = vec![String::from(refname)];
+        if refname != "HEAD" && Repository::head_refname() == refname {
+            refnames.push(String::from("HEAD"));
+        }
+        for refname in refnames {
+            if reflog::append(&refname, old, new, &committer, message).is_err() {
+                return Err(RepoError::IOError);
+            }
+        }
+        Ok(())
+    }
+
+    /// The commit-graph file is only a cache, so failing to update it is fine.
+    fn update_commit_graph(store: &dyn store::ObjectStore, hash: &str) {
+        let _ = commitgraph::update(store, COMMIT_GRAPH, &[String::from(hash)]);
+    }
+
+    fn ref_roots(store: &dyn store::ObjectStore) -> Result<Vec<store::ObjectId>, RepoError> {
+        match branch::all_refs() {
+            Err(_) => Err(RepoError::IOError),
+            Ok(refs) => Ok(refs
+                .iter()
+                .filter_map(|(_, hash)| store::ObjectId::from_hex(hash, store.format()))
+                .collect()),
+        }
+    }
+
+    fn reflog_roots(store: &dyn store::ObjectStore) -> Vec<store::ObjectId> {
+        let mut roots = Vec::new();
+        for refname in reflog::all_refnames() {
+            for entry in reflog::read(&refname).unwrap_or_default() {
+                roots.extend(store::ObjectId::from_hex(&entry.old, store.format()));
+                roots.extend(store::ObjectId::from_hex(&entry.new, store.format()));
+            }
+        }
+        roots.retain(|id| store.exists(id));
+        roots
+    }
+
+    fn index_roots(store: &dyn store::ObjectStore) -> Result<Vec<store::ObjectId>, RepoError> {
+        if !Path::new(".yit/index").exists() {
+            return Ok(Vec::new());
+        }
+        match index::Index::new(String::from(".yit/index"), store.format()) {
+            Err(_) => Err(RepoError::IndexParsingError),
+            Ok(index_obj) => Ok(index_obj
+                .index_map
+                .values()
+                .filter_map(|hash| store::ObjectId::from_hex(hash, store.format()))
+                .collect()),
+        }
+    }
+
+    /// Writes one pack per object encoding in use.
+    pub fn repack(self) -> Result<Vec<String>, RepoError> {
+        let store = self.store();
+        let roots = Repository::ref_roots(&*store)?;
+        match pack::reachable_objects(&*store, &roots) {
+            Err(_) => Err(RepoError::PackError),
+            Ok(objects) => match store.write_pack(&objects) {
+                Err(_) => Err(RepoError::PackError),
+                Ok(names) => Ok(names),
+            },
+        }
+    }
+
+    /// `prune` defaults to `gc.pruneexpire`, or two weeks.
+    pub fn gc(self, prune: Option<String>) -> Result<gc::GcReport, RepoError> {
+        let store = self.store();
+        let expire = match prune {
+            Some(prune) => prune,
+            None => config::Config::current()
+                .get("gc.pruneexpire")
+                .unwrap_or_else(|| String::from(gc::DEFAULT_EXPIRE)),
+        };
+        let expire = match gc::parse_expire(&expire) {
+            Err(_) => return Err(RepoError::GcError),
+            Ok(expire) => expire,
+        };
+        let mut roots = Repository::ref_roots(&*store)?;
+        // staged but not yet committed blobs must survive
+        roots.extend(Repository::index_roots(&*store)?);
+        // so must earlier values of refs, for `@{n}`
+        roots.extend(Repository::reflog_roots(&*store));
+        let report = match gc::collect(&*store, &roots, expire) {
+            Err(_) => return Err(RepoError::GcError),
+            Ok(report) => report,
+        };
+        // rebuilt from scratch so pruned commits drop out
+        let mut tips = Vec::new();
+        for root in Repository::ref_roots(&*store)? {
+            if let Ok(commit) = revision::resolve_commit(&*store, root.as_str()) {
+                tips.push(commit.to_string());
             }
         }
+        let mut graph = commitgraph::GraphFile::new();
+        match graph.add(&*store, &tips) {
+            Err(_) => Err(RepoError::GcError),
+            Ok(_) => match graph.write(COMMIT_GRAPH, store.format()) {
+                Err(_) => Err(RepoError::GcError),
+                Ok(_) => Ok(report),
+            },
+        }
+    }
+
+    pub fn fsck(self) -> Result<fsck::FsckReport, RepoError> {
+        let store = self.store();
+        let refs = match branch::all_refs() {
+            Err(_) => return Err(RepoError::IOError),
+            Ok(refs) => refs,
+        };
+        let mut roots = Repository::index_roots(&*store)?;
+        roots.extend(Repository::reflog_roots(&*store));
+        Ok(fsck::check(&*store, &refs, &roots))
+    }
+
+    pub fn log(
+        self,
+        revisions: Vec<String>,
+        options: log::LogOptions,
+    ) -> Result<String, RepoError> {
+        let store = self.store();
+        let revisions = if revisions.is_empty() {
+            vec![String::from("HEAD")]
+        } else {
+            revisions
+        };
+        let set = match revision::resolve_set(&*store, &revisions) {
+            Err(err) => return Err(RepoError::RevisionError(err)),
+            Ok(set) => set,
+        };
+        let mut graph = Repository::commit_graph(&*store);
+        match log::walk(&*store, &mut graph, &set, &options) {
+            Err(_) => Err(RepoError::LogError),
+            Ok(entries) => Ok(log::format_log(&*store, &entries, &options.format)),
+        }
+    }
+
+    pub fn merge_bases(self, first: String, second: String) -> Result<Vec<String>, RepoError> {
+        let store = self.store();
+        match revision::resolve_commit(&*store, &first) {
+            Err(err) => Err(RepoError::RevisionError(err)),
+            Ok(first) => match revision::resolve_commit(&*store, &second) {
+                Err(err) => Err(RepoError::RevisionError(err)),
+                Ok(second) => {
+                    let mut graph = Repository::commit_graph(&*store);
+                    match graph.merge_bases(first.as_str(), second.as_str()) {
+                        Err(_) => Err(RepoError::MergeError),
+                        Ok(bases) => Ok(bases),
+                    }
+                }
+            },
+        }
+    }
+
+    pub fn is_ancestor(self, ancestor: String, descendant: String) -> Result<bool, RepoError> {
+        let store = self.store();
+        match revision::resolve_commit(&*store, &ancestor) {
+            Err(err) => Err(RepoError::RevisionError(err)),
+            Ok(ancestor) => match revision::resolve_commit(&*store, &descendant) {
+                Err(err) => Err(RepoError::RevisionError(err)),
+                Ok(descendant) => {
+                    let mut graph = Repository::commit_graph(&*store);
+                    match graph.is_ancestor(ancestor.as_str(), descendant.as_str()) {
+                        Err(_) => Err(RepoError::MergeError),
+                        Ok(is_ancestor) => Ok(is_ancestor),
+                    }
+                }
+            },
+        }
     }
 }
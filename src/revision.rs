@@ -0,0 +1,342 @@
+use crate::branch;
+use crate::commit;
+use crate::log;
+use crate::object;
+use crate::reflog;
+use crate::store;
+use std::fmt;
+
+/// Shortest hex prefix accepted as an abbreviated object id.
+pub const MIN_ABBREV: usize = 4;
+
+#[derive(Debug)]
+pub enum RevisionError {
+    NotFound(String),
+    Ambiguous(String, Vec<store::ObjectId>),
+    NotACommit(String),
+    /// A malformed expression, or one that walks past the end of history.
+    Invalid(String),
+}
+
+impl fmt::Display for RevisionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RevisionError::NotFound(name) => write!(f, "unknown revision {}", name),
+            RevisionError::Ambiguous(prefix, candidates) => {
+                write!(
+                    f,
+                    "short object id {} is ambiguous, candidates are:",
+                    prefix
+                )?;
+                for id in candidates {
+                    write!(f, "\n  {}", id)?;
+                }
+                Ok(())
+            }
+            RevisionError::NotACommit(name) => write!(f, "{} is not a commit", name),
+            RevisionError::Invalid(name) => write!(f, "invalid revision {}", name),
+        }
+    }
+}
+
+fn is_abbreviated_id(store: &dyn store::ObjectStore, name: &str) -> bool {
+    name.len() >= MIN_ABBREV
+        && name.len() <= store.format().hex_len()
+        && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Accepts `HEAD` or `@`, branches and tags (`name`, `heads/name` or
+/// `refs/heads/name`), and ids or unique prefixes of them.
+pub fn resolve(
+    store: &dyn store::ObjectStore,
+    name: &str,
+) -> Result<store::ObjectId, RevisionError> {
+    resolve_as(store, name, false)
+}
+
+/// With `commit_only`, a prefix matching several objects only counts those
+/// that are or point to commits, as git disambiguates.
+fn resolve_as(
+    store: &dyn store::ObjectStore,
+    name: &str,
+    commit_only: bool,
+) -> Result<store::ObjectId, RevisionError> {
+    if name == "HEAD" || name == "@" {
+        return match branch::head_commit() {
+            Err(_) => Err(RevisionError::NotFound(String::from(name))),
+            Ok(hash) => match store::ObjectId::from_hex(&hash, store.format()) {
+                None => Err(RevisionError::NotFound(String::from(name))),
+                Some(id) => Ok(id),
+            },
+        };
+    }
+    if let Ok(refs) = branch::all_refs() {
+        let candidates = [
+            String::from(name),
+            String::from("refs/") + name,
+            String::from("refs/heads/") + name,
+            String::from("refs/tags/") + name,
+        ];
+        for candidate in &candidates {
+            if let Some((_, hash)) = refs.iter().find(|(ref_name, _)| ref_name == candidate) {
+                return match store::ObjectId::from_hex(hash, store.format()) {
+                    None => Err(RevisionError::NotFound(String::from(name))),
+                    Some(id) => Ok(id),
+                };
+            }
+        }
+    }
+    if !is_abbreviated_id(store, name) {
+        return Err(RevisionError::NotFound(String::from(name)));
+    }
+    let mut matches = match store.find_prefix(&name.to_lowercase()) {
+        Err(_) => return Err(RevisionError::NotFound(String::from(name))),
+        Ok(matches) => matches,
+    };
+    if commit_only && matches.len() > 1 {
+        let commits: Vec<store::ObjectId> = matches
+            .iter()
+            .filter(|id| peel(store, (*id).clone(), name).is_ok())
+            .cloned()
+            .collect();
+        if !commits.is_empty() {
+            matches = commits;
+        }
+    }
+    match matches.len() {
+        0 => Err(RevisionError::NotFound(String::from(name))),
+        1 => Ok(matches.remove(0)),
+        _ => Err(RevisionError::Ambiguous(String::from(name), matches)),
+    }
+}
+
+fn peel(
+    store: &dyn store::ObjectStore,
+    mut id: store::ObjectId,
+    name: &str,
+) -> Result<object::Commit, RevisionError> {
+    loop {
+        match store.read(&id) {
+            Ok(object::Object::Commit(commit)) => return Ok(commit),
+            Ok(object::Object::Tag(tag)) => id = tag.object,
+            Ok(_) => return Err(RevisionError::NotACommit(String::from(name))),
+            Err(_) => return Err(RevisionError::NotFound(String::from(name))),
+        }
+    }
+}
+
+/// The ref whose reflog `name@{...}` reads; a bare `@{...}` reads the
+/// current branch's, or HEAD's when it is detached.
+fn reflog_ref(name: &str) -> Result<String, RevisionError> {
+    if name.is_empty() {
+        return match branch::head_ref() {
+            Err(_) => Err(RevisionError::NotFound(String::from("HEAD"))),
+            Ok(head) => Ok(head.unwrap_or_else(|| String::from("HEAD"))),
+        };
+    }
+    let candidates = [
+        String::from(name),
+        String::from("refs/") + name,
+        String::from("refs/heads/") + name,
+    ];
+    match candidates
+        .iter()
+        .find(|candidate| reflog::exists(candidate))
+    {
+        None => Err(RevisionError::NotFound(String::from(name) + "@{}")),
+        Some(refname) => Ok(refname.clone()),
+    }
+}
+
+/// The branch (or commit, for a detached HEAD) checked out before the
+/// `n`th most recent checkout, as `@{-n}` names it.
+pub fn previous_checkout(n: usize) -> Option<String> {
+    let entries = reflog::read("HEAD").ok()?;
+    entries
+        .iter()
+        .rev()
+        .filter_map(|entry| entry.message.strip_prefix("checkout: moving from "))
+        .filter_map(|moved| moved.split_once(" to "))
+        .map(|(from, _)| String::from(from))
+        .nth(n.checked_sub(1)?)
+}
+
+fn resolve_reflog(
+    store: &dyn store::ObjectStore,
+    name: &str,
+    selector: &str,
+    expression: &str,
+) -> Result<store::ObjectId, RevisionError> {
+    let invalid = || RevisionError::Invalid(String::from(expression));
+    if let Some(n) = selector.strip_prefix('-') {
+        if !name.is_empty() {
+            return Err(invalid());
+        }
+        return match n.parse::<usize>().ok().and_then(previous_checkout) {
+            None => Err(invalid()),
+            Some(previous) => resolve(store, &previous),
+        };
+    }
+    let entries = match reflog::read(&reflog_ref(name)?) {
+        Err(_) => return Err(RevisionError::NotFound(String::from(expression))),
+        Ok(entries) => entries,
+    };
+    let hash = if let Ok(n) = selector.parse::<usize>() {
+        // `@{0}` is the latest update, `@{1}` the one before it
+        match entries.len().checked_sub(n + 1) {
+            None => return Err(invalid()),
+            Some(i) => entries[i].new.clone(),
+        }
+    } else {
+        let time = match log::parse_time(selector) {
+            None => return Err(invalid()),
+            Some(time) => time,
+        };
+        // the value at that time, or the oldest known one before the log starts
+        match entries
+            .iter()
+            .rev()
+            .find(|entry| entry.committer.time <= time)
+        {
+            Some(entry) => entry.new.clone(),
+            None => match entries.first() {
+                None => return Err(invalid()),
+                Some(entry) => entry.old.clone(),
+            },
+        }
+    };
+    match store::ObjectId::from_hex(&hash, store.format()) {
+        None => Err(invalid()),
+        Some(id) => Ok(id),
+    }
+}
+
+/// Carets and tildes inside `@{...}` belong to the name.
+fn split_suffix(expression: &str) -> (&str, &str) {
+    let mut depth = 0;
+    for (i, c) in expression.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '~' | '^' if depth == 0 => return (&expression[..i], &expression[i..]),
+            _ => {}
+        }
+    }
+    (expression, "")
+}
+
+/// The commit a single-commit expression names: anything `resolve` accepts,
+/// optionally followed by a reflog selector (`master@{1}`, `@{yesterday}`,
+/// `@{-1}`) and by any number of `~<n>` (n-th first-parent ancestor) and
+/// `^<n>` (n-th parent) steps.
+pub fn resolve_commit(
+    store: &dyn store::ObjectStore,
+    expression: &str,
+) -> Result<store::ObjectId, RevisionError> {
+    let invalid = || RevisionError::Invalid(String::from(expression));
+    let (base, mut suffix) = split_suffix(expression);
+    let mut id = match base.find("@{") {
+        Some(at) if base.ends_with('}') => resolve_reflog(
+            store,
+            &base[..at],
+            &base[at + 2..base.len() - 1],
+            expression,
+        )?,
+        _ if base.is_empty() => return Err(invalid()),
+        _ => resolve_as(store, base, true)?,
+    };
+    let mut commit = peel(store, id.clone(), expression)?;
+    while !suffix.is_empty() {
+        let step = suffix.as_bytes()[0];
+        if step != b'~' && step != b'^' {
+            return Err(invalid());
+        }
+        let digits = suffix[1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(suffix.len(), |end| end + 1);
+        let n = if digits == 1 {
+            1
+        } else {
+            match suffix[1..digits].parse::<usize>() {
+                Err(_) => return Err(invalid()),
+                Ok(n) => n,
+            }
+        };
+        suffix = &suffix[digits..];
+        let (parent, times) = match step {
+            // `^0` is the commit itself
+            b'^' if n == 0 => continue,
+            b'^' => (n - 1, 1),
+            _ => (0, n),
+        };
+        for _ in 0..times {
+            id = match commit.parents.get(parent) {
+                None => return Err(invalid()),
+                Some(parent) => parent.clone(),
+            };
+            commit = peel(store, id.clone(), expression)?;
+        }
+    }
+    Ok(id)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommitSet {
+    pub include: Vec<store::ObjectId>,
+    pub exclude: Vec<store::ObjectId>,
+}
+
+/// Resolves expressions such as `B`, `^A`, `A..B` (in B but not A) and
+/// `A...B` (in either but not both); an empty side of a range means HEAD.
+pub fn resolve_set(
+    store: &dyn store::ObjectStore,
+    expressions: &[String],
+) -> Result<CommitSet, RevisionError> {
+    let side = |name: &str| {
+        if name.is_empty() {
+            resolve_commit(store, "HEAD")
+        } else {
+            resolve_commit(store, name)
+        }
+    };
+    let mut set = CommitSet::default();
+    for expression in expressions {
+        if let Some(excluded) = expression.strip_prefix('^') {
+            set.exclude.push(resolve_commit(store, excluded)?);
+        } else if let Some((first, second)) = expression.split_once("...") {
+            let (first, second) = (side(first)?, side(second)?);
+            let mut graph = commit::CommitGraph::new(store);
+            match graph.merge_bases(first.as_str(), second.as_str()) {
+                Err(_) => return Err(RevisionError::NotFound(expression.clone())),
+                Ok(bases) => set.exclude.extend(
+                    bases
+                        .iter()
+                        .filter_map(|base| store::ObjectId::from_hex(base, store.format())),
+                ),
+            }
+            set.include.push(first);
+            set.include.push(second);
+        } else if let Some((first, second)) = expression.split_once("..") {
+            set.exclude.push(side(first)?);
+            set.include.push(side(second)?);
+        } else {
+            set.include.push(resolve_commit(store, expression)?);
+        }
+    }
+    Ok(set)
+}
+
+/// Length of the ids shown by `abbreviate`, unless more digits are needed.
+pub const DEFAULT_ABBREV: usize = 7;
+
+pub fn abbreviate(store: &dyn store::ObjectStore, id: &store::ObjectId) -> String {
+    let hex = id.as_str();
+    let mut len = DEFAULT_ABBREV.min(hex.len());
+    while len < hex.len() {
+        match store.find_prefix(&hex[..len]) {
+            Ok(matches) if matches.len() > 1 => len += 1,
+            _ => break,
+        }
+    }
+    String::from(&hex[..len])
+}
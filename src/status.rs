@@ -0,0 +1,250 @@
+use crate::ignore;
+use crate::index;
+use crate::store;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+
+#[derive(Debug)]
+pub enum StatusError {
+    IOError,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    fn code(self) -> char {
+        match self {
+            Change::Added => 'A',
+            Change::Modified => 'M',
+            Change::Deleted => 'D',
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Change::Added => "new file:",
+            Change::Modified => "modified:",
+            Change::Deleted => "deleted:",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct StatusEntry {
+    pub path: String,
+    pub staged: Option<Change>,
+    pub unstaged: Option<Change>,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Status {
+    /// the current branch; `None` when HEAD is detached
+    pub branch: Option<String>,
+    /// the commit HEAD points to, empty before the first commit
+    pub head: String,
+    pub entries: Vec<StatusEntry>,
+    pub untracked: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Format {
+    #[default]
+    Long,
+    Short,
+    /// The short format, kept stable for scripts.
+    Porcelain,
+}
+
+fn change(from: Option<&String>, to: Option<&String>) -> Option<Change> {
+    match (from, to) {
+        (None, Some(_)) => Some(Change::Added),
+        (Some(_), None) => Some(Change::Deleted),
+        (Some(from), Some(to)) if from != to => Some(Change::Modified),
+        _ => None,
+    }
+}
+
+pub fn compare(
+    head_map: &HashMap<String, String>,
+    index_map: &HashMap<String, String>,
+    worktree: &HashMap<String, String>,
+) -> (Vec<StatusEntry>, Vec<String>) {
+    let mut staged_map = head_map.clone();
+    index::apply(&mut staged_map, index_map.clone());
+    let paths: BTreeSet<&String> = head_map.keys().chain(staged_map.keys()).collect();
+    let entries = paths
+        .into_iter()
+        .filter_map(|path| {
+            let staged = change(head_map.get(path), staged_map.get(path));
+            let unstaged = match staged_map.get(path) {
+                None => None,
+                Some(_) => change(staged_map.get(path), worktree.get(path)),
+            };
+            if staged.is_none() && unstaged.is_none() {
+                return None;
+            }
+            Some(StatusEntry {
+                path: path.clone(),
+                staged,
+                unstaged,
+            })
+        })
+        .collect();
+    let mut untracked: Vec<String> = worktree
+        .keys()
+        .filter(|path| !staged_map.contains_key(*path))
+        .cloned()
+        .collect();
+    untracked.sort();
+    (entries, untracked)
+}
+
+/// `root` is empty for the whole working tree or a directory ending with `/`.
+/// Ignored directories are only entered when they hold a tracked file.
+pub fn worktree_files(
+    root: &str,
+    rules: &mut ignore::IgnoreRules,
+    tracked: &HashMap<String, String>,
+) -> Result<Vec<String>, StatusError> {
+    let mut files = Vec::new();
+    let mut dirs = vec![String::from(root)];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(if dir.is_empty() { "." } else { &dir }) {
+            Err(_) => return Err(StatusError::IOError),
+            Ok(entries) => entries,
+        };
+        for entry in entries.flatten() {
+            let name = dir.clone() + &entry.file_name().to_string_lossy();
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => {
+                    let prefix = name.clone() + "/";
+                    if name != ".yit"
+                        && (!rules.is_ignored(&name, true)
+                            || tracked.keys().any(|path| path.starts_with(&prefix)))
+                    {
+                        dirs.push(prefix);
+                    }
+                }
+                Ok(_) => {
+                    if tracked.contains_key(&name) || !rules.is_ignored(&name, false) {
+                        files.push(name);
+                    }
+                }
+                Err(_) => return Err(StatusError::IOError),
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Files whose stat data in `index_obj` is unchanged aren't read again.
+pub fn hash_worktree(
+    store: &dyn store::ObjectStore,
+    tracked: &HashMap<String, String>,
+    index_obj: &mut index::Index,
+) -> Result<HashMap<String, String>, StatusError> {
+    let mut rules = ignore::IgnoreRules::load();
+    let mut hashes = HashMap::new();
+    for path in worktree_files("", &mut rules, tracked)? {
+        match index_obj.hash_file(store, &path, false) {
+            Err(_) => return Err(StatusError::IOError),
+            Ok(hash) => hashes.insert(path, hash),
+        };
+    }
+    index_obj.retain_stats(|path| hashes.contains_key(path));
+    Ok(hashes)
+}
+
+fn format_long(status: &Status) -> String {
+    let mut output = match &status.branch {
+        Some(branch) => format!("On branch {}\n", branch),
+        None => format!(
+            "HEAD detached at {}\n",
+            &status.head[..status.head.len().min(7)]
+        ),
+    };
+    if status.head.is_empty() {
+        output.push_str("\nNo commits yet\n");
+    }
+    let staged: Vec<String> = status
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let change = entry.staged?;
+            Some(format!("\t{:<12}{}\n", change.label(), entry.path))
+        })
+        .collect();
+    let unstaged: Vec<String> = status
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let change = entry.unstaged?;
+            Some(format!("\t{:<12}{}\n", change.label(), entry.path))
+        })
+        .collect();
+    let untracked: Vec<String> = status
+        .untracked
+        .iter()
+        .map(|path| format!("\t{}\n", path))
+        .collect();
+    for (title, lines) in [
+        ("Changes to be committed:", staged),
+        ("Changes not staged for commit:", unstaged),
+        ("Untracked files:", untracked),
+    ] {
+        if !lines.is_empty() {
+            output.push('\n');
+            output.push_str(title);
+            output.push('\n');
+            output.extend(lines);
+        }
+    }
+    output.push('\n');
+    if status.entries.iter().any(|entry| entry.staged.is_some()) {
+        return output;
+    }
+    if !status.entries.is_empty() {
+        output.push_str("no changes added to commit\n");
+    } else if !status.untracked.is_empty() {
+        output.push_str("nothing added to commit but untracked files present\n");
+    } else {
+        output.push_str("nothing to commit, working tree clean\n");
+    }
+    output
+}
+
+pub fn format_status(status: &Status, format: Format, show_branch: bool) -> String {
+    if format == Format::Long {
+        return format_long(status);
+    }
+    let mut output = String::new();
+    if show_branch {
+        match &status.branch {
+            Some(branch) if status.head.is_empty() => {
+                output.push_str(&format!("## No commits yet on {}\n", branch))
+            }
+            Some(branch) => output.push_str(&format!("## {}\n", branch)),
+            None => output.push_str("## HEAD (no branch)\n"),
+        }
+    }
+    let code = |change: Option<Change>| change.map_or(' ', |change| change.code());
+    for entry in &status.entries {
+        output.push(code(entry.staged));
+        output.push(code(entry.unstaged));
+        output.push(' ');
+        output.push_str(&entry.path);
+        output.push('\n');
+    }
+    for path in &status.untracked {
+        output.push_str("?? ");
+        output.push_str(path);
+        output.push('\n');
+    }
+    output
+}
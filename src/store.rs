@@ -0,0 +1,715 @@
+use crate::config;
+use crate::encoding;
+use crate::gc;
+use crate::object;
+use crate::pack;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug)]
+pub enum StoreError {
+    IOError,
+    NotFound,
+    Corrupt(object::ObjectError),
+    Unsupported,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct ObjectId(String);
+
+impl ObjectId {
+    /// `hex` must have the length of an id in `format`.
+    pub fn from_hex(hex: &str, format: encoding::ObjectFormat) -> Option<Self> {
+        if hex.len() != format.hex_len() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(ObjectId(hex.to_lowercase()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn dir(&self) -> &str {
+        &self.0[0..2]
+    }
+
+    pub fn file(&self) -> &str {
+        &self.0[2..]
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl ObjectKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ObjectKind::Blob => "blob",
+            ObjectKind::Tree => "tree",
+            ObjectKind::Commit => "commit",
+            ObjectKind::Tag => "tag",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "blob" => Some(ObjectKind::Blob),
+            "tree" => Some(ObjectKind::Tree),
+            "commit" => Some(ObjectKind::Commit),
+            "tag" => Some(ObjectKind::Tag),
+            _ => None,
+        }
+    }
+}
+
+fn for_each_chunk(
+    reader: &mut dyn Read,
+    len: u64,
+    mut f: impl FnMut(&[u8]) -> Result<(), StoreError>,
+) -> Result<(), StoreError> {
+    let mut buffer = vec![0; 64 * 1024];
+    let mut remaining = len;
+    while remaining > 0 {
+        let wanted = remaining.min(buffer.len() as u64) as usize;
+        match reader.read(&mut buffer[..wanted]) {
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            // the content is shorter than announced
+            Err(_) | Ok(0) => return Err(StoreError::IOError),
+            Ok(read) => {
+                f(&buffer[..read])?;
+                remaining -= read as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn hash_reader(
+    format: encoding::ObjectFormat,
+    encoding: encoding::Encoding,
+    kind: ObjectKind,
+    len: u64,
+    reader: &mut dyn Read,
+) -> Result<ObjectId, StoreError> {
+    let mut hasher = format.hasher();
+    hasher.input(&encoding.hash_header(kind.as_str(), len));
+    for_each_chunk(reader, len, |chunk| {
+        hasher.input(chunk);
+        Ok(())
+    })?;
+    Ok(ObjectId(hasher.result_str()))
+}
+
+pub trait ObjectStore: Send + Sync {
+    fn encoding(&self) -> encoding::Encoding;
+
+    fn format(&self) -> encoding::ObjectFormat;
+
+    fn compression(&self) -> u8 {
+        encoding::DEFAULT_COMPRESSION
+    }
+
+    /// Storing an existing object is a no-op.
+    fn put(&self, kind: ObjectKind, content: &[u8]) -> Result<ObjectId, StoreError>;
+
+    fn get(&self, id: &ObjectId) -> Result<(ObjectKind, Vec<u8>), StoreError>;
+
+    fn get_encoded(
+        &self,
+        id: &ObjectId,
+    ) -> Result<(ObjectKind, encoding::Encoding, Vec<u8>), StoreError> {
+        let (kind, content) = self.get(id)?;
+        Ok((kind, self.encoding(), content))
+    }
+
+    fn exists(&self, id: &ObjectId) -> bool;
+
+    fn list(&self) -> Result<Vec<ObjectId>, StoreError>;
+
+    /// Ids starting with the lowercase hex `prefix`.
+    fn find_prefix(&self, prefix: &str) -> Result<Vec<ObjectId>, StoreError> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|id| id.as_str().starts_with(prefix))
+            .collect())
+    }
+
+    fn read(&self, id: &ObjectId) -> Result<object::Object, StoreError> {
+        let (kind, content) = self.get(id)?;
+        match object::Object::parse(kind, &content, self.encoding(), self.format()) {
+            Err(err) => Err(StoreError::Corrupt(err)),
+            Ok(object) => Ok(object),
+        }
+    }
+
+    fn write(&self, object: &object::Object) -> Result<ObjectId, StoreError> {
+        self.put(object.kind(), &object.serialize(self.encoding()))
+    }
+
+    fn hash(&self, kind: ObjectKind, content: &[u8]) -> ObjectId {
+        let mut hasher = self.format().hasher();
+        hasher.input(&self.encoding().hash_input(kind.as_str(), content));
+        ObjectId(hasher.result_str())
+    }
+
+    fn hash_stream(
+        &self,
+        kind: ObjectKind,
+        len: u64,
+        reader: &mut dyn Read,
+    ) -> Result<ObjectId, StoreError> {
+        hash_reader(self.format(), self.encoding(), kind, len, reader)
+    }
+
+    fn put_stream(
+        &self,
+        kind: ObjectKind,
+        len: u64,
+        reader: &mut dyn Read,
+    ) -> Result<ObjectId, StoreError> {
+        let mut content = Vec::new();
+        for_each_chunk(reader, len, |chunk| {
+            content.extend(chunk);
+            Ok(())
+        })?;
+        self.put(kind, &content)
+    }
+
+    /// The path of each object groups similar blobs in the pack.
+    fn write_pack(&self, objects: &[(ObjectId, String)]) -> Result<Vec<String>, StoreError> {
+        let _ = objects;
+        Err(StoreError::Unsupported)
+    }
+
+    /// Packs `reachable`, then deletes the loose objects that got packed or
+    /// are unreachable and older than `expire`, and packs made redundant.
+    /// Returns how many unreachable objects were deleted.
+    fn prune(
+        &self,
+        reachable: &[(ObjectId, String)],
+        expire: Option<Duration>,
+    ) -> Result<usize, StoreError> {
+        let _ = (reachable, expire);
+        Err(StoreError::Unsupported)
+    }
+
+    fn stream<'a>(&'a self, id: &ObjectId) -> Result<(ObjectKind, Box<dyn Read + 'a>), StoreError> {
+        let (kind, content) = self.get(id)?;
+        Ok((kind, Box::new(io::Cursor::new(content))))
+    }
+
+    fn stream_encoded<'a>(
+        &'a self,
+        id: &ObjectId,
+    ) -> Result<(ObjectKind, encoding::Encoding, Box<dyn Read + 'a>), StoreError> {
+        let (kind, encoding, content) = self.get_encoded(id)?;
+        Ok((kind, encoding, Box::new(io::Cursor::new(content))))
+    }
+}
+
+/// One zlib compressed file per object under `<root>/xx/yyyy`, falling back
+/// to the packs in `<root>/pack` for objects that are not stored loose.
+pub struct LooseStore {
+    root: String,
+    encoding: encoding::Encoding,
+    format: encoding::ObjectFormat,
+    compression: u8,
+    packs: Mutex<(Vec<String>, Vec<pack::Pack>)>,
+}
+
+impl LooseStore {
+    pub fn new(root: String, encoding: encoding::Encoding) -> Self {
+        LooseStore {
+            root,
+            encoding,
+            format: encoding::ObjectFormat::Sha1,
+            compression: encoding::DEFAULT_COMPRESSION,
+            packs: Mutex::new((Vec::new(), Vec::new())),
+        }
+    }
+
+    pub fn with_format(mut self, format: encoding::ObjectFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_compression(mut self, level: u8) -> Self {
+        self.compression = level;
+        self
+    }
+
+    pub fn open() -> Self {
+        let config = config::Config::current();
+        LooseStore::new(
+            String::from(".yit/objects"),
+            encoding::Encoding::from_config(&config),
+        )
+        .with_format(encoding::ObjectFormat::from_config(&config))
+        .with_compression(encoding::compression_level(&config))
+    }
+
+    fn object_path(&self, id: &ObjectId) -> String {
+        self.root.clone() + "/" + id.dir() + "/" + id.file()
+    }
+
+    /// Runs `f` on the current packs, reloading them if the pack directory changed.
+    fn with_packs<T>(&self, f: impl FnOnce(&[pack::Pack]) -> T) -> T {
+        let paths = pack::pack_index_paths(&self.root);
+        match self.packs.lock() {
+            Err(_) => f(&[]),
+            Ok(mut packs) => {
+                if packs.0 != paths {
+                    let loaded = paths
+                        .iter()
+                        .filter_map(|path| pack::Pack::open(path, self.format).ok())
+                        .collect();
+                    *packs = (paths, loaded);
+                }
+                f(&packs.1)
+            }
+        }
+    }
+
+    fn get_packed(
+        &self,
+        id: &ObjectId,
+    ) -> Result<(ObjectKind, encoding::Encoding, Vec<u8>), StoreError> {
+        self.with_packs(|packs| {
+            for pack in packs {
+                match pack.get(id) {
+                    Err(pack::PackError::NotFound) => continue,
+                    Err(pack::PackError::IOError) => return Err(StoreError::IOError),
+                    Err(pack::PackError::Corrupt) => {
+                        return Err(StoreError::Corrupt(object::ObjectError::InvalidPackEntry))
+                    }
+                    Ok((kind, content)) => {
+                        let encoding = pack.encoding().unwrap_or(self.encoding);
+                        return Ok((kind, encoding, content));
+                    }
+                }
+            }
+            Err(StoreError::NotFound)
+        })
+    }
+
+    /// Bumps the mtime of the loose file or pack holding `id`, so gc counts an
+    /// object stored again as new. False if there is no such file to touch.
+    fn freshen(&self, id: &ObjectId) -> bool {
+        let touch = |path: &str| match fs::OpenOptions::new().append(true).open(path) {
+            Err(_) => false,
+            Ok(file) => file.set_modified(SystemTime::now()).is_ok(),
+        };
+        let path = self.object_path(id);
+        if Path::new(&path).exists() {
+            return touch(&path);
+        }
+        self.with_packs(|packs| {
+            packs
+                .iter()
+                .any(|pack| pack.contains(id) && touch(pack.pack_path()))
+        })
+    }
+
+    fn temp_path(&self) -> String {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        format!(
+            "{}/tmp_obj_{}_{}",
+            self.root,
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        )
+    }
+
+    fn write_temp(
+        &self,
+        temp_path: &str,
+        kind: ObjectKind,
+        len: u64,
+        reader: &mut dyn Read,
+    ) -> Result<ObjectId, StoreError> {
+        let file = match File::create(temp_path) {
+            Err(_) => return Err(StoreError::IOError),
+            Ok(file) => file,
+        };
+        let mut deflater = encoding::Deflater::new(io::BufWriter::new(file), self.compression);
+        let mut hasher = self.format.hasher();
+        hasher.input(&self.encoding.hash_header(kind.as_str(), len));
+        if deflater
+            .write_all(&self.encoding.header(kind.as_str(), len))
+            .is_err()
+        {
+            return Err(StoreError::IOError);
+        }
+        for_each_chunk(reader, len, |chunk| {
+            hasher.input(chunk);
+            match deflater.write_all(chunk) {
+                Err(_) => Err(StoreError::IOError),
+                Ok(_) => Ok(()),
+            }
+        })?;
+        match deflater.finish() {
+            Err(_) => Err(StoreError::IOError),
+            Ok(_) => Ok(ObjectId(hasher.result_str())),
+        }
+    }
+
+    /// Reads the `"<type>\n"` or `"<type> <len>\0"` header off a loose object.
+    fn read_header(reader: &mut dyn Read) -> Result<(ObjectKind, encoding::Encoding), StoreError> {
+        let mut header = Vec::new();
+        let mut byte = [0; 1];
+        let encoding = loop {
+            match reader.read(&mut byte) {
+                Err(_) => return Err(StoreError::IOError),
+                Ok(0) => return Err(StoreError::Corrupt(object::ObjectError::MalformedHeader)),
+                Ok(_) => {
+                    if byte[0] == b'\n' {
+                        break encoding::Encoding::Yit;
+                    }
+                    if byte[0] == 0 {
+                        break encoding::Encoding::Git;
+                    }
+                    header.push(byte[0]);
+                    if header.len() > 32 {
+                        return Err(StoreError::Corrupt(object::ObjectError::MalformedHeader));
+                    }
+                }
+            }
+        };
+        let header = String::from_utf8_lossy(&header).to_string();
+        let kind_name = header.split(' ').next().unwrap_or("");
+        match ObjectKind::parse(kind_name) {
+            None => Err(StoreError::Corrupt(object::ObjectError::UnknownType(
+                String::from(kind_name),
+            ))),
+            Some(kind) => Ok((kind, encoding)),
+        }
+    }
+
+    fn load(&self, id: &ObjectId) -> Result<Vec<u8>, StoreError> {
+        match fs::read(self.object_path(id)) {
+            Err(_) => Err(StoreError::NotFound),
+            Ok(buffer) => match encoding::decompress(&buffer) {
+                None => Err(StoreError::Corrupt(object::ObjectError::InvalidCompression)),
+                Some(decompressed) => Ok(decompressed),
+            },
+        }
+    }
+}
+
+impl ObjectStore for LooseStore {
+    fn encoding(&self) -> encoding::Encoding {
+        self.encoding
+    }
+
+    fn write_pack(&self, objects: &[(ObjectId, String)]) -> Result<Vec<String>, StoreError> {
+        match pack::write_pack(self, objects, &(self.root.clone() + "/pack")) {
+            Err(_) => Err(StoreError::IOError),
+            Ok(names) => Ok(names),
+        }
+    }
+
+    fn prune(
+        &self,
+        reachable: &[(ObjectId, String)],
+        expire: Option<Duration>,
+    ) -> Result<usize, StoreError> {
+        match gc::prune_loose(self, &self.root, reachable, expire) {
+            Err(_) => Err(StoreError::IOError),
+            Ok(pruned) => Ok(pruned),
+        }
+    }
+
+    fn format(&self) -> encoding::ObjectFormat {
+        self.format
+    }
+
+    fn compression(&self) -> u8 {
+        self.compression
+    }
+
+    fn put(&self, kind: ObjectKind, content: &[u8]) -> Result<ObjectId, StoreError> {
+        if !Path::new(&self.root).exists() {
+            return Err(StoreError::IOError);
+        }
+        let id = self.hash(kind, content);
+        if self.freshen(&id) {
+            return Ok(id);
+        }
+        let compressed = encoding::compress(
+            &self.encoding.encode(kind.as_str(), content),
+            self.compression,
+        );
+        // renamed into place so a crash never leaves a truncated object behind
+        let temp_path = self.temp_path();
+        if fs::write(&temp_path, compressed).is_err()
+            || fs::create_dir_all(self.root.clone() + "/" + id.dir()).is_err()
+            || fs::rename(&temp_path, self.object_path(&id)).is_err()
+        {
+            let _ = fs::remove_file(&temp_path);
+            return Err(StoreError::IOError);
+        }
+        Ok(id)
+    }
+
+    fn get(&self, id: &ObjectId) -> Result<(ObjectKind, Vec<u8>), StoreError> {
+        let (kind, _, content) = self.get_encoded(id)?;
+        Ok((kind, content))
+    }
+
+    fn get_encoded(
+        &self,
+        id: &ObjectId,
+    ) -> Result<(ObjectKind, encoding::Encoding, Vec<u8>), StoreError> {
+        let data = match self.load(id) {
+            Err(StoreError::NotFound) => return self.get_packed(id),
+            Err(err) => return Err(err),
+            Ok(data) => data,
+        };
+        match object::split_header(&data) {
+            Err(err) => Err(StoreError::Corrupt(err)),
+            Ok((kind, encoding, content)) => Ok((kind, encoding, content.to_vec())),
+        }
+    }
+
+    /// Parses tree and commit bodies according to the object's own header, or
+    /// its pack's, so objects written with the other encoding remain readable.
+    fn read(&self, id: &ObjectId) -> Result<object::Object, StoreError> {
+        let data = match self.load(id) {
+            Err(StoreError::NotFound) => {
+                let (kind, encoding, content) = self.get_packed(id)?;
+                return match object::Object::parse(kind, &content, encoding, self.format) {
+                    Err(err) => Err(StoreError::Corrupt(err)),
+                    Ok(object) => Ok(object),
+                };
+            }
+            Err(err) => return Err(err),
+            Ok(data) => data,
+        };
+        match object::Object::decode(&data, self.format) {
+            Err(err) => Err(StoreError::Corrupt(err)),
+            Ok(object) => Ok(object),
+        }
+    }
+
+    /// Hashes and compresses in a single pass, so the content is never held in
+    /// memory as a whole.
+    fn put_stream(
+        &self,
+        kind: ObjectKind,
+        len: u64,
+        reader: &mut dyn Read,
+    ) -> Result<ObjectId, StoreError> {
+        if !Path::new(&self.root).exists() {
+            return Err(StoreError::IOError);
+        }
+        let temp_path = self.temp_path();
+        let id = match self.write_temp(&temp_path, kind, len, reader) {
+            Err(err) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(err);
+            }
+            Ok(id) => id,
+        };
+        if self.freshen(&id) {
+            let _ = fs::remove_file(&temp_path);
+            return Ok(id);
+        }
+        if fs::create_dir_all(self.root.clone() + "/" + id.dir()).is_err()
+            || fs::rename(&temp_path, self.object_path(&id)).is_err()
+        {
+            let _ = fs::remove_file(&temp_path);
+            return Err(StoreError::IOError);
+        }
+        Ok(id)
+    }
+
+    fn stream<'a>(&'a self, id: &ObjectId) -> Result<(ObjectKind, Box<dyn Read + 'a>), StoreError> {
+        let (kind, _, reader) = self.stream_encoded(id)?;
+        Ok((kind, reader))
+    }
+
+    fn stream_encoded<'a>(
+        &'a self,
+        id: &ObjectId,
+    ) -> Result<(ObjectKind, encoding::Encoding, Box<dyn Read + 'a>), StoreError> {
+        let file = match File::open(self.object_path(id)) {
+            Err(_) => {
+                let (kind, encoding, content) = self.get_packed(id)?;
+                return Ok((kind, encoding, Box::new(io::Cursor::new(content))));
+            }
+            Ok(file) => file,
+        };
+        let mut reader = match encoding::Inflater::new(io::BufReader::new(file)) {
+            Err(_) => return Err(StoreError::IOError),
+            Ok(reader) => reader,
+        };
+        let (kind, encoding) = LooseStore::read_header(&mut reader)?;
+        Ok((kind, encoding, Box::new(reader)))
+    }
+
+    fn exists(&self, id: &ObjectId) -> bool {
+        Path::new(&self.object_path(id)).exists()
+            || self.with_packs(|packs| packs.iter().any(|pack| pack.contains(id)))
+    }
+
+    fn list(&self) -> Result<Vec<ObjectId>, StoreError> {
+        let mut ids = Vec::new();
+        let dirs = match fs::read_dir(&self.root) {
+            Err(_) => return Err(StoreError::IOError),
+            Ok(dirs) => dirs,
+        };
+        for dir in dirs.flatten() {
+            let dir_name = dir.file_name().to_string_lossy().to_string();
+            if dir_name.len() != 2 {
+                continue;
+            }
+            if let Ok(files) = fs::read_dir(dir.path()) {
+                for file in files.flatten() {
+                    let file_name = file.file_name().to_string_lossy().to_string();
+                    if let Some(id) =
+                        ObjectId::from_hex(&(dir_name.clone() + &file_name), self.format)
+                    {
+                        ids.push(id);
+                    }
+                }
+            }
+        }
+        self.with_packs(|packs| {
+            for pack in packs {
+                ids.extend(pack.ids().iter().cloned());
+            }
+        });
+        ids.sort();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    fn find_prefix(&self, prefix: &str) -> Result<Vec<ObjectId>, StoreError> {
+        if prefix.len() < 2 {
+            return Ok(self
+                .list()?
+                .into_iter()
+                .filter(|id| id.as_str().starts_with(prefix))
+                .collect());
+        }
+        let mut ids = Vec::new();
+        if let Ok(files) = fs::read_dir(self.root.clone() + "/" + &prefix[..2]) {
+            for file in files.flatten() {
+                let file_name = file.file_name().to_string_lossy().to_string();
+                if !file_name.starts_with(&prefix[2..]) {
+                    continue;
+                }
+                if let Some(id) =
+                    ObjectId::from_hex(&(String::from(&prefix[..2]) + &file_name), self.format)
+                {
+                    ids.push(id);
+                }
+            }
+        }
+        self.with_packs(|packs| {
+            for pack in packs {
+                ids.extend(
+                    pack.ids()
+                        .iter()
+                        .filter(|id| id.as_str().starts_with(prefix))
+                        .cloned(),
+                );
+            }
+        });
+        ids.sort();
+        ids.dedup();
+        Ok(ids)
+    }
+}
+
+pub struct MemoryStore {
+    objects: Mutex<HashMap<ObjectId, (ObjectKind, Vec<u8>)>>,
+    encoding: encoding::Encoding,
+    format: encoding::ObjectFormat,
+}
+
+impl MemoryStore {
+    pub fn new(encoding: encoding::Encoding) -> Self {
+        MemoryStore {
+            objects: Mutex::new(HashMap::new()),
+            encoding,
+            format: encoding::ObjectFormat::Sha1,
+        }
+    }
+
+    pub fn with_format(mut self, format: encoding::ObjectFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl ObjectStore for MemoryStore {
+    fn encoding(&self) -> encoding::Encoding {
+        self.encoding
+    }
+
+    fn format(&self) -> encoding::ObjectFormat {
+        self.format
+    }
+
+    fn put(&self, kind: ObjectKind, content: &[u8]) -> Result<ObjectId, StoreError> {
+        let id = self.hash(kind, content);
+        match self.objects.lock() {
+            Err(_) => Err(StoreError::IOError),
+            Ok(mut objects) => {
+                objects.insert(id.clone(), (kind, content.to_vec()));
+                Ok(id)
+            }
+        }
+    }
+
+    fn get(&self, id: &ObjectId) -> Result<(ObjectKind, Vec<u8>), StoreError> {
+        match self.objects.lock() {
+            Err(_) => Err(StoreError::IOError),
+            Ok(objects) => match objects.get(id) {
+                None => Err(StoreError::NotFound),
+                Some(object) => Ok(object.clone()),
+            },
+        }
+    }
+
+    fn exists(&self, id: &ObjectId) -> bool {
+        match self.objects.lock() {
+            Err(_) => false,
+            Ok(objects) => objects.contains_key(id),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<ObjectId>, StoreError> {
+        match self.objects.lock() {
+            Err(_) => Err(StoreError::IOError),
+            Ok(objects) => {
+                let mut ids: Vec<ObjectId> = objects.keys().cloned().collect();
+                ids.sort();
+                Ok(ids)
+            }
+        }
+    }
+}
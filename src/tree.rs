@@ -1,115 +1,141 @@
-use crate::blob;
+use crate::encoding;
 use crate::file;
-use crypto::digest::Digest;
-use crypto::sha1::Sha1;
-use miniz_oxide::deflate::compress_to_vec;
+use crate::object;
+use crate::store;
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
-use std::io::prelude::*;
+use std::path::Path;
 
 #[derive(Clone)]
 pub struct Tree {
     pub subtrees: Vec<Tree>,
-    pub blobs: Vec<blob::Blob>,
+    /// (full path, blob hash) of every file directly inside this tree
+    pub blobs: Vec<(String, String)>,
     pub name: String,
 }
 
-pub enum TreeError {
-    IOError,
-}
-
 impl Tree {
+    /// Splits the first component off a path, leaving the rest with its
+    /// leading `/`.
     fn get_word(str: String) -> Option<(String, String)> {
-        let mut word: String = String::from("");
-        let mut rest = str.clone();
-        if str.clone().is_empty() {
+        if str.is_empty() {
             return None;
         }
-        if !rest.is_empty() && rest.chars().nth(0).unwrap() == '/' {
-            rest = rest[1..].to_string();
+        let path = str.strip_prefix('/').unwrap_or(&str);
+        match path.find('/') {
+            None => Some((String::from(path), String::new())),
+            Some(pos) => Some((String::from(&path[..pos]), String::from(&path[pos..]))),
         }
-        while !rest.is_empty() && rest.chars().nth(0).unwrap() != '/' {
-            word = (word.to_owned() + &rest.chars().nth(0).unwrap().to_string()).to_string();
-            rest = rest[1..].to_string();
-        }
-        return Some((word.clone(), rest));
     }
 
-    pub fn load_index_map(index_map: HashMap<String, String>) {
+    /// Returns the files written; blobs missing from `store` are skipped.
+    pub fn load_index_map(
+        store: &dyn store::ObjectStore,
+        index_map: HashMap<String, String>,
+    ) -> Vec<(String, String)> {
+        let mut written = Vec::new();
         for (key, val) in index_map {
-            match file::cat_file(val) {
-                Err(_) => (),
-                Ok(file_content) => match File::create(key) {
-                    Err(_) => (),
-                    Ok(mut file) => match file.write_all(file_content.as_bytes()) {
-                        Err(_) => (),
-                        Ok(_) => (),
-                    },
-                },
+            let stored =
+                store::ObjectId::from_hex(&val, store.format()).is_some_and(|id| store.exists(&id));
+            if !stored {
+                continue;
+            }
+            if let Some(parent) = Path::new(&key).parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            if let Ok(mut file) = File::create(&key) {
+                if file::cat_file_to(store, val.clone(), &mut file).is_ok() {
+                    written.push((key, val));
+                }
             }
         }
+        written
+    }
+
+    pub fn add_blob(&mut self, path: String, hash: String) {
+        self.insert_blob(path.clone(), path, hash);
     }
 
-    pub fn add_blob(&mut self, path: String, full_path: String) {
-        let rest_path = path.clone();
-        let res = Tree::get_word(rest_path);
-        match res {
+    fn insert_blob(&mut self, path: String, full_path: String, hash: String) {
+        match Tree::get_word(path) {
             None => (),
             Some((word, rest)) => {
-                let mut subtree_option = self
-                    .subtrees
-                    .iter_mut()
-                    .filter(|tree| (**tree).name == word);
-                match subtree_option.next() {
+                if rest.is_empty() {
+                    self.blobs.push((full_path, hash));
+                    return;
+                }
+                match self.subtrees.iter_mut().find(|tree| tree.name == word) {
                     None => {
-                        if rest.is_empty() {
-                            let blob = blob::Blob::new(full_path.clone());
-                            self.blobs.push(blob.clone());
-                        } else {
-                            let mut subtree = Tree {
-                                subtrees: Vec::new(),
-                                blobs: Vec::new(),
-                                name: String::from(word),
-                            };
-                            subtree.add_blob(rest, full_path);
-                            self.subtrees.push(subtree);
-                        }
-                    }
-                    Some(subtree_iter) => {
-                        if rest.is_empty() {
-                            let blob = blob::Blob::new(path.clone());
-                            subtree_iter.blobs.push(blob.clone());
-                        } else {
-                            subtree_iter.add_blob(rest, full_path);
-                        }
+                        let mut subtree = Tree {
+                            subtrees: Vec::new(),
+                            blobs: Vec::new(),
+                            name: word,
+                        };
+                        subtree.insert_blob(rest, full_path, hash);
+                        self.subtrees.push(subtree);
                     }
+                    Some(subtree) => subtree.insert_blob(rest, full_path, hash),
                 }
             }
         }
     }
-    
-    pub fn tree_to_index_map(hash: String) -> HashMap<String, String> {
-        let file_content_res = file::cat_file(hash);
+
+    pub fn tree_to_index_map(
+        store: &dyn store::ObjectStore,
+        hash: String,
+    ) -> HashMap<String, String> {
+        Tree::tree_to_index_map_with_prefix(store, hash, String::from(""))
+    }
+
+    /// Git tree entries only hold the base name, so the path of the enclosing
+    /// tree is passed down as `prefix`. Yit trees store full paths for blobs.
+    fn tree_to_index_map_with_prefix(
+        store: &dyn store::ObjectStore,
+        hash: String,
+        prefix: String,
+    ) -> HashMap<String, String> {
         let mut index_map: HashMap<String, String> = HashMap::new();
-        match file_content_res {
-            Err(_) => index_map,
-            Ok(file_content) => {
-                let lines: Vec<&str> = file_content.split('\n').collect();
-                for line in lines {
-                    if line == "" {
-                        break;
-                    }
-                    let words: Vec<&str> = line.split(" ").collect();
-                    if words[0] == "tree" {
-                        index_map.extend(Tree::tree_to_index_map(String::from(words[2])));
-                    } else {
-                        index_map.insert(String::from(words[1]), String::from(words[2]));
-                    }
-                }
-                index_map
+        let entries = match store::ObjectId::from_hex(&hash, store.format()) {
+            None => return index_map,
+            Some(id) => match store.read(&id) {
+                Ok(object::Object::Tree(entries)) => entries,
+                _ => return index_map,
+            },
+        };
+        for entry in entries {
+            let is_tree = entry.kind() == store::ObjectKind::Tree;
+            let path = if !is_tree && store.encoding() == encoding::Encoding::Yit {
+                entry.name
+            } else {
+                prefix.clone() + &entry.name
+            };
+            if !Tree::is_safe_path(&path) {
+                continue;
+            }
+            if is_tree {
+                index_map.extend(Tree::tree_to_index_map_with_prefix(
+                    store,
+                    entry.id.to_string(),
+                    path + "/",
+                ));
+            } else {
+                index_map.insert(path, entry.id.to_string());
             }
         }
+        index_map
+    }
+
+    /// Paths from a tree end up written below the worktree on checkout, so
+    /// absolute paths and `.`, `..` or `.yit` components are refused.
+    fn is_safe_path(path: &str) -> bool {
+        !path.starts_with('/')
+            && path.split('/').all(|part| {
+                !part.is_empty()
+                    && part != "."
+                    && part != ".."
+                    && !part.eq_ignore_ascii_case(".yit")
+            })
     }
 
     fn index_map_to_tree(index_map: HashMap<String, String>) -> Self {
@@ -118,65 +144,45 @@ impl Tree {
             blobs: Vec::new(),
             name: String::from("/"),
         };
-        for (key, _) in index_map {
-            tree.add_blob(key.clone(), key);
+        for (key, val) in index_map {
+            tree.add_blob(key, val);
         }
-        return tree;
+        tree
     }
 
-    pub fn hash_tree(self) -> String {
-        let mut content = String::from("");
-        //trees
-        for tree in self.clone().subtrees {
-            let hash = tree.clone().hash_tree();
-            content.push_str("tree ");
-            content.push_str(&tree.clone().name);
-            content.push_str(" ");
-            content.push_str(&hash);
-            content.push_str("\n");
+    pub fn hash_tree(self, store: &dyn store::ObjectStore) -> String {
+        let mut entries: Vec<object::TreeEntry> = Vec::new();
+        for tree in self.subtrees {
+            let name = tree.name.clone();
+            if let Some(id) = store::ObjectId::from_hex(&tree.hash_tree(store), store.format()) {
+                entries.push(object::TreeEntry {
+                    mode: object::MODE_TREE,
+                    name,
+                    id,
+                });
+            }
         }
-
-        //blobs
-        for blob in self.clone().blobs {
-            let hash_res = blob.clone().hash_object(false);
-            match hash_res {
-                Err(_) => (),
-                Ok(hash) => {
-                    content.push_str("blob ");
-                    content.push_str(&blob.clone().file_path);
-                    content.push_str(" ");
-                    content.push_str(&hash);
-                    content.push_str("\n");
-                }
+        for (path, hash) in self.blobs {
+            // git tree entries only hold the base name
+            let name = match store.encoding() {
+                encoding::Encoding::Yit => path.clone(),
+                encoding::Encoding::Git => match path.rfind('/') {
+                    None => path.clone(),
+                    Some(pos) => String::from(&path[pos + 1..]),
+                },
+            };
+            if let Some(id) = store::ObjectId::from_hex(&hash, store.format()) {
+                entries.push(object::TreeEntry {
+                    mode: object::MODE_FILE,
+                    name,
+                    id,
+                });
             }
         }
-        let mut hasher = Sha1::new();
-        hasher.input_str(&content);
-        let result = hasher.result_str();
-        match Tree::write_tree(result.clone(), content) {
+        entries.sort_by_key(|entry| entry.sort_key());
+        match store.write(&object::Object::Tree(entries)) {
             Err(_) => String::from(""),
-            Ok(_) => result,
-        }
-    }
-
-    pub fn write_tree(hash: String, content: String) -> Result<(), TreeError> {
-        let new_content = String::from("tree\n") + &content;
-        let dir = &hash[0..2];
-        let filename = &hash[2..];
-        let res = fs::create_dir_all(String::from(".yit/objects/") + dir);
-        if res.is_err() {
-            return Err(TreeError::IOError);
-        }
-        match File::create(String::from(".yit/objects/") + dir + "/" + filename) {
-            Err(_) => Err(TreeError::IOError),
-            Ok(mut file) => {
-                let compressed = compress_to_vec(new_content.as_bytes(), 0);
-                let res_wr = file.write_all(&compressed);
-                if res_wr.is_err() {
-                    return Err(TreeError::IOError);
-                }
-                Ok(())
-            }
+            Ok(id) => id.to_string(),
         }
     }
 
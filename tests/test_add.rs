@@ -0,0 +1,116 @@
+use std::{env, fs};
+use yit::{pathspec, repo};
+
+#[test]
+fn test_pathspec() {
+    let spec = pathspec::Pathspec::new("./src/");
+    assert_eq!("src", spec.spec);
+    assert_eq!("src/", spec.root());
+    assert!(spec.matches("src"));
+    assert!(spec.matches("src/a/lib.rs"));
+    assert!(!spec.matches("srcs/lib.rs"));
+
+    let all = pathspec::Pathspec::new(".");
+    assert_eq!("", all.root());
+    assert!(all.matches("anything/at/all"));
+
+    let glob = pathspec::Pathspec::new("*.rs");
+    assert!(glob.is_glob());
+    assert_eq!("", glob.root());
+    assert!(glob.matches("main.rs"));
+    assert!(glob.matches("src/deep/lib.rs"));
+    assert!(!glob.matches("main.rc"));
+
+    let nested = pathspec::Pathspec::new("src/te?t/*.txt");
+    assert_eq!("src/", nested.root());
+    assert!(nested.matches("src/test/a.txt"));
+    assert!(!nested.matches("doc/test/a.txt"));
+}
+
+fn add(repo: &repo::Repository, pathspecs: &[&str], options: repo::AddOptions) -> Vec<String> {
+    let pathspecs = pathspecs.iter().map(|spec| String::from(*spec)).collect();
+    match repo.clone().add_paths(pathspecs, options) {
+        Ok(staged) => staged
+            .into_iter()
+            .map(|(action, path)| action + " " + &path)
+            .collect(),
+        Err(_) => panic!("add failed"),
+    }
+}
+
+#[test]
+fn test_add_paths() {
+    let _ = fs::remove_dir_all("tmp/add");
+    fs::create_dir_all("tmp/add/src/nested").unwrap();
+    fs::create_dir_all("tmp/add/target").unwrap();
+    env::set_current_dir("tmp/add").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    for name in [
+        ".yitignore",
+        "README",
+        "main.rs",
+        "src/lib.rs",
+        "src/nested/mod.rs",
+        "src/notes.txt",
+        "target/out",
+    ] {
+        fs::write(name, name).unwrap();
+    }
+    fs::write(".yitignore", "target/\n").unwrap();
+    let default = repo::AddOptions::default();
+
+    assert_eq!(
+        vec!["add main.rs", "add src/lib.rs", "add src/nested/mod.rs"],
+        add(&repo, &["*.rs"], default)
+    );
+    assert_eq!(vec!["add src/notes.txt"], add(&repo, &["src/"], default));
+    // staging again finds nothing new
+    assert_eq!(
+        vec!["add .yitignore", "add README"],
+        add(&repo, &["."], default)
+    );
+    assert!(add(&repo, &["."], default).is_empty());
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+
+    assert!(matches!(
+        repo.clone().add_paths(vec![String::from("target/out")], default),
+        Err(repo::RepoError::IgnoredPath(path)) if path == "target/out"
+    ));
+    assert!(matches!(
+        repo.clone().add_paths(vec![String::from("*.md")], default),
+        Err(repo::RepoError::UntrackedPath(_))
+    ));
+    let force = repo::AddOptions {
+        force: true,
+        ..default
+    };
+    assert_eq!(vec!["add target/out"], add(&repo, &["target"], force));
+
+    fs::write("main.rs", "changed").unwrap();
+    fs::remove_file("src/notes.txt").unwrap();
+    fs::write("NEW", "new").unwrap();
+    let update = repo::AddOptions {
+        update: true,
+        ..default
+    };
+    assert_eq!(
+        vec!["add main.rs", "remove src/notes.txt"],
+        add(&repo, &[], update)
+    );
+    let all = repo::AddOptions {
+        all: true,
+        ..default
+    };
+    fs::remove_file("README").unwrap();
+    assert_eq!(vec!["add NEW", "remove README"], add(&repo, &[], all));
+    assert!(repo.clone().commit(String::from("second")).is_ok());
+    match repo.clone().status() {
+        Ok(result) => {
+            assert!(result.entries.is_empty());
+            assert!(result.untracked.is_empty());
+        }
+        Err(_) => panic!("unable to read status"),
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::Cursor;
+use std::{env, fs};
+use yit::encoding::ObjectFormat;
+use yit::{diff, index, patch, repo, store};
+
+const OLD: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n";
+const NEW: &str = "one\n2\nthree\nfour\nfive\nsix\n7\neight\n";
+
+#[test]
+fn test_hunks() {
+    let old = diff::split_lines(OLD);
+    let new = diff::split_lines(NEW);
+    assert_eq!(
+        vec![
+            diff::Edit::Equal(0, 0),
+            diff::Edit::Delete(1),
+            diff::Edit::Insert(1)
+        ],
+        diff::line_diff(&old, &new)[..3]
+    );
+
+    let hunks = diff::hunks(&old, &new, 3);
+    assert_eq!(1, hunks.len());
+    assert_eq!("@@ -1,8 +1,8 @@", hunks[0].header());
+    assert_eq!(NEW, diff::apply_hunks(&old, &hunks));
+
+    let parts = hunks[0].split().unwrap();
+    assert_eq!(2, parts.len());
+    assert_eq!("@@ -1,6 +1,6 @@", parts[0].header());
+    assert_eq!("@@ -3,6 +3,6 @@", parts[1].header());
+    assert!(parts[0].split().is_none());
+    assert_eq!(
+        "one\n2\nthree\nfour\nfive\nsix\nseven\neight\n",
+        diff::apply_hunks(&old, &parts[..1])
+    );
+    assert_eq!(
+        "one\ntwo\nthree\nfour\nfive\nsix\n7\neight\n",
+        diff::apply_hunks(&old, &parts[1..])
+    );
+    assert_eq!(NEW, diff::apply_hunks(&old, &parts));
+
+    let no_newline = diff::split_lines("a\nb");
+    let hunks = diff::hunks(&diff::split_lines("a\n"), &no_newline, 3);
+    assert!(hunks[0]
+        .to_string()
+        .ends_with("+b\n\\ No newline at end of file\n"));
+}
+
+#[test]
+fn test_large_diff() {
+    // nothing in common, the worst case for the edit script search
+    let old: Vec<String> = (0..2000).map(|i| format!("old {}\n", i)).collect();
+    let new: Vec<String> = (0..2000).map(|i| format!("new {}\n", i)).collect();
+    let edits = diff::line_diff(&old, &new);
+    assert_eq!(4000, edits.len());
+    assert!(edits[..2000]
+        .iter()
+        .all(|edit| matches!(edit, diff::Edit::Delete(_))));
+    assert_eq!(diff::Edit::Insert(1999), edits[3999]);
+
+    let old: Vec<&str> = old.iter().map(|line| line.as_str()).collect();
+    let new: Vec<&str> = new.iter().map(|line| line.as_str()).collect();
+    assert_eq!(
+        new.concat(),
+        diff::apply_hunks(&old, &diff::hunks(&old, &new, 3))
+    );
+
+    assert_eq!(None, diff::as_text(b"text\0with a NUL".to_vec()));
+    assert_eq!(None, diff::as_text(vec![0xff, b'a']));
+    assert_eq!(
+        Some(String::from("ü\n")),
+        diff::as_text("ü\n".as_bytes().to_vec())
+    );
+}
+
+#[test]
+fn test_parse_edited_hunk() {
+    let old = diff::split_lines(OLD);
+    let hunks = diff::hunks(&old, &diff::split_lines(NEW), 1);
+    assert_eq!("@@ -1,3 +1,3 @@", hunks[0].header());
+
+    // keep "two" and add a line of our own instead
+    let edited =
+        patch::parse_edited_hunk(&hunks[0], "# comment\n one\n two\n+2.5\n three\n", &old).unwrap();
+    assert_eq!("@@ -1,3 +1,4 @@", edited.header());
+    assert_eq!(
+        "one\ntwo\n2.5\nthree\nfour\nfive\nsix\nseven\neight\n",
+        diff::apply_hunks(&old, &[edited])
+    );
+    // the unchanged lines must still be there
+    assert!(patch::parse_edited_hunk(&hunks[0], " one\n+2\n three\n", &old).is_none());
+    assert!(patch::parse_edited_hunk(&hunks[0], " one\n-zwei\n three\n", &old).is_none());
+}
+
+#[test]
+fn test_add_patch() {
+    let _ = fs::remove_dir_all("tmp/add_patch");
+    fs::create_dir_all("tmp/add_patch").unwrap();
+    env::set_current_dir("tmp/add_patch").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    fs::write("numbers.txt", OLD).unwrap();
+    fs::write("other.txt", "other\n").unwrap();
+    assert!(repo.clone().add(String::from("numbers.txt")).is_ok());
+    assert!(repo.clone().add(String::from("other.txt")).is_ok());
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+
+    fs::write("numbers.txt", NEW).unwrap();
+    fs::write("other.txt", "changed\n").unwrap();
+    let mut output = Vec::new();
+    let staged =
+        match repo
+            .clone()
+            .add_patch(Vec::new(), &mut Cursor::new("s\ny\nn\nn\n"), &mut output)
+        {
+            Ok(staged) => staged,
+            Err(_) => panic!("add -p failed"),
+        };
+    assert_eq!(vec!["numbers.txt"], staged);
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("diff --yit a/numbers.txt b/numbers.txt\n"));
+    assert!(output.contains("Stage this hunk [y,n,q,a,d,s,e,?]? Split into 2 hunks.\n"));
+    assert!(output.contains("(2/2) Stage this hunk [y,n,q,a,d,e,?]? "));
+
+    let index_map = match index::Index::new(String::from(".yit/index"), ObjectFormat::Sha1) {
+        Ok(index) => index.index_map,
+        Err(_) => panic!("unable to read the index"),
+    };
+    let hash = &index_map["numbers.txt"];
+    let store = store::LooseStore::open();
+    let id = store::ObjectId::from_hex(hash, store::ObjectStore::format(&store)).unwrap();
+    match store::ObjectStore::get(&store, &id) {
+        Ok((_, content)) => assert_eq!(
+            "one\n2\nthree\nfour\nfive\nsix\nseven\neight\n",
+            String::from_utf8(content).unwrap()
+        ),
+        Err(_) => panic!("staged blob is missing"),
+    }
+    // the working file keeps every change
+    assert_eq!(NEW, fs::read_to_string("numbers.txt").unwrap());
+    assert!(!index_map.contains_key("other.txt"));
+}
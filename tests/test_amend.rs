@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::{env, fs};
+use yit::{branch, commit, reflog, repo, store, tree};
+
+fn read(store: &store::LooseStore, hash: &str) -> commit::CommitNode {
+    match commit::CommitNode::read(store, hash) {
+        Ok(node) => node,
+        Err(_) => panic!("unable to read commit"),
+    }
+}
+
+fn files(store: &store::LooseStore, node: &commit::CommitNode) -> Vec<String> {
+    let map: HashMap<String, String> = tree::Tree::tree_to_index_map(store, node.tree_hash.clone());
+    let mut files: Vec<String> = map.into_keys().collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn test_amend() {
+    let _ = fs::remove_dir_all("tmp/amend");
+    fs::create_dir_all("tmp/amend").unwrap();
+    env::set_current_dir("tmp/amend").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    // nothing to amend yet
+    assert!(repo.clone().amend(None).is_err());
+
+    env::set_var("YIT_AUTHOR_NAME", "Alice");
+    env::set_var("YIT_AUTHOR_EMAIL", "alice@example.com");
+    env::set_var("YIT_AUTHOR_DATE", "1600000000 +0000");
+    env::set_var("YIT_COMMITTER_DATE", "1600000000 +0000");
+    fs::write("a.txt", "a").unwrap();
+    assert!(repo.clone().add(String::from("a.txt")).is_ok());
+    assert!(repo.clone().commit(String::from("First")).is_ok());
+    let first = branch::get_commit(String::from("master")).ok().unwrap();
+    fs::write("b.txt", "b").unwrap();
+    assert!(repo.clone().add(String::from("b.txt")).is_ok());
+    assert!(repo.clone().commit(String::from("Secnd")).is_ok());
+    let second = branch::get_commit(String::from("master")).ok().unwrap();
+
+    let store = store::LooseStore::open();
+    env::set_var("YIT_AUTHOR_NAME", "Bob");
+    env::set_var("YIT_COMMITTER_DATE", "1600000100 +0000");
+    assert!(repo.clone().amend(Some(String::from("Second"))).is_ok());
+    let reworded = branch::get_commit(String::from("master")).ok().unwrap();
+    assert_ne!(second, reworded);
+    let node = read(&store, &reworded);
+    assert_eq!(vec![first.clone()], node.parents);
+    assert_eq!("Alice", node.author.unwrap().name);
+    assert_eq!(1600000100, node.time);
+    assert_eq!(
+        "Second",
+        commit::read_message(&store, &reworded).ok().unwrap()
+    );
+    assert_eq!(
+        read(&store, &second).tree_hash,
+        read(&store, &reworded).tree_hash
+    );
+
+    // a forgotten file joins the tip, which keeps its message
+    fs::write("c.txt", "c").unwrap();
+    assert!(repo.clone().add(String::from("c.txt")).is_ok());
+    assert!(repo.clone().amend(None).is_ok());
+    let amended = branch::get_commit(String::from("master")).ok().unwrap();
+    let node = read(&store, &amended);
+    assert_eq!(vec![first.clone()], node.parents);
+    assert_eq!(vec!["a.txt", "b.txt", "c.txt"], files(&store, &node));
+    assert_eq!(
+        "Second",
+        commit::read_message(&store, &amended).ok().unwrap()
+    );
+
+    for refname in ["HEAD", "refs/heads/master"] {
+        let entries = reflog::read(refname).ok().unwrap();
+        assert_eq!(4, entries.len());
+        assert_eq!(second, entries[2].old);
+        assert_eq!(reworded, entries[2].new);
+        assert_eq!("commit (amend): Second", entries[3].message);
+        assert_eq!(amended, entries[3].new);
+    }
+
+    // the root commit can be amended too and stays a root
+    assert!(repo.clone().checkout(first.clone()).is_ok());
+    assert!(repo.clone().amend(Some(String::from("Root"))).is_ok());
+    let head = fs::read_to_string(".yit/HEAD").unwrap();
+    let root = read(&store, head.trim());
+    assert!(root.parents.is_empty());
+    assert_eq!(vec!["a.txt"], files(&store, &root));
+}
@@ -0,0 +1,34 @@
+use std::{env, fs};
+use yit::{blob, file, repo, store};
+
+#[test]
+fn test_binary_round_trip() {
+    let _ = fs::remove_dir_all("tmp/binary");
+    fs::create_dir_all("tmp/binary").unwrap();
+    env::set_current_dir("tmp/binary").unwrap();
+
+    let content: Vec<u8> = vec![
+        0x89, b'P', b'N', b'G', 0, 0, 0xff, 0xfe, 0x80, b'\n', 0, 0xc3,
+    ];
+    fs::write("image.bin", &content).unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    assert!(repo.clone().add(String::from("image.bin")).is_ok());
+    assert!(repo.clone().commit(String::from("binary")).is_ok());
+
+    let store = store::LooseStore::open();
+    let hash = match blob::Blob::new(String::from("image.bin")).hash_object(&store, false) {
+        Ok(hash) => hash,
+        Err(_) => panic!("unable to hash binary file"),
+    };
+    match file::cat_file(&store, hash) {
+        Ok(stored) => assert_eq!(content, stored),
+        Err(_) => panic!("unable to read binary blob"),
+    }
+
+    assert!(repo.clone().checkout(String::from("other")).is_ok());
+    fs::write("image.bin", b"overwritten").unwrap();
+    assert!(repo.checkout(String::from("master")).is_ok());
+    assert_eq!(content, fs::read("image.bin").unwrap());
+}
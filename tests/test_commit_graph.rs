@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::{env, fs};
+use yit::{branch, commit, commitgraph, encoding, identity, repo, store, tree};
+
+fn write(store: &store::MemoryStore, tree_hash: &str, parents: Vec<String>, time: i64) -> String {
+    let signature = identity::Signature {
+        name: String::from("A U Thor"),
+        email: String::from("author@example.com"),
+        time,
+        offset: 0,
+    };
+    match commit::write_commit(
+        store,
+        time.to_string(),
+        parents,
+        String::from(tree_hash),
+        &signature,
+        &signature,
+    ) {
+        Ok(hash) => hash,
+        Err(_) => panic!("unable to write commit"),
+    }
+}
+
+#[test]
+fn test_commit_graph() {
+    let store = store::MemoryStore::new(encoding::Encoding::Yit);
+    let tree_hash = tree::Tree::new(HashMap::new()).hash_tree(&store);
+
+    // two lines of history that merge each other at every step, which
+    // doubles the number of paths to the root with each generation
+    let root = write(&store, &tree_hash, Vec::new(), 0);
+    let mut left = root.clone();
+    let mut right = root.clone();
+    for i in 0..200 {
+        let time = 10 * (i + 1);
+        let next_left = write(&store, &tree_hash, vec![left.clone(), right.clone()], time);
+        right = write(&store, &tree_hash, vec![right, left], time + 1);
+        left = next_left;
+    }
+    let side = write(&store, &tree_hash, vec![root.clone()], 5);
+
+    let mut graph = commit::CommitGraph::new(&store);
+    let first = match graph.get(&left) {
+        Ok(node) => node,
+        Err(_) => panic!("unable to read commit"),
+    };
+    let second = match graph.get(&left) {
+        Ok(node) => node,
+        Err(_) => panic!("unable to read commit"),
+    };
+    assert!(Rc::ptr_eq(&first, &second));
+    assert_eq!(2, first.parents.len());
+
+    let ancestors: Vec<String> = graph
+        .ancestors(&left)
+        .map(|node| match node {
+            Ok(node) => node.hash.clone(),
+            Err(_) => panic!("unable to read commit"),
+        })
+        .collect();
+    assert_eq!(2 * 200, ancestors.len());
+    assert_eq!(left, ancestors[0]);
+    assert_eq!(root, ancestors[ancestors.len() - 1]);
+
+    assert!(matches!(graph.is_ancestor(&root, &left), Ok(true)));
+    assert!(matches!(graph.is_ancestor(&left, &left), Ok(true)));
+    assert!(matches!(graph.is_ancestor(&left, &right), Ok(false)));
+    assert!(matches!(graph.is_ancestor(&side, &left), Ok(false)));
+    match graph.merge_base(&side, &left) {
+        Ok(Some(base)) => assert_eq!(root, base),
+        _ => panic!("no merge base"),
+    }
+    match graph.merge_base(&left, &right) {
+        Ok(Some(base)) => assert_eq!(2, graph.get(&base).ok().unwrap().parents.len()),
+        _ => panic!("no merge base"),
+    }
+    assert!(graph.get(&tree_hash).is_err());
+}
+
+#[test]
+fn test_commit_graph_file() {
+    let _ = fs::remove_dir_all("tmp/commit_graph");
+    fs::create_dir_all("tmp/commit_graph").unwrap();
+    env::set_current_dir("tmp/commit_graph").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    let commit = |name: &str| {
+        fs::write(name, name).unwrap();
+        assert!(repo.clone().add(String::from(name)).is_ok());
+        assert!(repo.clone().commit(String::from(name)).is_ok());
+        branch::get_commit(String::from("master")).ok().unwrap()
+    };
+    let first = commit("first");
+    let second = commit("second");
+
+    let format = encoding::ObjectFormat::Sha1;
+    let graph = match commitgraph::GraphFile::open(".yit/commit-graph", format) {
+        Ok(graph) => graph,
+        Err(_) => panic!("commit-graph file not written"),
+    };
+    assert_eq!(2, graph.len());
+    assert_eq!(1, graph.get(&first).unwrap().generation);
+    assert_eq!(2, graph.get(&second).unwrap().generation);
+    assert_eq!(vec![first.clone()], graph.get(&second).unwrap().parents);
+
+    // answers come from the file alone
+    let empty = store::MemoryStore::new(encoding::Encoding::Yit);
+    let mut cached = commit::CommitGraph::new(&empty).with_cache(graph);
+    assert!(matches!(cached.is_ancestor(&first, &second), Ok(true)));
+    assert!(matches!(cached.is_ancestor(&second, &first), Ok(false)));
+    assert!(matches!(cached.merge_base(&first, &second), Ok(Some(base)) if base == first));
+
+    // an unreadable file is rebuilt by the next commit
+    fs::write(".yit/commit-graph", "garbage").unwrap();
+    let third = commit("third");
+    match commitgraph::GraphFile::open(".yit/commit-graph", format) {
+        Ok(graph) => assert_eq!(3, graph.get(&third).unwrap().generation),
+        Err(_) => panic!("commit-graph file not rebuilt"),
+    }
+
+    // gc drops commits no longer reachable from any ref
+    fs::write(".yit/refs/heads/master", String::from("ref\n") + &second).unwrap();
+    assert!(repo.gc(Some(String::from("now"))).is_ok());
+    match commitgraph::GraphFile::open(".yit/commit-graph", format) {
+        Ok(graph) => {
+            assert_eq!(2, graph.len());
+            assert!(graph.get(&third).is_none());
+        }
+        Err(_) => panic!("commit-graph file not written by gc"),
+    }
+}
@@ -0,0 +1,105 @@
+use std::{env, fs};
+use yit::{commandparser, log, message, repo};
+
+fn last_message(repo: &repo::Repository) -> String {
+    let options = log::LogOptions {
+        max_count: Some(1),
+        format: log::Format::Custom(String::from("%s|%b")),
+        ..log::LogOptions::default()
+    };
+    match repo.clone().log(Vec::new(), options) {
+        Ok(output) => output,
+        Err(_) => panic!("log failed"),
+    }
+}
+
+#[test]
+fn test_split_args() {
+    let split = |input: &str| commandparser::split_args(input).ok().unwrap();
+    assert_eq!(vec!["commit", "-m", "first"], split("commit -m first\n"));
+    assert_eq!(
+        vec!["commit", "-m", "Fix the build", "-m", "It's done"],
+        split("commit -m \"Fix the build\" -m 'It'\\''s done'")
+    );
+    assert_eq!(
+        vec!["a b", "c\"d", "e\\f"],
+        split("a\\ b \"c\\\"d\" 'e\\f'")
+    );
+    assert_eq!(vec!["", "x"], split("'' x"));
+    assert!(split("  \t\n").is_empty());
+    assert!(commandparser::split_args("commit -m \"oops").is_err());
+}
+
+#[test]
+fn test_cleanup() {
+    assert_eq!(
+        "Subject\n\nBody line\n\nMore",
+        message::cleanup(
+            "\n\nSubject  \n\n\n# a comment\nBody line\n\n\nMore\n\n",
+            true
+        )
+    );
+    assert_eq!(
+        "# not a comment",
+        message::cleanup("# not a comment\n", false)
+    );
+    assert_eq!("", message::cleanup("\n# only comments\n#\n", true));
+}
+
+#[test]
+fn test_edit_commit_message() {
+    let _ = fs::remove_dir_all("tmp/commit_message");
+    fs::create_dir_all("tmp/commit_message").unwrap();
+    env::set_current_dir("tmp/commit_message").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    fs::write("a.txt", "one").unwrap();
+    assert!(repo.clone().add(String::from("a.txt")).is_ok());
+    assert!(repo
+        .clone()
+        .commit(String::from("Subject\n\nFirst paragraph"))
+        .is_ok());
+    assert_eq!("Subject|First paragraph\n", last_message(&repo));
+
+    fs::write("a.txt", "two").unwrap();
+    fs::write("b.txt", "three").unwrap();
+    assert!(repo.clone().add(String::from("a.txt")).is_ok());
+    assert!(repo.clone().add(String::from("b.txt")).is_ok());
+    match repo.clone().staged_changes() {
+        Ok(changes) => assert_eq!(
+            vec![
+                (String::from("modified"), String::from("a.txt")),
+                (String::from("new file"), String::from("b.txt")),
+            ],
+            changes
+        ),
+        Err(_) => panic!("unable to list staged changes"),
+    }
+
+    // the editor keeps a copy of the template and appends a message to it
+    env::set_var(
+        "YIT_EDITOR",
+        "edit() { cp \"$1\" template; printf 'Edited\\n\\n# dropped\\nBody  \\n' >> \"$1\"; }; edit",
+    );
+    let message = match repo.clone().edit_commit_message(false) {
+        Ok(message) => message,
+        Err(_) => panic!("editor failed"),
+    };
+    assert_eq!("Edited\n\nBody", message);
+    let template = fs::read_to_string("template").unwrap();
+    assert!(template.starts_with("\n# Please enter the commit message"));
+    assert!(template.contains("# On branch master\n"));
+    assert!(template.contains("#\tmodified:   a.txt\n#\tnew file:   b.txt\n"));
+    assert!(repo.clone().commit(message).is_ok());
+    assert_eq!("Edited|Body\n", last_message(&repo));
+
+    env::set_var("YIT_EDITOR", "false");
+    assert!(matches!(
+        repo.clone().edit_commit_message(false),
+        Err(repo::RepoError::EditorError(
+            message::MessageError::EditorFailed(_)
+        ))
+    ));
+    env::remove_var("YIT_EDITOR");
+}
@@ -0,0 +1,94 @@
+use std::io::prelude::*;
+use std::{env, fs};
+use yit::store::ObjectStore;
+use yit::{blob, config, encoding, file, repo, store};
+
+fn sample(len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i * 7 % 251) as u8 ^ (i >> 12) as u8)
+        .collect()
+}
+
+#[test]
+fn test_streaming_round_trip() {
+    let content = sample(300 * 1024);
+    for level in &[0, 1, 6, 9] {
+        let mut deflater = encoding::Deflater::new(Vec::new(), *level);
+        // odd chunk sizes so writes straddle the internal buffers
+        for chunk in content.chunks(1000) {
+            deflater.write_all(chunk).unwrap();
+        }
+        let compressed = deflater.finish().unwrap();
+        assert_eq!(Some(content.clone()), encoding::decompress(&compressed));
+
+        let mut inflated = Vec::new();
+        encoding::Inflater::new(&compressed[..])
+            .unwrap()
+            .read_to_end(&mut inflated)
+            .unwrap();
+        assert_eq!(content, inflated);
+    }
+
+    let compressed = encoding::compress(&content, 6);
+    let mut truncated = encoding::Inflater::new(&compressed[..compressed.len() / 2]).unwrap();
+    assert!(truncated.read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_compression_level() {
+    let mut config = config::Config::new();
+    assert_eq!(6, encoding::compression_level(&config));
+    config.set("core.compression", "9");
+    assert_eq!(9, encoding::compression_level(&config));
+    config.set("core.compression", "0");
+    assert_eq!(0, encoding::compression_level(&config));
+    // -1 asks for zlib's default level
+    config.set("core.compression", "-1");
+    assert_eq!(6, encoding::compression_level(&config));
+}
+
+#[test]
+fn test_compressed_loose_objects() {
+    let _ = fs::remove_dir_all("tmp/compression");
+    fs::create_dir_all("tmp/compression").unwrap();
+    env::set_current_dir("tmp/compression").unwrap();
+
+    let mut config = config::Config::new();
+    config.set("core.compression", "9");
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init_with(config).is_ok());
+
+    let content = sample(2 * 1024 * 1024);
+    fs::write("large.bin", &content).unwrap();
+    let store = store::LooseStore::open();
+    let hash = match blob::Blob::new(String::from("large.bin")).hash_object(&store, true) {
+        Ok(hash) => hash,
+        Err(_) => panic!("hashing failed"),
+    };
+    let id = store::ObjectId::from_hex(&hash, store.format()).unwrap();
+    assert_eq!(store.hash(store::ObjectKind::Blob, &content), id);
+    let stored = fs::metadata(format!(".yit/objects/{}/{}", id.dir(), id.file())).unwrap();
+    assert!(stored.len() < content.len() as u64 / 2);
+
+    let mut copy = Vec::new();
+    match file::cat_file_to(&store, hash, &mut copy) {
+        Ok(_) => assert_eq!(content, copy),
+        Err(_) => panic!("cat_file_to failed"),
+    }
+
+    // level 0 stores the content as is
+    let uncompressed = store::LooseStore::open().with_compression(0);
+    let id = uncompressed
+        .put_stream(store::ObjectKind::Blob, 5, &mut &b"short"[..])
+        .unwrap();
+    let stored = fs::metadata(format!(".yit/objects/{}/{}", id.dir(), id.file())).unwrap();
+    assert!(stored.len() > 5);
+    assert_eq!(
+        (store::ObjectKind::Blob, b"short".to_vec()),
+        uncompressed.get(&id).unwrap()
+    );
+    // content shorter than announced is rejected
+    assert!(uncompressed
+        .put_stream(store::ObjectKind::Blob, 10, &mut &b"short"[..])
+        .is_err());
+}
@@ -0,0 +1,111 @@
+use std::{env, fs};
+use yit::store::ObjectStore;
+use yit::{encoding, fsck, repo, store};
+
+#[test]
+fn test_fsck_memory_store() {
+    let store = store::MemoryStore::new(encoding::Encoding::Git);
+    let blob = store.put(store::ObjectKind::Blob, b"content").unwrap();
+    let missing = store.hash(store::ObjectKind::Blob, b"never stored");
+    let tree = store
+        .put(
+            store::ObjectKind::Tree,
+            &[
+                b"100644 a\0".to_vec(),
+                encoding::hex_to_bytes(blob.as_str()).unwrap(),
+                b"100644 b\0".to_vec(),
+                encoding::hex_to_bytes(missing.as_str()).unwrap(),
+            ]
+            .concat(),
+        )
+        .unwrap();
+    let refs = vec![
+        (String::from("refs/heads/tree"), tree.to_string()),
+        (String::from("refs/heads/bad"), String::from("nonsense")),
+    ];
+
+    let report = fsck::check(&store, &refs, &[]);
+    assert_eq!(2, report.checked);
+    assert_eq!(
+        vec![
+            fsck::Problem::Missing {
+                id: missing,
+                kind: store::ObjectKind::Blob,
+                referenced_by: tree.clone(),
+            },
+            fsck::Problem::WrongType {
+                id: tree,
+                expected: store::ObjectKind::Commit,
+                actual: store::ObjectKind::Tree,
+                referenced_by: String::from("refs/heads/tree"),
+            },
+            fsck::Problem::BrokenRef {
+                name: String::from("refs/heads/bad"),
+                target: String::from("nonsense"),
+            },
+        ],
+        report.problems
+    );
+}
+
+#[test]
+fn test_fsck_reports_problems() {
+    let _ = fs::remove_dir_all("tmp/fsck");
+    fs::create_dir_all("tmp/fsck").unwrap();
+    env::set_current_dir("tmp/fsck").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    fs::write("file", b"content").unwrap();
+    assert!(repo.clone().add(String::from("file")).is_ok());
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+
+    match repo.clone().fsck() {
+        Ok(report) => {
+            assert_eq!(3, report.checked);
+            assert!(report.problems.is_empty());
+        }
+        Err(_) => panic!("fsck failed"),
+    }
+
+    let store = store::LooseStore::open();
+    let orphan = store
+        .put(store::ObjectKind::Blob, b"nothing points here")
+        .unwrap();
+    let truncated = store
+        .put(store::ObjectKind::Blob, b"about to be truncated")
+        .unwrap();
+    let path = format!(".yit/objects/{}/{}", truncated.dir(), truncated.file());
+    let data = fs::read(&path).unwrap();
+    fs::write(&path, &data[..data.len() / 2]).unwrap();
+    fs::write(".yit/refs/heads/gone", format!("ref\n{}", "0".repeat(40))).unwrap();
+    // a branch created before the first commit points nowhere yet
+    fs::write(".yit/refs/heads/unborn", "ref\n").unwrap();
+    // hashed as the git encoding it was written in, not the repository's
+    let git_blob = store::LooseStore::new(String::from(".yit/objects"), encoding::Encoding::Git)
+        .put(store::ObjectKind::Blob, b"written with core.gitcompat")
+        .unwrap();
+
+    match repo.fsck() {
+        Ok(report) => {
+            assert_eq!(6, report.checked);
+            assert_eq!(4, report.problems.len());
+            assert!(report.problems.contains(&fsck::Problem::Dangling {
+                id: orphan,
+                kind: store::ObjectKind::Blob,
+            }));
+            assert!(report.problems.contains(&fsck::Problem::Dangling {
+                id: git_blob,
+                kind: store::ObjectKind::Blob,
+            }));
+            assert!(report.problems.iter().any(
+                |problem| matches!(problem, fsck::Problem::Corrupt { id, .. } if *id == truncated)
+            ));
+            assert!(report.problems.contains(&fsck::Problem::BrokenRef {
+                name: String::from("refs/heads/gone"),
+                target: "0".repeat(40),
+            }));
+        }
+        Err(_) => panic!("fsck failed"),
+    }
+}
@@ -0,0 +1,109 @@
+use std::time::{Duration, SystemTime};
+use std::{env, fs};
+use yit::store::ObjectStore;
+use yit::{gc, repo, store};
+
+#[test]
+fn test_parse_expire() {
+    assert_eq!(None, gc::parse_expire("never").unwrap());
+    assert_eq!(
+        Some(Duration::from_secs(0)),
+        gc::parse_expire("now").unwrap()
+    );
+    assert_eq!(
+        Some(Duration::from_secs(90)),
+        gc::parse_expire("90").unwrap()
+    );
+    assert_eq!(
+        Some(Duration::from_secs(14 * 24 * 3600)),
+        gc::parse_expire("2.weeks.ago").unwrap()
+    );
+    assert!(gc::parse_expire("soon").is_err());
+}
+
+#[test]
+fn test_gc_needs_a_store_with_packs() {
+    let memory = store::MemoryStore::new(yit::encoding::Encoding::Yit);
+    let blob = memory.put(store::ObjectKind::Blob, b"in memory").unwrap();
+    assert!(matches!(
+        gc::collect(&memory, std::slice::from_ref(&blob), None),
+        Err(gc::GcError::Unsupported)
+    ));
+    assert!(matches!(
+        memory.write_pack(&[(blob.clone(), String::from("blob"))]),
+        Err(store::StoreError::Unsupported)
+    ));
+    assert!(memory.exists(&blob));
+}
+
+#[test]
+fn test_gc_prunes_unreachable_objects() {
+    let _ = fs::remove_dir_all("tmp/gc");
+    fs::create_dir_all("tmp/gc").unwrap();
+    env::set_current_dir("tmp/gc").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    fs::write("committed", b"committed content").unwrap();
+    assert!(repo.clone().add(String::from("committed")).is_ok());
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+    fs::write("staged", b"staged content").unwrap();
+    assert!(repo.clone().add(String::from("staged")).is_ok());
+
+    let store = store::LooseStore::open();
+    let orphan = store
+        .put(store::ObjectKind::Blob, b"left behind by a failed merge")
+        .unwrap();
+    let staged = store.hash(store::ObjectKind::Blob, b"staged content");
+
+    // a long grace period keeps the orphan around
+    match repo.clone().gc(Some(String::from("never"))) {
+        Ok(report) => assert_eq!(0, report.pruned),
+        Err(_) => panic!("gc failed"),
+    }
+    assert!(store.exists(&orphan));
+
+    // storing the orphan again makes it as fresh as a new object
+    let orphan_path = format!(".yit/objects/{}/{}", orphan.dir(), orphan.file());
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&orphan_path)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(3600))
+        .unwrap();
+    store
+        .put(store::ObjectKind::Blob, b"left behind by a failed merge")
+        .unwrap();
+    match repo.clone().gc(Some(String::from("600"))) {
+        Ok(report) => assert_eq!(0, report.pruned),
+        Err(_) => panic!("gc failed"),
+    }
+    assert!(store.exists(&orphan));
+
+    match repo.clone().gc(Some(String::from("now"))) {
+        Ok(report) => {
+            assert_eq!(1, report.pruned);
+            // commit, tree, committed blob and the staged blob
+            assert_eq!(4, report.packed);
+        }
+        Err(_) => panic!("gc failed"),
+    }
+    assert!(!store.exists(&orphan));
+    assert!(store.exists(&staged));
+    // everything left lives in exactly one pack
+    let entries: Vec<String> = fs::read_dir(".yit/objects")
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(vec![String::from("pack")], entries);
+    // its pack, index and encoding files
+    assert_eq!(3, fs::read_dir(".yit/objects/pack").unwrap().count());
+
+    fs::write("committed", b"scratch").unwrap();
+    assert!(repo.checkout(String::from("master")).is_ok());
+    assert_eq!(
+        b"committed content".to_vec(),
+        fs::read("committed").unwrap()
+    );
+}
@@ -0,0 +1,85 @@
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+use std::{env, fs};
+use yit::{branch, commit, config, file, repo, store, tree};
+
+#[test]
+fn test_git_compatible_objects() {
+    let _ = fs::remove_dir_all("tmp/git_compat");
+    fs::create_dir_all("tmp/git_compat").unwrap();
+    env::set_current_dir("tmp/git_compat").unwrap();
+
+    fs::write("foobar", b"test content").unwrap();
+    fs::create_dir("ehoo").unwrap();
+    fs::write("ehoo/daaa", b"another test").unwrap();
+
+    let repo = repo::Repository::new();
+    let mut config = config::Config::new();
+    config.set("core.gitcompat", "true");
+    config.set("user.name", "A U Thor");
+    config.set("user.email", "author@example.com");
+    assert!(repo.clone().init_with(config).is_ok());
+    env::set_var("YIT_AUTHOR_DATE", "1600000000 +0200");
+    env::set_var("YIT_COMMITTER_NAME", "C O Mitter");
+    env::set_var("YIT_COMMITTER_EMAIL", "committer@example.com");
+    env::set_var("YIT_COMMITTER_DATE", "@1600000100 -0530");
+    assert!(repo.clone().add(String::from("foobar")).is_ok());
+    assert!(repo.clone().add(String::from("ehoo/daaa")).is_ok());
+    assert!(repo.commit(String::from("git compatible")).is_ok());
+
+    // Hashes below were produced by `git hash-object`, `git write-tree` and
+    // `git hash-object -t commit` on the same content.
+    let commit_hash = match branch::get_commit(String::from("master")) {
+        Ok(hash) => hash,
+        Err(_) => panic!("master has no commit"),
+    };
+    assert_eq!("538c30988ed75e022119c8c0b89203131df5c323", commit_hash);
+    let store = store::LooseStore::open();
+    let commit_node = match commit::CommitNode::read(&store, &commit_hash) {
+        Ok(node) => node,
+        Err(_) => panic!("unable to read commit"),
+    };
+    assert_eq!(
+        "ccffa8694608ee46e8dd58e6f277c867770d28c3",
+        commit_node.tree_hash
+    );
+    let committer = commit_node.committer.unwrap();
+    assert_eq!("C O Mitter", committer.name);
+    assert_eq!(1600000100, committer.time);
+    assert_eq!(-330, committer.offset);
+    assert_eq!(
+        "A U Thor <author@example.com> 1600000000 +0200",
+        commit_node.author.unwrap().to_string()
+    );
+    let index_map = tree::Tree::tree_to_index_map(&store, commit_node.tree_hash);
+    assert_eq!(2, index_map.len());
+    assert_eq!(
+        "08cf6101416f0ce0dda3c80e627f333854c4085c",
+        index_map["foobar"]
+    );
+    assert_eq!(
+        "6321613b31b1e29a6205ac8cefee5383f6b28e75",
+        index_map["ehoo/daaa"]
+    );
+
+    let stored = fs::read(".yit/objects/08/cf6101416f0ce0dda3c80e627f333854c4085c").unwrap();
+    assert_eq!(
+        b"blob 12\0test content".to_vec(),
+        decompress_to_vec_zlib(&stored).unwrap()
+    );
+
+    // An object written by git itself ("hello") is readable as well.
+    fs::create_dir_all(".yit/objects/b6").unwrap();
+    fs::write(
+        ".yit/objects/b6/fc4c620b67d95f953a5c1c1230aaab5db5a1b0",
+        compress_to_vec_zlib(b"blob 5\0hello", 6),
+    )
+    .unwrap();
+    match file::cat_file(
+        &store,
+        String::from("b6fc4c620b67d95f953a5c1c1230aaab5db5a1b0"),
+    ) {
+        Ok(content) => assert_eq!(b"hello".to_vec(), content),
+        Err(_) => panic!("unable to read git object"),
+    }
+}
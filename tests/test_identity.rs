@@ -0,0 +1,25 @@
+use yit::identity::{self, Signature};
+
+#[test]
+fn test_signature_round_trip() {
+    let signature = Signature::parse("Jane Q. Doe <jane@example.com> 1600000000 +0545").unwrap();
+    assert_eq!("Jane Q. Doe", signature.name);
+    assert_eq!("jane@example.com", signature.email);
+    assert_eq!(1600000000, signature.time);
+    assert_eq!(345, signature.offset);
+    assert_eq!(
+        "Jane Q. Doe <jane@example.com> 1600000000 +0545",
+        signature.to_string()
+    );
+
+    let west = Signature {
+        offset: -90,
+        ..signature
+    };
+    assert_eq!("-0130", west.format_offset());
+
+    assert_eq!(Some((5, -300)), identity::parse_date("@5 -0500"));
+    assert!(identity::parse_date("yesterday").is_none());
+    assert!(identity::parse_date("5 +0575").is_none());
+    assert!(Signature::parse("no email 1600000000 +0000").is_none());
+}
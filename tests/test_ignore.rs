@@ -0,0 +1,134 @@
+use std::{env, fs};
+use yit::{ignore, repo, status};
+
+#[test]
+fn test_ignore_rules() {
+    let mut rules = ignore::IgnoreRules::default();
+    rules.add_rules(
+        "",
+        "# build output\n*.log\n!keep.log\nbuild/\n/root.txt\ndoc/*.txt\n**/cache\nlogs/**\na/**/z\n[abc]x?.rs\n\\#hash\ntrailing   \n\\!bang\n",
+    );
+    rules.add_rules("sub/", "!*.log\n/only-here\n");
+    let mut ignored = |path: &str, is_dir: bool| rules.is_ignored(path, is_dir);
+
+    assert!(ignored("a.log", false));
+    assert!(ignored("deep/er/b.log", false));
+    assert!(!ignored("keep.log", false));
+    assert!(!ignored("a.txt", false));
+    assert!(ignored("build", true));
+    assert!(ignored("src/build", true));
+    assert!(!ignored("build", false));
+    assert!(ignored("build/anything.rs", false));
+    assert!(ignored("root.txt", false));
+    assert!(!ignored("dir/root.txt", false));
+    assert!(ignored("doc/a.txt", false));
+    assert!(!ignored("doc/sub/a.txt", false));
+    assert!(ignored("cache", true));
+    assert!(ignored("x/y/cache", false));
+    assert!(ignored("logs/a/b.txt", false));
+    assert!(!ignored("logs", true));
+    assert!(ignored("a/z", false));
+    assert!(ignored("a/b/c/z", false));
+    assert!(ignored("bx1.rs", false));
+    assert!(!ignored("dx1.rs", false));
+    assert!(!ignored("bx/1.rs", false));
+    assert!(ignored("#hash", false));
+    assert!(ignored("trailing", false));
+    assert!(ignored("!bang", false));
+
+    // deeper ignore files win, and only apply below their directory
+    assert!(!ignored("sub/x.log", false));
+    assert!(ignored("sub/only-here", false));
+    assert!(!ignored("only-here", false));
+
+    // a file can't be re-included when its directory is ignored
+    rules.add_rules("", "!build/keep.rs\n");
+    match rules.matching("build/keep.rs", false) {
+        Some(rule) => {
+            assert_eq!("build/", rule.text);
+            assert_eq!(".yitignore", rule.source);
+            assert_eq!(4, rule.line);
+        }
+        None => panic!("no rule matched"),
+    }
+    match rules.matching("sub/x.log", false) {
+        Some(rule) => {
+            assert!(rule.negated);
+            assert_eq!("sub/.yitignore", rule.source);
+            assert_eq!(1, rule.line);
+        }
+        None => panic!("no rule matched"),
+    }
+    assert!(rules.matching("plain.rs", false).is_none());
+}
+
+#[test]
+fn test_ignore_files() {
+    let _ = fs::remove_dir_all("tmp/ignore");
+    fs::create_dir_all("tmp/ignore/target/debug").unwrap();
+    fs::create_dir_all("tmp/ignore/src").unwrap();
+    env::set_current_dir("tmp/ignore").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    fs::write("tracked.o", "tracked").unwrap();
+    assert!(repo.clone().add(String::from("tracked.o")).is_ok());
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+
+    fs::write(".yitignore", "target/\n*.o\n").unwrap();
+    fs::write("src/.yitignore", "!special.o\n").unwrap();
+    fs::create_dir_all(".yit/info").unwrap();
+    fs::write(".yit/info/exclude", "secret.txt\n").unwrap();
+    for name in [
+        "target/debug/yit",
+        "main.o",
+        "src/lib.o",
+        "src/special.o",
+        "src/lib.rs",
+        "secret.txt",
+        "tracked.o",
+    ] {
+        fs::write(name, name).unwrap();
+    }
+
+    let untracked = match repo.clone().status() {
+        Ok(result) => status::format_status(&result, status::Format::Porcelain, false),
+        Err(_) => panic!("unable to read status"),
+    };
+    assert_eq!(
+        " M tracked.o\n?? .yitignore\n?? src/.yitignore\n?? src/lib.rs\n?? src/special.o\n",
+        untracked
+    );
+
+    let paths = [
+        "target/debug/yit",
+        "src/special.o",
+        "secret.txt",
+        "tracked.o",
+        "src/lib.rs",
+    ];
+    let check = |no_index: bool| {
+        let paths = paths.iter().map(|path| String::from(*path)).collect();
+        match repo.clone().check_ignore(paths, no_index) {
+            Ok(results) => results
+                .into_iter()
+                .map(|(path, rule)| match rule {
+                    Some(rule) => format!("{}:{}:{} {}", rule.source, rule.line, rule.text, path),
+                    None => format!(":: {}", path),
+                })
+                .collect::<Vec<String>>(),
+            Err(_) => panic!("check-ignore failed"),
+        }
+    };
+    assert_eq!(
+        vec![
+            ".yitignore:1:target/ target/debug/yit",
+            "src/.yitignore:1:!special.o src/special.o",
+            ".yit/info/exclude:1:secret.txt secret.txt",
+            ":: tracked.o",
+            ":: src/lib.rs",
+        ],
+        check(false)
+    );
+    assert_eq!(".yitignore:2:*.o tracked.o", check(true)[3]);
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use std::{env, fs};
+use yit::encoding::ObjectFormat;
+use yit::{index, repo, status};
+
+#[test]
+fn test_encode_decode() {
+    let mut index_map = HashMap::new();
+    index_map.insert(String::from("src/lib.rs"), String::from("ab12"));
+    index_map.insert(String::from("gone"), String::from(index::DELETED));
+    let mut stat_cache = HashMap::new();
+    let stat = index::Stat {
+        size: 42,
+        mtime: (1_600_000_000, 5),
+        ctime: (-1, 999_999_999),
+        inode: 77,
+        mode: 0o100644,
+    };
+    for path in ["src/lib.rs", "README"] {
+        let hash = String::from("cd34");
+        stat_cache.insert(String::from(path), index::CachedStat { stat, hash });
+    }
+
+    let data = index::encode(&index_map, &stat_cache, ObjectFormat::Sha1).unwrap();
+    assert_eq!(b"YIDX\0\0\0\x01\0\0\0\x03", &data[..12]);
+    // entries are sorted by path
+    assert_eq!(b"\0\x06README", &data[12..20]);
+    let decoded = index::decode(&data, String::from("index"), ObjectFormat::Sha1).unwrap();
+    assert_eq!(index_map, decoded.index_map);
+    assert_eq!(stat_cache, decoded.stat_cache);
+    assert_eq!(
+        data,
+        index::encode(&index_map, &stat_cache, ObjectFormat::Sha1).unwrap()
+    );
+    // sha-256 repositories checksum their index with sha-256
+    let sha256 = index::encode(&index_map, &stat_cache, ObjectFormat::Sha256).unwrap();
+    assert_eq!(data.len() + 12, sha256.len());
+    assert!(index::decode(&sha256, String::new(), ObjectFormat::Sha256).is_ok());
+    assert!(index::decode(&sha256, String::new(), ObjectFormat::Sha1).is_err());
+
+    let mut corrupt = data.clone();
+    corrupt[20] ^= 1;
+    assert!(matches!(
+        index::decode(&corrupt, String::new(), ObjectFormat::Sha1),
+        Err(index::IndexError::Corrupt)
+    ));
+    assert!(matches!(
+        index::decode(&data[..data.len() - 1], String::new(), ObjectFormat::Sha1),
+        Err(index::IndexError::Corrupt)
+    ));
+}
+
+#[test]
+fn test_stat_cache() {
+    let _ = fs::remove_dir_all("tmp/index");
+    fs::create_dir_all("tmp/index").unwrap();
+    env::set_current_dir("tmp/index").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    // an index written by the first versions is still read
+    fs::write("a.txt", "a").unwrap();
+    fs::write("b.txt", "b").unwrap();
+    assert!(repo.clone().add(String::from("a.txt")).is_ok());
+    let staged = index::Index::new(String::from(".yit/index"), ObjectFormat::Sha1).unwrap();
+    let hash = staged.index_map["a.txt"].clone();
+    // paths in it may hold spaces
+    fs::write("c d.txt", "a").unwrap();
+    fs::write(".yit/index", format!("a.txt {0}\nc d.txt {0}\n", hash)).unwrap();
+    assert!(repo.clone().add(String::from("b.txt")).is_ok());
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+
+    // files changed well before the index is written can be trusted
+    let old = SystemTime::now() - Duration::from_secs(10);
+    for path in ["a.txt", "b.txt", "c d.txt"] {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+    }
+    let short = |repo: &repo::Repository| match repo.clone().status() {
+        Ok(result) => status::format_status(&result, status::Format::Short, false),
+        Err(_) => panic!("unable to read status"),
+    };
+    assert_eq!("", short(&repo));
+    let mut cached = index::Index::new(String::from(".yit/index"), ObjectFormat::Sha1).unwrap();
+    assert!(cached.index_map.is_empty());
+    assert_eq!(hash, cached.stat_cache["a.txt"].hash);
+    assert_eq!(hash, cached.stat_cache["c d.txt"].hash);
+    assert_eq!(1, cached.stat_cache["a.txt"].stat.size);
+
+    // an unchanged file isn't read again, so a wrong cached id shows
+    cached.stat_cache.get_mut("a.txt").unwrap().hash = cached.stat_cache["b.txt"].hash.clone();
+    cached.write().unwrap();
+    assert_eq!(" M a.txt\n", short(&repo));
+
+    // any change to the stat data means hashing again
+    fs::write("a.txt", "a").unwrap();
+    fs::write("b.txt", "B").unwrap();
+    assert_eq!(" M b.txt\n", short(&repo));
+
+    // checkout writes the index afresh with the stat data of the files it
+    // wrote; files written in the instant the index is may be left out
+    assert!(repo.clone().commit(String::from("second")).is_ok());
+    assert!(repo.clone().checkout(String::from("other")).is_ok());
+    let checked_out = index::Index::new(String::from(".yit/index"), ObjectFormat::Sha1).unwrap();
+    assert!(checked_out.index_map.is_empty());
+    for (path, cached) in &checked_out.stat_cache {
+        assert_eq!(fs::metadata(path).unwrap().len(), cached.stat.size);
+    }
+    assert_eq!("", short(&repo));
+}
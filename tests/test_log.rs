@@ -0,0 +1,168 @@
+use std::{env, fs};
+use yit::{log, repo};
+
+fn commit(repo: &repo::Repository, file: &str, content: &str, author: &str, time: i64) {
+    fs::write(file, content).unwrap();
+    assert!(repo.clone().add(String::from(file)).is_ok());
+    set_identity(author, time);
+    assert!(repo.clone().commit(String::from(file)).is_ok());
+}
+
+fn set_identity(author: &str, time: i64) {
+    env::set_var("YIT_AUTHOR_NAME", author);
+    env::set_var("YIT_AUTHOR_EMAIL", author.to_lowercase() + "@example.com");
+    env::set_var("YIT_AUTHOR_DATE", format!("{} +0200", time));
+    env::set_var("YIT_COMMITTER_DATE", format!("{} +0200", time));
+}
+
+fn oneline(repo: &repo::Repository, revisions: &[&str], options: log::LogOptions) -> String {
+    let options = log::LogOptions {
+        format: log::Format::Custom(String::from("%s")),
+        ..options
+    };
+    let revisions = revisions.iter().map(|revision| String::from(*revision));
+    match repo.clone().log(revisions.collect(), options) {
+        Ok(output) => output.lines().collect::<Vec<&str>>().join(" "),
+        Err(_) => panic!("log failed"),
+    }
+}
+
+#[test]
+fn test_parse_time() {
+    assert_eq!(Some(1600000000), log::parse_time("@1600000000"));
+    assert_eq!(Some(1600000000), log::parse_time("1600000000 +0200"));
+    assert_eq!(Some(1599955200), log::parse_time("2020-09-13"));
+    assert!(log::parse_time("3.days.ago").is_some());
+    assert!(log::parse_time("someday").is_none());
+}
+
+#[test]
+fn test_log() {
+    let _ = fs::remove_dir_all("tmp/log");
+    fs::create_dir_all("tmp/log/dir").unwrap();
+    env::set_current_dir("tmp/log").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    commit(&repo, "a.txt", "one", "Alice", 1600000000);
+    commit(&repo, "b.txt", "two", "Bob", 1600001000);
+    assert!(repo.clone().checkout(String::from("topic")).is_ok());
+    commit(&repo, "dir/d.txt", "three", "Alice", 1600002000);
+    assert!(repo.clone().checkout(String::from("master")).is_ok());
+    commit(&repo, "c.txt", "four", "Bob", 1600003000);
+    set_identity("Merger", 1600004000);
+    assert!(repo
+        .clone()
+        .merge(String::from("topic"), String::from("master"))
+        .is_ok());
+
+    let all = log::LogOptions::default();
+    assert_eq!(
+        "Merge topic into master c.txt dir/d.txt b.txt a.txt",
+        oneline(&repo, &[], all.clone())
+    );
+    assert_eq!(
+        "dir/d.txt b.txt a.txt",
+        oneline(&repo, &["topic"], all.clone())
+    );
+    assert_eq!(
+        "Merge topic into master c.txt",
+        oneline(&repo, &["topic..master"], all.clone())
+    );
+    assert_eq!(
+        "c.txt dir/d.txt",
+        oneline(&repo, &["HEAD~1...topic"], all.clone())
+    );
+    assert_eq!(
+        "c.txt dir/d.txt",
+        oneline(&repo, &["HEAD^1", "topic", "^HEAD~2"], all.clone())
+    );
+    let first_parent = log::LogOptions {
+        first_parent: true,
+        ..all.clone()
+    };
+    assert_eq!(
+        "Merge topic into master c.txt b.txt a.txt",
+        oneline(&repo, &[], first_parent)
+    );
+    let max_count = log::LogOptions {
+        max_count: Some(2),
+        ..all.clone()
+    };
+    assert_eq!(
+        "Merge topic into master c.txt",
+        oneline(&repo, &[], max_count)
+    );
+    let range = log::LogOptions {
+        since: Some(1600001500),
+        until: Some(1600003500),
+        ..all.clone()
+    };
+    assert_eq!("c.txt dir/d.txt", oneline(&repo, &[], range));
+    let author = log::LogOptions {
+        author: Some(String::from("bob@")),
+        ..all.clone()
+    };
+    assert_eq!("c.txt b.txt", oneline(&repo, &[], author));
+    // the merge takes c.txt unchanged from master, so it does not count
+    let path = log::LogOptions {
+        paths: vec![String::from("c.txt")],
+        ..all.clone()
+    };
+    assert_eq!("c.txt", oneline(&repo, &[], path));
+    let dir = log::LogOptions {
+        paths: vec![String::from("dir/")],
+        ..all.clone()
+    };
+    assert_eq!("dir/d.txt", oneline(&repo, &[], dir));
+    let file_in_dir = log::LogOptions {
+        paths: vec![String::from("dir/d.txt")],
+        ..all.clone()
+    };
+    assert_eq!("dir/d.txt", oneline(&repo, &[], file_in_dir));
+    // along the first parents the merge is what brings dir/ in
+    let first_parent_dir = log::LogOptions {
+        paths: vec![String::from("dir/")],
+        first_parent: true,
+        ..all.clone()
+    };
+    assert_eq!(
+        "Merge topic into master",
+        oneline(&repo, &[], first_parent_dir)
+    );
+
+    let format = log::LogOptions {
+        max_count: Some(1),
+        format: log::Format::Custom(String::from("%an <%ae> %at%n%ad|%p")),
+        ..all.clone()
+    };
+    let output = repo.clone().log(Vec::new(), format).ok().unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!("Merger <merger@example.com> 1600004000", lines[0]);
+    let (date, parents) = lines[1].split_once('|').unwrap();
+    assert_eq!("Sun Sep 13 15:33:20 2020 +0200", date);
+    assert_eq!(2, parents.split(' ').count());
+    assert!(parents.split(' ').all(|parent| parent.len() == 7));
+
+    let medium = log::LogOptions {
+        max_count: Some(2),
+        ..all.clone()
+    };
+    let output = repo.clone().log(Vec::new(), medium).ok().unwrap();
+    assert!(output.starts_with("commit "));
+    assert!(output.contains("\nMerge: "));
+    assert!(output.contains(
+        "\nAuthor: Bob <bob@example.com>\nDate:   Sun Sep 13 15:16:40 2020 +0200\n\n    c.txt\n"
+    ));
+    assert_eq!(1, output.matches("\n\ncommit ").count());
+
+    let oneline = log::LogOptions {
+        format: log::Format::Oneline,
+        ..all
+    };
+    let output = repo.log(vec![String::from("topic")], oneline).ok().unwrap();
+    assert_eq!(3, output.lines().count());
+    assert!(output
+        .lines()
+        .all(|line| line.len() > 8 && line.as_bytes()[7] == b' '));
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::{env, fs};
+use yit::{branch, commandparser, commit, encoding, identity, repo, store, tree};
+
+fn write(store: &store::MemoryStore, parents: &[&String], time: i64) -> String {
+    let tree_hash = tree::Tree::new(HashMap::new()).hash_tree(store);
+    let signature = identity::Signature {
+        name: String::from("A U Thor"),
+        email: String::from("author@example.com"),
+        time,
+        offset: 0,
+    };
+    match commit::write_commit(
+        store,
+        time.to_string(),
+        parents.iter().map(|parent| parent.to_string()).collect(),
+        tree_hash,
+        &signature,
+        &signature,
+    ) {
+        Ok(hash) => hash,
+        Err(_) => panic!("unable to write commit"),
+    }
+}
+
+fn bases(graph: &mut commit::CommitGraph, first: &str, second: &str) -> Vec<String> {
+    match graph.merge_bases(first, second) {
+        Ok(mut bases) => {
+            bases.sort();
+            bases
+        }
+        Err(_) => panic!("unable to compute merge bases"),
+    }
+}
+
+#[test]
+fn test_merge_bases() {
+    let store = store::MemoryStore::new(encoding::Encoding::Yit);
+
+    // criss-cross: each side merges the other's first commit
+    //
+    //   root - a1 - a2 - a3
+    //      \     X
+    //       b1 - b2 - b3
+    let root = write(&store, &[], 100);
+    let a1 = write(&store, &[&root], 200);
+    let b1 = write(&store, &[&root], 210);
+    let a2 = write(&store, &[&a1, &b1], 300);
+    let b2 = write(&store, &[&b1, &a1], 310);
+    let a3 = write(&store, &[&a2], 400);
+    let b3 = write(&store, &[&b2], 410);
+    let mut expected = vec![a1.clone(), b1.clone()];
+    expected.sort();
+
+    let mut graph = commit::CommitGraph::new(&store);
+    assert_eq!(expected, bases(&mut graph, &a3, &b3));
+    assert_eq!(expected, bases(&mut graph, &b2, &a2));
+    assert_eq!(vec![a2.clone()], bases(&mut graph, &a2, &a3));
+    assert_eq!(vec![root.clone()], bases(&mut graph, &a1, &b1));
+    assert_eq!(vec![a3.clone()], bases(&mut graph, &a3, &a3));
+    assert!(matches!(graph.is_ancestor(&b1, &a3), Ok(true)));
+    assert!(matches!(graph.is_ancestor(&a3, &b3), Ok(false)));
+
+    // a commit dated before its parent does not make the parent look like a
+    // separate base
+    let skewed = write(&store, &[&a1], 50);
+    let later = write(&store, &[&skewed], 500);
+    assert_eq!(vec![a1.clone()], bases(&mut graph, &later, &a3));
+
+    let unrelated = write(&store, &[], 600);
+    assert!(bases(&mut graph, &unrelated, &a3).is_empty());
+
+    // a long history with a criss-cross merge near its tips
+    let mut tip = a3.clone();
+    for time in 1000..3000 {
+        tip = write(&store, &[&tip], time);
+    }
+    let c1 = write(&store, &[&tip], 4000);
+    let d1 = write(&store, &[&tip], 4010);
+    let c2 = write(&store, &[&c1, &d1], 4100);
+    let d2 = write(&store, &[&d1, &c1], 4110);
+    let mut expected = vec![c1.clone(), d1.clone()];
+    expected.sort();
+    assert_eq!(expected, bases(&mut graph, &c2, &d2));
+}
+
+#[test]
+fn test_merge_base_command() {
+    let _ = fs::remove_dir_all("tmp/merge_base");
+    fs::create_dir_all("tmp/merge_base").unwrap();
+    env::set_current_dir("tmp/merge_base").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    let commit = |name: &str| {
+        fs::write(name, name).unwrap();
+        assert!(repo.clone().add(String::from(name)).is_ok());
+        assert!(repo.clone().commit(String::from(name)).is_ok());
+    };
+    commit("first");
+    let first = branch::get_commit(String::from("master")).ok().unwrap();
+    assert!(repo.clone().checkout(String::from("topic")).is_ok());
+    commit("second");
+    commit("third");
+    let topic = branch::get_commit(String::from("topic")).ok().unwrap();
+
+    match repo
+        .clone()
+        .merge_bases(String::from("master"), String::from("topic"))
+    {
+        Ok(bases) => assert_eq!(vec![first.clone()], bases),
+        Err(_) => panic!("unable to compute merge bases"),
+    }
+    assert!(matches!(
+        repo.clone()
+            .is_ancestor(String::from("master"), String::from("topic")),
+        Ok(true)
+    ));
+    assert!(matches!(
+        repo.clone()
+            .is_ancestor(String::from("topic"), String::from("master")),
+        Ok(false)
+    ));
+
+    // `diff A...B` compares B with where it forked from A
+    assert_eq!(
+        Ok((first.clone(), String::from("topic"))),
+        commandparser::diff_revisions(&repo, &["master...topic"])
+    );
+    assert_eq!(
+        Ok((String::from("HEAD"), String::from("topic"))),
+        commandparser::diff_revisions(&repo, &["..topic"])
+    );
+
+    // topic is two commits ahead, so merging it is a fast-forward
+    assert!(repo
+        .clone()
+        .merge(String::from("topic"), String::from("master"))
+        .is_ok());
+    assert_eq!(
+        topic,
+        branch::get_commit(String::from("master")).ok().unwrap()
+    );
+}
@@ -0,0 +1,97 @@
+use yit::encoding::{Encoding, ObjectFormat};
+use yit::object::{Commit, Object, ObjectError, Tag, TreeEntry, MODE_FILE, MODE_TREE};
+use yit::store::{ObjectId, ObjectKind};
+
+fn id(hex: &str) -> ObjectId {
+    ObjectId::from_hex(hex, ObjectFormat::Sha1).unwrap()
+}
+
+#[test]
+fn test_object_round_trip() {
+    let objects = vec![
+        Object::Blob(vec![0, 159, 146, 150, b'\n']),
+        Object::Tree(vec![
+            TreeEntry {
+                mode: MODE_TREE,
+                name: String::from("ehoo"),
+                id: id("317d91ad7f66024bf7045b4b7b1590b452bf0641"),
+            },
+            TreeEntry {
+                mode: MODE_FILE,
+                name: String::from("foobar"),
+                id: id("08cf6101416f0ce0dda3c80e627f333854c4085c"),
+            },
+        ]),
+        Object::Commit(Commit {
+            tree: id("ccffa8694608ee46e8dd58e6f277c867770d28c3"),
+            parents: vec![id("d701810b9a74748a4c7cdea803c206f48aeaa12a")],
+            headers: vec![(String::from("encoding"), String::from("UTF-8"))],
+            message: String::from("first line\n\nbody"),
+        }),
+        Object::Tag(Tag {
+            object: id("d701810b9a74748a4c7cdea803c206f48aeaa12a"),
+            kind: ObjectKind::Commit,
+            name: String::from("v1.0"),
+            tagger: None,
+            message: String::from("release"),
+        }),
+    ];
+    for encoding in [Encoding::Yit, Encoding::Git] {
+        for object in &objects {
+            let body = object.serialize(encoding);
+            assert_eq!(
+                *object,
+                Object::parse(object.kind(), &body, encoding, ObjectFormat::Sha1).unwrap()
+            );
+            assert_eq!(
+                *object,
+                Object::decode(&object.encode(encoding), ObjectFormat::Sha1).unwrap()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_legacy_root_commit() {
+    let data = b"commit\nccffa8694608ee46e8dd58e6f277c867770d28c3\n\n\nfirst commit";
+    assert_eq!(
+        Object::Commit(Commit {
+            tree: id("ccffa8694608ee46e8dd58e6f277c867770d28c3"),
+            parents: Vec::new(),
+            headers: Vec::new(),
+            message: String::from("first commit"),
+        }),
+        Object::decode(data, ObjectFormat::Sha1).unwrap()
+    );
+}
+
+#[test]
+fn test_object_parse_errors() {
+    assert_eq!(
+        Err(ObjectError::UnknownType(String::from("blub"))),
+        Object::decode(b"blub 3\0abc", ObjectFormat::Sha1)
+    );
+    assert_eq!(
+        Err(ObjectError::LengthMismatch {
+            expected: 5,
+            actual: 3
+        }),
+        Object::decode(b"blob 5\0abc", ObjectFormat::Sha1)
+    );
+    assert_eq!(
+        Err(ObjectError::MalformedHeader),
+        Object::decode(b"blob", ObjectFormat::Sha1)
+    );
+    assert_eq!(
+        Err(ObjectError::MissingField("tree")),
+        Object::decode(b"commit 17\0author someone\n\nx", ObjectFormat::Sha1)
+    );
+    assert_eq!(
+        Err(ObjectError::InvalidId(String::from("xyz"))),
+        Object::decode(b"commit 10\0tree xyz\n\n", ObjectFormat::Sha1)
+    );
+    assert_eq!(
+        Err(ObjectError::InvalidTreeEntry(0)),
+        Object::decode(b"tree 8\x00100644 a", ObjectFormat::Sha1)
+    );
+}
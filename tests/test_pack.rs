@@ -0,0 +1,146 @@
+use std::{env, fs};
+use yit::store::ObjectStore;
+use yit::{branch, commit, encoding, file, object, pack, repo, store, tree};
+
+fn numbered_lines(count: usize, changed: usize) -> String {
+    let mut content = String::from("");
+    for i in 0..count {
+        if i == changed {
+            content.push_str("this line was changed\n");
+        } else {
+            content.push_str(&format!("line number {} of the file\n", i));
+        }
+    }
+    content
+}
+
+#[test]
+fn test_delta_round_trip() {
+    let base = numbered_lines(200, 1000).into_bytes();
+    let target = numbered_lines(210, 50).into_bytes();
+    let delta = pack::create_delta(&base, &target);
+    assert!(delta.len() < target.len() / 10);
+    assert_eq!(target, pack::apply_delta(&base, &delta).unwrap());
+    assert!(pack::apply_delta(&target, &delta).is_err());
+}
+
+#[test]
+fn test_repack_and_read_from_pack() {
+    let _ = fs::remove_dir_all("tmp/pack");
+    fs::create_dir_all("tmp/pack").unwrap();
+    env::set_current_dir("tmp/pack").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    fs::write("notes.txt", numbered_lines(300, 1000)).unwrap();
+    assert!(repo.clone().add(String::from("notes.txt")).is_ok());
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+    fs::write("notes.txt", numbered_lines(300, 120)).unwrap();
+    assert!(repo.clone().add(String::from("notes.txt")).is_ok());
+    assert!(repo.clone().commit(String::from("second")).is_ok());
+
+    let names = match repo.clone().repack() {
+        Ok(names) => names,
+        Err(_) => panic!("repack failed"),
+    };
+    assert_eq!(1, names.len());
+    let pack_path = String::from(".yit/objects/pack/pack-") + &names[0] + ".pack";
+    // a plain version 2 pack git can read, the encoding kept beside it
+    assert_eq!(b"PACK\0\0\0\x02", &fs::read(&pack_path).unwrap()[..8]);
+    assert_eq!(
+        "yit",
+        fs::read_to_string(pack_path.replace(".pack", ".encoding")).unwrap()
+    );
+    let pack_size = fs::metadata(&pack_path).unwrap().len() as usize;
+    // the second version is stored as a small delta against the first
+    assert!(pack_size < numbered_lines(300, 1000).len() * 3 / 2);
+
+    for entry in fs::read_dir(".yit/objects").unwrap().flatten() {
+        if entry.file_name() != "pack" {
+            fs::remove_dir_all(entry.path()).unwrap();
+        }
+    }
+
+    let store = store::LooseStore::open();
+    assert_eq!(6, store.list().unwrap().len());
+    let head = match branch::get_commit(String::from("master")) {
+        Ok(hash) => hash,
+        Err(_) => panic!("master has no commit"),
+    };
+    let node = match commit::CommitNode::read(&store, &head) {
+        Ok(node) => node,
+        Err(_) => panic!("unable to read commit"),
+    };
+    assert_eq!(1, node.parents.len());
+    let index_map = tree::Tree::tree_to_index_map(&store, node.tree_hash);
+    match file::cat_file(&store, index_map["notes.txt"].clone()) {
+        Ok(content) => assert_eq!(numbered_lines(300, 120).into_bytes(), content),
+        Err(_) => panic!("unable to read packed blob"),
+    }
+
+    fs::write("notes.txt", b"scratch").unwrap();
+    assert!(repo.checkout(String::from("master")).is_ok());
+    assert_eq!(
+        numbered_lines(300, 120).into_bytes(),
+        fs::read("notes.txt").unwrap()
+    );
+
+    // a git encoded tree packed next to yit objects still parses as one
+    let git_store = store::LooseStore::new(String::from(".yit/objects"), encoding::Encoding::Git);
+    let blob = git_store.put(store::ObjectKind::Blob, b"shared").unwrap();
+    let git_tree = git_store
+        .write(&object::Object::Tree(vec![object::TreeEntry {
+            mode: 0o100644,
+            name: String::from("a b"),
+            id: blob.clone(),
+        }]))
+        .unwrap();
+    let yit_blob = store.put(store::ObjectKind::Blob, b"yit").unwrap();
+    let objects = vec![
+        (git_tree.clone(), String::from("")),
+        (blob.clone(), String::from("a b")),
+        (yit_blob.clone(), String::from("yit")),
+    ];
+    assert_eq!(2, store.write_pack(&objects).unwrap().len());
+    for id in &[git_tree.clone(), blob, yit_blob] {
+        fs::remove_file(format!(".yit/objects/{}/{}", id.dir(), id.file())).unwrap();
+    }
+    match store.read(&git_tree) {
+        Ok(object::Object::Tree(entries)) => assert_eq!("a b", entries[0].name),
+        _ => panic!("unable to read packed git tree"),
+    }
+    // big blobs are streamed into the pack and submodule commits skipped
+    let big: Vec<u8> = (0..3 << 20).map(|i| (i % 251) as u8).collect();
+    let big_blob = git_store.put(store::ObjectKind::Blob, &big).unwrap();
+    let submodule = store::ObjectId::from_hex(
+        "0123456789abcdef0123456789abcdef01234567",
+        encoding::ObjectFormat::Sha1,
+    )
+    .unwrap();
+    let with_submodule = git_store
+        .write(&object::Object::Tree(vec![
+            object::TreeEntry {
+                mode: object::MODE_FILE,
+                name: String::from("big"),
+                id: big_blob.clone(),
+            },
+            object::TreeEntry {
+                mode: object::MODE_GITLINK,
+                name: String::from("sub"),
+                id: submodule,
+            },
+        ]))
+        .unwrap();
+    let objects = pack::reachable_objects(&store, std::slice::from_ref(&with_submodule)).unwrap();
+    assert_eq!(2, objects.len());
+    assert_eq!(1, store.write_pack(&objects).unwrap().len());
+    for id in &[with_submodule, big_blob.clone()] {
+        fs::remove_file(format!(".yit/objects/{}/{}", id.dir(), id.file())).unwrap();
+    }
+    assert_eq!(big, store.get(&big_blob).unwrap().1);
+
+    // packs are renamed into place, leaving nothing half written behind
+    for entry in fs::read_dir(".yit/objects/pack").unwrap().flatten() {
+        assert!(!entry.file_name().to_string_lossy().ends_with(".lock"));
+    }
+}
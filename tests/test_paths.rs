@@ -0,0 +1,172 @@
+use std::{env, fs};
+use yit::encoding::{Encoding, ObjectFormat};
+use yit::object::{self, Object, TreeEntry};
+use yit::store::ObjectStore;
+use yit::{branch, commit, repo, status, store, tree};
+
+const NAMES: [&str; 6] = [
+    "my notes.txt",
+    "line\nbreak",
+    "tab\tand \"quotes\"",
+    "back\\slash",
+    "ünïcødé ✓.md",
+    "dir with space/naïve file",
+];
+
+#[test]
+fn test_quote_name() {
+    assert_eq!("plain.txt", object::quote_name("plain.txt"));
+    assert_eq!("ü.txt", object::quote_name("ü.txt"));
+    assert_eq!("\"my notes.txt\"", object::quote_name("my notes.txt"));
+    assert_eq!(
+        "\"a\\nb\\t\\\"c\\\"\\\\d\\001\"",
+        object::quote_name("a\nb\t\"c\"\\d\u{1}")
+    );
+    assert_eq!("\"\\\"quoted\"", object::quote_name("\"quoted"));
+    for name in NAMES {
+        let quoted = object::quote_name(name) + " rest";
+        assert_eq!(
+            Some((String::from(name), " rest")),
+            object::unquote_name(&quoted)
+        );
+    }
+    assert_eq!(None, object::unquote_name("\"unterminated"));
+
+    let id = store::ObjectId::from_hex(
+        "ccffa8694608ee46e8dd58e6f277c867770d28c3",
+        ObjectFormat::Sha1,
+    )
+    .unwrap();
+    let entries: Vec<TreeEntry> = NAMES
+        .iter()
+        .map(|name| TreeEntry {
+            mode: object::MODE_FILE,
+            name: String::from(*name),
+            id: id.clone(),
+        })
+        .collect();
+    let tree = Object::Tree(entries);
+    for encoding in [Encoding::Yit, Encoding::Git] {
+        let data = tree.serialize(encoding);
+        assert_eq!(
+            tree,
+            Object::parse(store::ObjectKind::Tree, &data, encoding, ObjectFormat::Sha1).unwrap()
+        );
+    }
+    // trees of plain names are written as before
+    let plain = b"blob a.txt ccffa8694608ee46e8dd58e6f277c867770d28c3\n";
+    let parsed = Object::parse(
+        store::ObjectKind::Tree,
+        plain,
+        Encoding::Yit,
+        ObjectFormat::Sha1,
+    )
+    .unwrap();
+    assert_eq!(plain.to_vec(), parsed.serialize(Encoding::Yit));
+}
+
+#[test]
+fn test_unsafe_tree_paths() {
+    for encoding in [Encoding::Yit, Encoding::Git] {
+        let store = store::MemoryStore::new(encoding);
+        let blob = store.put(store::ObjectKind::Blob, b"payload").unwrap();
+        let entry = |mode, name: &str, id: &store::ObjectId| TreeEntry {
+            mode,
+            name: String::from(name),
+            id: id.clone(),
+        };
+        let put_tree = |entries| {
+            store
+                .put(
+                    store::ObjectKind::Tree,
+                    &Object::Tree(entries).serialize(encoding),
+                )
+                .unwrap()
+        };
+        let inner = put_tree(vec![entry(object::MODE_FILE, "../../escape", &blob)]);
+        let root = put_tree(vec![
+            entry(object::MODE_FILE, "kept.txt", &blob),
+            entry(object::MODE_FILE, "../escape", &blob),
+            entry(object::MODE_FILE, "/tmp/absolute", &blob),
+            entry(object::MODE_FILE, ".yit/HEAD", &blob),
+            entry(object::MODE_FILE, "dir/.YIT/config", &blob),
+            entry(object::MODE_FILE, "./dot", &blob),
+            entry(object::MODE_TREE, "..", &inner),
+            entry(object::MODE_TREE, ".yit", &inner),
+            entry(object::MODE_TREE, "sub", &inner),
+        ]);
+        let paths: Vec<String> = tree::Tree::tree_to_index_map(&store, root.to_string())
+            .into_keys()
+            .collect();
+        assert_eq!(vec!["kept.txt"], paths);
+    }
+}
+
+#[test]
+fn test_special_paths() {
+    let _ = fs::remove_dir_all("tmp/paths");
+    fs::create_dir_all("tmp/paths/dir with space").unwrap();
+    env::set_current_dir("tmp/paths").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    for name in NAMES {
+        fs::write(name, String::from("content of ") + name).unwrap();
+    }
+    match repo
+        .clone()
+        .add_paths(vec![String::from(".")], repo::AddOptions::default())
+    {
+        Ok(staged) => assert_eq!(NAMES.len(), staged.len()),
+        Err(_) => panic!("add failed"),
+    }
+    assert!(repo.clone().commit(String::from("odd names")).is_ok());
+
+    let store = store::LooseStore::open();
+    let head = branch::get_commit(String::from("master")).ok().unwrap();
+    let tree_hash = match commit::CommitNode::read(&store, &head) {
+        Ok(node) => node.tree_hash,
+        Err(_) => panic!("unable to read the commit"),
+    };
+    let mut paths: Vec<String> = tree::Tree::tree_to_index_map(&store, tree_hash)
+        .into_keys()
+        .collect();
+    paths.sort();
+    let mut expected: Vec<String> = NAMES.iter().map(|name| String::from(*name)).collect();
+    expected.sort();
+    assert_eq!(expected, paths);
+    match repo.clone().status() {
+        Ok(result) => {
+            assert!(result.entries.is_empty());
+            assert!(result.untracked.is_empty());
+        }
+        Err(_) => panic!("unable to read status"),
+    }
+
+    fs::write("my notes.txt", "changed").unwrap();
+    assert!(repo.clone().add(String::from("my notes.txt")).is_ok());
+    match repo.clone().status() {
+        Ok(result) => assert_eq!(
+            "M  my notes.txt\n",
+            status::format_status(&result, status::Format::Porcelain, false)
+        ),
+        Err(_) => panic!("unable to read status"),
+    }
+    assert!(repo.clone().commit(String::from("change")).is_ok());
+
+    assert!(repo.clone().checkout(head).is_ok());
+    assert_eq!(
+        "content of my notes.txt",
+        fs::read_to_string("my notes.txt").unwrap()
+    );
+    fs::remove_dir_all("dir with space").unwrap();
+    fs::write("ünïcødé ✓.md", "overwritten").unwrap();
+    assert!(repo.checkout(String::from("master")).is_ok());
+    assert_eq!("changed", fs::read_to_string("my notes.txt").unwrap());
+    for name in &NAMES[1..] {
+        assert_eq!(
+            String::from("content of ") + name,
+            fs::read_to_string(name).unwrap()
+        );
+    }
+}
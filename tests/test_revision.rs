@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::{env, fs};
+use yit::store::ObjectStore;
+use yit::{branch, encoding, object, repo, revision, store};
+
+#[test]
+fn test_resolve_prefix() {
+    let store = store::MemoryStore::new(encoding::Encoding::Git);
+    // store blobs until two of them share their first four digits
+    let mut seen: HashMap<String, store::ObjectId> = HashMap::new();
+    let (first, second) = (0..)
+        .find_map(|i: u32| {
+            let id = store
+                .put(store::ObjectKind::Blob, i.to_string().as_bytes())
+                .unwrap();
+            let prefix = String::from(&id.as_str()[..4]);
+            seen.insert(prefix, id.clone()).map(|other| (other, id))
+        })
+        .unwrap();
+    let prefix = &first.as_str()[..4];
+
+    match revision::resolve(&store, prefix) {
+        Err(revision::RevisionError::Ambiguous(name, mut candidates)) => {
+            assert_eq!(prefix, name);
+            let mut expected = vec![first.clone(), second.clone()];
+            expected.sort();
+            candidates.sort();
+            assert_eq!(expected, candidates);
+        }
+        _ => panic!("expected an ambiguous prefix"),
+    }
+    let unique = if first.as_str()[4..6] != second.as_str()[4..6] {
+        &first.as_str()[..6]
+    } else {
+        first.as_str()
+    };
+    assert_eq!(first, revision::resolve(&store, unique).unwrap());
+    assert_eq!(
+        first,
+        revision::resolve(&store, &first.as_str().to_uppercase()).unwrap()
+    );
+    assert!(matches!(
+        revision::resolve(&store, &first.as_str()[..3]),
+        Err(revision::RevisionError::NotFound(_))
+    ));
+    assert!(matches!(
+        revision::resolve_commit(&store, first.as_str()),
+        Err(revision::RevisionError::NotACommit(_))
+    ));
+}
+
+#[test]
+fn test_prefix_shared_with_a_blob() {
+    let store = store::MemoryStore::new(encoding::Encoding::Git);
+    let tree = store.write(&object::Object::Tree(Vec::new())).unwrap();
+    let commit = store
+        .write(&object::Object::Commit(object::Commit {
+            tree,
+            parents: Vec::new(),
+            headers: Vec::new(),
+            message: String::from("first"),
+        }))
+        .unwrap();
+    let prefix = &commit.as_str()[..4];
+    let blob = (0..)
+        .map(|i: u32| {
+            store
+                .put(store::ObjectKind::Blob, i.to_string().as_bytes())
+                .unwrap()
+        })
+        .find(|id| id.as_str().starts_with(prefix))
+        .unwrap();
+
+    match revision::resolve(&store, prefix) {
+        Err(revision::RevisionError::Ambiguous(_, candidates)) => {
+            assert!(candidates.contains(&blob));
+        }
+        _ => panic!("expected an ambiguous prefix"),
+    }
+    // where only a commit will do, the blob doesn't count
+    assert_eq!(commit, revision::resolve_commit(&store, prefix).unwrap());
+}
+
+#[test]
+fn test_abbreviated_checkout() {
+    let _ = fs::remove_dir_all("tmp/revision");
+    fs::create_dir_all("tmp/revision").unwrap();
+    env::set_current_dir("tmp/revision").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    fs::write("file", b"first").unwrap();
+    assert!(repo.clone().add(String::from("file")).is_ok());
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+    let first = branch::get_commit(String::from("master")).ok().unwrap();
+    fs::write("file", b"second").unwrap();
+    assert!(repo.clone().add(String::from("file")).is_ok());
+    assert!(repo.clone().commit(String::from("second")).is_ok());
+
+    let store = store::LooseStore::open();
+    assert_eq!(
+        first,
+        revision::resolve_commit(&store, &first[..7])
+            .unwrap()
+            .to_string()
+    );
+    assert!(repo
+        .clone()
+        .diff(String::from(&first[..7]), String::from("master"))
+        .is_ok());
+
+    // an abbreviated id checks out a detached HEAD
+    assert!(repo.clone().checkout(String::from(&first[..7])).is_ok());
+    assert_eq!(first, fs::read_to_string(".yit/HEAD").unwrap());
+    assert_eq!(b"first".to_vec(), fs::read("file").unwrap());
+    fs::write("other", b"detached").unwrap();
+    assert!(repo.clone().add(String::from("other")).is_ok());
+    assert!(repo.clone().commit(String::from("detached")).is_ok());
+    let detached = fs::read_to_string(".yit/HEAD").unwrap();
+    assert_ne!(first, detached);
+    assert!(store::ObjectId::from_hex(&detached, encoding::ObjectFormat::Sha1).is_some());
+
+    assert!(repo.clone().checkout(String::from("master")).is_ok());
+    assert_eq!(b"second".to_vec(), fs::read("file").unwrap());
+    assert_eq!(
+        ".yit/refs/heads/master",
+        fs::read_to_string(".yit/HEAD").unwrap()
+    );
+
+    let second = branch::get_commit(String::from("master")).ok().unwrap();
+    let resolve = |expression: &str| match revision::resolve_commit(&store, expression) {
+        Ok(id) => id.to_string(),
+        Err(err) => panic!("{}: {}", expression, err),
+    };
+    assert_eq!(second, resolve("HEAD"));
+    assert_eq!(second, resolve("@"));
+    assert_eq!(first, resolve("HEAD~1"));
+    assert_eq!(first, resolve("HEAD^"));
+    assert_eq!(first, resolve("master~^0"));
+    assert_eq!(first, resolve(&(String::from(&detached[..7]) + "^1")));
+    for invalid in ["HEAD~2", "HEAD^2", "master@{2}", "HEAD^x", "@{-9}"] {
+        assert!(matches!(
+            revision::resolve_commit(&store, invalid),
+            Err(revision::RevisionError::Invalid(_))
+        ));
+    }
+
+    // reflogs: master moved twice, HEAD also moved on each checkout
+    assert_eq!(second, resolve("master@{0}"));
+    assert_eq!(first, resolve("master@{1}"));
+    assert_eq!(first, resolve("@{1}"));
+    assert_eq!(first, resolve("master@{1}~0"));
+    assert_eq!(detached, resolve("HEAD@{1}"));
+    assert_eq!(second, resolve("master@{now}"));
+    assert_eq!(detached, resolve("@{-1}"));
+    assert_eq!(second, resolve("@{-2}"));
+    assert_eq!(
+        revision::CommitSet {
+            include: vec![resolve_id(&second)],
+            exclude: vec![resolve_id(&first)],
+        },
+        revision::resolve_set(&store, &[String::from("HEAD~1..master")]).unwrap()
+    );
+
+    // `checkout @{-1}` goes back to where HEAD was before
+    assert!(repo.clone().checkout(String::from("@{-1}")).is_ok());
+    assert_eq!(detached, fs::read_to_string(".yit/HEAD").unwrap());
+    assert!(repo.checkout(String::from("@{-1}")).is_ok());
+    assert_eq!(
+        ".yit/refs/heads/master",
+        fs::read_to_string(".yit/HEAD").unwrap()
+    );
+}
+
+fn resolve_id(hash: &str) -> store::ObjectId {
+    store::ObjectId::from_hex(hash, encoding::ObjectFormat::Sha1).unwrap()
+}
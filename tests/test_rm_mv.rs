@@ -0,0 +1,99 @@
+use std::path::Path;
+use std::{env, fs};
+use yit::{branch, commit, repo, store, tree};
+
+fn committed_files(store: &store::LooseStore) -> Vec<String> {
+    let head = branch::get_commit(String::from("master")).ok().unwrap();
+    let node = match commit::CommitNode::read(store, &head) {
+        Ok(node) => node,
+        Err(_) => panic!("unable to read commit"),
+    };
+    let mut files: Vec<String> = tree::Tree::tree_to_index_map(store, node.tree_hash)
+        .into_keys()
+        .collect();
+    files.sort();
+    files
+}
+
+fn staged(repo: &repo::Repository) -> Vec<String> {
+    match repo.clone().staged_changes() {
+        Ok(changes) => changes
+            .into_iter()
+            .map(|(status, path)| status + " " + &path)
+            .collect(),
+        Err(_) => panic!("unable to list staged changes"),
+    }
+}
+
+#[test]
+fn test_rm_mv() {
+    let _ = fs::remove_dir_all("tmp/rm_mv");
+    fs::create_dir_all("tmp/rm_mv/dir").unwrap();
+    env::set_current_dir("tmp/rm_mv").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    for name in ["a.txt", "b.txt", "dir/c.txt", "dir/d.txt"] {
+        fs::write(name, name).unwrap();
+        assert!(repo.clone().add(String::from(name)).is_ok());
+    }
+    assert!(repo.clone().commit(String::from("initial")).is_ok());
+    let store = store::LooseStore::open();
+
+    assert!(matches!(
+        repo.clone()
+            .rm(vec![String::from("b.txt"), String::from("missing.txt")], false),
+        Err(repo::RepoError::UntrackedPath(path)) if path == "missing.txt"
+    ));
+    assert!(Path::new("b.txt").exists());
+
+    assert!(repo.clone().rm(vec![String::from("b.txt")], false).is_ok());
+    assert!(repo.clone().rm(vec![String::from("a.txt")], true).is_ok());
+    assert!(!Path::new("b.txt").exists());
+    assert!(Path::new("a.txt").exists());
+    assert_eq!(vec!["deleted a.txt", "deleted b.txt"], staged(&repo));
+    // staged removals survive switching branches, files and all
+    assert!(repo.clone().checkout(String::from("other")).is_ok());
+    assert!(!Path::new("b.txt").exists());
+    assert_eq!(vec!["deleted a.txt", "deleted b.txt"], staged(&repo));
+    assert!(repo.clone().checkout(String::from("master")).is_ok());
+    assert_eq!(vec!["deleted a.txt", "deleted b.txt"], staged(&repo));
+    assert!(repo.clone().commit(String::from("remove")).is_ok());
+    assert_eq!(vec!["dir/c.txt", "dir/d.txt"], committed_files(&store));
+
+    // a file that was only staged is simply forgotten
+    fs::write("e.txt", "e").unwrap();
+    assert!(repo.clone().add(String::from("e.txt")).is_ok());
+    assert!(repo.clone().rm(vec![String::from("e.txt")], true).is_ok());
+    assert!(staged(&repo).is_empty());
+    assert!(repo.clone().rm(vec![String::from("e.txt")], true).is_err());
+
+    assert!(matches!(
+        repo.clone().mv(String::from("dir"), String::from("a.txt")),
+        Err(repo::RepoError::DestinationExists(_))
+    ));
+    assert!(repo
+        .clone()
+        .mv(String::from("dir/c.txt"), String::from("c.txt"))
+        .is_ok());
+    assert!(!Path::new("dir/c.txt").exists());
+    assert_eq!("dir/c.txt", fs::read_to_string("c.txt").unwrap());
+    assert_eq!(vec!["new file c.txt", "deleted dir/c.txt"], staged(&repo));
+    fs::create_dir("moved").unwrap();
+    assert!(repo
+        .clone()
+        .mv(String::from("dir"), String::from("moved"))
+        .is_ok());
+    assert!(Path::new("moved/dir/d.txt").exists());
+    assert!(repo.clone().commit(String::from("move")).is_ok());
+    assert_eq!(vec!["c.txt", "moved/dir/d.txt"], committed_files(&store));
+
+    // a removed file can be added back
+    assert!(repo.clone().add(String::from("a.txt")).is_ok());
+    assert_eq!(vec!["new file a.txt"], staged(&repo));
+    assert!(repo.clone().commit(String::from("restore")).is_ok());
+    assert_eq!(
+        vec!["a.txt", "c.txt", "moved/dir/d.txt"],
+        committed_files(&store)
+    );
+}
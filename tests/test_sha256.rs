@@ -0,0 +1,78 @@
+use std::{env, fs, path};
+use yit::encoding::{Encoding, ObjectFormat};
+use yit::store::ObjectStore;
+use yit::{branch, commit, config, repo, store, tree};
+
+#[test]
+fn test_sha256_ids() {
+    let store = store::MemoryStore::new(Encoding::Git).with_format(ObjectFormat::Sha256);
+    let id = store.put(store::ObjectKind::Blob, b"hello\n").unwrap();
+    // `git hash-object` in a repository created with --object-format=sha256
+    assert_eq!(
+        "2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4",
+        id.as_str()
+    );
+    assert_eq!(
+        id,
+        store::ObjectId::from_hex(id.as_str(), ObjectFormat::Sha256).unwrap()
+    );
+    assert!(store::ObjectId::from_hex(&id.as_str()[..50], ObjectFormat::Sha256).is_none());
+    // sha-1 ids don't belong in a sha-256 repository, nor the other way round
+    assert!(store::ObjectId::from_hex(&id.as_str()[..40], ObjectFormat::Sha256).is_none());
+    assert!(store::ObjectId::from_hex(id.as_str(), ObjectFormat::Sha1).is_none());
+}
+
+#[test]
+fn test_sha256_repository() {
+    let _ = fs::remove_dir_all("tmp/sha256");
+    fs::create_dir_all("tmp/sha256").unwrap();
+    env::set_current_dir("tmp/sha256").unwrap();
+
+    fs::write("foobar", b"hello\n").unwrap();
+    fs::create_dir("ehoo").unwrap();
+    fs::write("ehoo/file", b"nested\n").unwrap();
+
+    let repo = repo::Repository::new();
+    let mut config = config::Config::new();
+    config.set("core.gitcompat", "true");
+    config.set("extensions.objectformat", "sha256");
+    assert!(repo.clone().init_with(config).is_ok());
+    assert!(repo.clone().add(String::from("foobar")).is_ok());
+    assert!(repo.clone().add(String::from("ehoo/file")).is_ok());
+    assert!(repo.clone().commit(String::from("sha256")).is_ok());
+
+    let commit_hash = match branch::get_commit(String::from("master")) {
+        Ok(hash) => hash,
+        Err(_) => panic!("master has no commit"),
+    };
+    assert_eq!(64, commit_hash.len());
+    let store = store::LooseStore::open();
+    let commit_node = match commit::CommitNode::read(&store, &commit_hash) {
+        Ok(node) => node,
+        Err(_) => panic!("unable to read commit"),
+    };
+    // same tree as `git write-tree` in a sha256 repository
+    assert_eq!(
+        "73e5b26d25f4c3fe68ba7304a811f1793b33aea453e3155b4760ada3a1923fd8",
+        commit_node.tree_hash
+    );
+    let index_map = tree::Tree::tree_to_index_map(&store, commit_node.tree_hash.clone());
+    assert_eq!(
+        "901dd740cdbc4bf5ec97deb7308876c6e3b326fcbf34e4e86686f76e01e8da82",
+        index_map["ehoo/file"]
+    );
+    assert!(path::Path::new(
+        ".yit/objects/2c/f8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4"
+    )
+    .exists());
+
+    // packs carry 32 byte ids
+    assert!(repo.clone().gc(Some(String::from("now"))).is_ok());
+    match repo.clone().fsck() {
+        Ok(report) => assert!(report.problems.is_empty()),
+        Err(_) => panic!("fsck failed"),
+    }
+    fs::write("foobar", b"scratch").unwrap();
+    assert!(repo.checkout(String::from("master")).is_ok());
+    assert_eq!(b"hello\n".to_vec(), fs::read("foobar").unwrap());
+}
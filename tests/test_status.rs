@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::{env, fs};
+use yit::{index, repo, status};
+
+fn map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+    entries
+        .iter()
+        .map(|(path, hash)| (String::from(*path), String::from(*hash)))
+        .collect()
+}
+
+#[test]
+fn test_compare() {
+    let head = map(&[("same", "1"), ("staged", "1"), ("both", "1"), ("gone", "1")]);
+    let index_map = map(&[
+        ("staged", "2"),
+        ("both", "2"),
+        ("added", "3"),
+        ("gone", index::DELETED),
+    ]);
+    let worktree = map(&[
+        ("same", "1"),
+        ("staged", "2"),
+        ("both", "4"),
+        ("gone", "1"),
+        ("new", "5"),
+    ]);
+    let (entries, untracked) = status::compare(&head, &index_map, &worktree);
+    let codes: Vec<String> = entries
+        .iter()
+        .map(|entry| format!("{:?} {:?} {}", entry.staged, entry.unstaged, entry.path))
+        .collect();
+    assert_eq!(
+        vec![
+            "Some(Added) Some(Deleted) added",
+            "Some(Modified) Some(Modified) both",
+            "Some(Deleted) None gone",
+            "Some(Modified) None staged",
+        ],
+        codes
+    );
+    // a file removed with --cached is untracked again
+    assert_eq!(vec!["gone", "new"], untracked);
+
+    let result = status::Status {
+        branch: Some(String::from("master")),
+        head: String::from("0123456789"),
+        entries,
+        untracked,
+    };
+    assert_eq!(
+        "## master\nAD added\nMM both\nD  gone\nM  staged\n?? gone\n?? new\n",
+        status::format_status(&result, status::Format::Short, true)
+    );
+    assert_eq!(
+        status::format_status(&result, status::Format::Short, false),
+        status::format_status(&result, status::Format::Porcelain, false)
+    );
+    let long = status::format_status(&result, status::Format::Long, false);
+    assert!(long.starts_with("On branch master\n\nChanges to be committed:\n"));
+    assert!(long.contains("\tnew file:   added\n"));
+    assert!(long.contains("\nChanges not staged for commit:\n\tdeleted:    added\n"));
+    assert!(long.ends_with("\nUntracked files:\n\tgone\n\tnew\n\n"));
+
+    let clean = status::Status {
+        branch: None,
+        head: String::from("0123456789"),
+        ..status::Status::default()
+    };
+    assert_eq!(
+        "HEAD detached at 0123456\n\nnothing to commit, working tree clean\n",
+        status::format_status(&clean, status::Format::Long, false)
+    );
+    assert_eq!(
+        "## HEAD (no branch)\n",
+        status::format_status(&clean, status::Format::Porcelain, true)
+    );
+}
+
+#[test]
+fn test_status() {
+    let _ = fs::remove_dir_all("tmp/status");
+    fs::create_dir_all("tmp/status/dir").unwrap();
+    env::set_current_dir("tmp/status").unwrap();
+
+    let repo = repo::Repository::new();
+    assert!(repo.clone().init().is_ok());
+    let short = |repo: &repo::Repository| match repo.clone().status() {
+        Ok(result) => status::format_status(&result, status::Format::Short, true),
+        Err(_) => panic!("unable to read status"),
+    };
+    fs::write("a.txt", "a").unwrap();
+    fs::write("dir/b.txt", "b").unwrap();
+    assert_eq!(
+        "## No commits yet on master\n?? a.txt\n?? dir/b.txt\n",
+        short(&repo)
+    );
+    assert!(repo.clone().add(String::from("a.txt")).is_ok());
+    assert!(repo.clone().add(String::from("dir/b.txt")).is_ok());
+    assert_eq!(
+        "## No commits yet on master\nA  a.txt\nA  dir/b.txt\n",
+        short(&repo)
+    );
+    assert!(repo.clone().commit(String::from("first")).is_ok());
+    assert_eq!("## master\n", short(&repo));
+
+    fs::write("a.txt", "changed").unwrap();
+    fs::remove_file("dir/b.txt").unwrap();
+    fs::write("c.txt", "c").unwrap();
+    assert_eq!(
+        "## master\n M a.txt\n D dir/b.txt\n?? c.txt\n",
+        short(&repo)
+    );
+    assert!(repo.clone().add(String::from("a.txt")).is_ok());
+    assert!(repo
+        .clone()
+        .rm(vec![String::from("dir/b.txt")], false)
+        .is_ok());
+    fs::write("a.txt", "changed again").unwrap();
+    assert_eq!(
+        "## master\nMM a.txt\nD  dir/b.txt\n?? c.txt\n",
+        short(&repo)
+    );
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use yit::store::ObjectStore;
+use yit::{commit, encoding, file, identity, store, tree};
+
+#[test]
+fn test_memory_store() {
+    let store = store::MemoryStore::new(encoding::Encoding::Yit);
+    let foobar = store
+        .put(store::ObjectKind::Blob, b"test content")
+        .unwrap()
+        .to_string();
+    let daaa = store
+        .put(store::ObjectKind::Blob, b"another test")
+        .unwrap()
+        .to_string();
+
+    let mut index_map = HashMap::new();
+    index_map.insert(String::from("foobar"), foobar.clone());
+    index_map.insert(String::from("ehoo/daaa"), daaa.clone());
+    let tree_hash = tree::Tree::new(index_map.clone()).hash_tree(&store);
+    let author =
+        identity::Signature::parse("A U Thor <author@example.com> 1600000000 +0200").unwrap();
+    let first = commit::write_commit(
+        &store,
+        String::from("first"),
+        Vec::new(),
+        tree_hash.clone(),
+        &author,
+        &author,
+    );
+    let first = match first {
+        Ok(hash) => hash,
+        Err(_) => panic!("unable to write commit"),
+    };
+    let second = match commit::write_commit(
+        &store,
+        String::from("second"),
+        vec![first.clone()],
+        tree_hash.clone(),
+        &author,
+        &author,
+    ) {
+        Ok(hash) => hash,
+        Err(_) => panic!("unable to write commit"),
+    };
+
+    let node = match commit::CommitNode::read(&store, &second) {
+        Ok(node) => node,
+        Err(_) => panic!("unable to read commit"),
+    };
+    assert_eq!(tree_hash, node.tree_hash);
+    assert_eq!(1, node.parents.len());
+    assert_eq!(first, node.parents[0]);
+    assert_eq!(Some(author.clone()), node.author);
+    assert_eq!(Some(author), node.committer);
+    assert_eq!(index_map, tree::Tree::tree_to_index_map(&store, tree_hash));
+    match file::cat_file(&store, foobar.clone()) {
+        Ok(content) => assert_eq!(b"test content".to_vec(), content),
+        Err(_) => panic!("unable to read blob"),
+    }
+
+    // blobs, one subtree, the root tree and two commits
+    assert_eq!(6, store.list().unwrap().len());
+    assert!(store.exists(&store::ObjectId::from_hex(&daaa, store.format()).unwrap()));
+    assert_eq!(
+        store
+            .hash(store::ObjectKind::Blob, b"test content")
+            .to_string(),
+        foobar
+    );
+}